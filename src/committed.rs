@@ -0,0 +1,812 @@
+use halo2_base::halo2_proofs::{
+    circuit::{AssignedCell, Region, Value},
+    plonk::{Advice, Column, ConstraintSystem, Error, Expression, Fixed, Selector},
+    poly::Rotation,
+};
+use halo2_base::utils::PrimeField;
+use std::marker::PhantomData;
+
+use crate::RegexDef;
+
+/// The table cells of a [`CommittedRegexCheckConfig`], for the caller to commit to.
+#[derive(Debug, Clone)]
+pub struct AssignedCommittedTable<F: PrimeField> {
+    /// `[enabled, prev_state, next_state, character]` of every transition row, including the
+    /// disabled padding rows.
+    pub transitions: Vec<[AssignedCell<F, F>; 4]>,
+    /// `[enabled, state]` of every accepted-state row, including the disabled padding rows.
+    pub accepted_states: Vec<[AssignedCell<F, F>; 2]>,
+}
+
+/// Checks the input against a DFA that is a private witness as well: the transition and accepted
+/// state tables are assigned to advice columns and referenced by dynamic lookups, so only their
+/// sizes and the first state are fixed at configuration.
+///
+/// The lookup tables are only the rows [`Self::assign_table`] assigns: rows
+/// `0..=max_transitions` of the transition columns and rows `0..=max_accepted_states` of the
+/// accepted-state columns, flagged by fixed columns. Every other row of these columns is excluded
+/// from the lookups, so the cells of [`AssignedCommittedTable`] are the whole table.
+///
+/// Soundness caveats:
+/// - The proof only shows that the input is accepted by the assigned table, which the prover
+///   chooses. Without binding the cells of [`AssignedCommittedTable`] to a public commitment
+///   (e.g. an in-circuit hash exposed as an instance), a table accepting everything passes.
+/// - The table is not checked to be deterministic or consistent with `largest_state_val`; with
+///   several rows for one `(state, char)` the proof shows that some run is accepted.
+/// - Table rows flagged as disabled (0) are never matched by enabled input rows. The input rows
+///   after the input are disabled up to the last row and hold the character 0, so the returned
+///   character cells hold the checked input followed by zeros.
+///
+/// [`Self::configure_well_formed`] closes the second gap over the same assigned rows, so a
+/// single verifying key serves any deterministic table within the configured sizes; binding
+/// those cells to a public commitment stays the caller's job.
+#[derive(Debug, Clone)]
+pub struct CommittedRegexCheckConfig<F: PrimeField> {
+    characters: Column<Advice>,
+    state: Column<Advice>,
+    char_enable: Column<Advice>,
+    q_first: Selector,
+    not_q_first: Selector,
+    // The row after the last character.
+    q_last: Selector,
+    table_enabled: Column<Advice>,
+    table_prev_state: Column<Advice>,
+    table_next_state: Column<Advice>,
+    table_character: Column<Advice>,
+    accepted_enabled: Column<Advice>,
+    accepted_states: Column<Advice>,
+    // 1 on the assigned rows of each table, so that other rows of the table columns are not
+    // part of the lookup tables.
+    q_transition_rows: Column<Fixed>,
+    q_accepted_rows: Column<Fixed>,
+    first_state_val: u64,
+    max_chars_size: usize,
+    max_transitions: usize,
+    max_accepted_states: usize,
+    well_formed: Option<WellFormedConfig>,
+    _marker: PhantomData<F>,
+}
+
+/// The columns of [`CommittedRegexCheckConfig::configure_well_formed`].
+#[derive(Debug, Clone)]
+struct WellFormedConfig {
+    q_table: Selector,
+    q_table_end: Selector,
+    same_prev: Column<Advice>,
+    byte_range: Column<Fixed>,
+}
+
+impl<F: PrimeField> CommittedRegexCheckConfig<F> {
+    pub fn configure(
+        meta: &mut ConstraintSystem<F>,
+        first_state_val: u64,
+        max_chars_size: usize,
+        max_transitions: usize,
+        max_accepted_states: usize,
+    ) -> Self {
+        let characters = meta.advice_column();
+        let state = meta.advice_column();
+        let char_enable = meta.advice_column();
+        let q_first = meta.complex_selector();
+        let not_q_first = meta.complex_selector();
+        let q_last = meta.selector();
+        let table_enabled = meta.advice_column();
+        let table_prev_state = meta.advice_column();
+        let table_next_state = meta.advice_column();
+        let table_character = meta.advice_column();
+        let accepted_enabled = meta.advice_column();
+        let accepted_states = meta.advice_column();
+        let q_transition_rows = meta.fixed_column();
+        let q_accepted_rows = meta.fixed_column();
+        for column in [
+            characters,
+            state,
+            char_enable,
+            table_enabled,
+            table_prev_state,
+            table_next_state,
+            table_character,
+            accepted_enabled,
+            accepted_states,
+        ] {
+            meta.enable_equality(column);
+        }
+
+        meta.create_gate("The state must start from the first state", |meta| {
+            let q_frist = meta.query_selector(q_first);
+            let cur_state = meta.query_advice(state, Rotation::cur());
+            let cur_enable = meta.query_advice(char_enable, Rotation::cur());
+            let not_cur_enable = Expression::Constant(F::from(1)) - cur_enable.clone();
+            // Also on padding, so that an empty input is checked from the first state.
+            vec![
+                q_frist.clone() * (cur_state - Expression::Constant(F::from(first_state_val))),
+                q_frist * cur_enable * not_cur_enable,
+            ]
+        });
+
+        meta.create_gate("The transition of enable flags", |meta| {
+            let not_q_frist = meta.query_selector(not_q_first);
+            let cur_enable = meta.query_advice(char_enable, Rotation::cur());
+            let not_cur_enable = Expression::Constant(F::from(1)) - cur_enable.clone();
+            let prev_enable = meta.query_advice(char_enable, Rotation::prev());
+            let enable_change = prev_enable - cur_enable.clone();
+            let not_enable_change = Expression::Constant(F::from(1)) - enable_change.clone();
+            vec![
+                not_q_frist.clone() * enable_change * not_enable_change,
+                not_q_frist * cur_enable * not_cur_enable,
+            ]
+        });
+
+        // An enabled row is looked up with the state on the next row, which for the last row is
+        // outside the check.
+        meta.create_gate("The last row must not be enabled", |meta| {
+            let q_last = meta.query_selector(q_last);
+            vec![q_last * meta.query_advice(char_enable, Rotation::cur())]
+        });
+
+        // The enable flags never go back to 1, so the disabled rows are the padding after the
+        // input, which must hold zeros rather than bytes appended to the checked input.
+        meta.create_gate("The padding characters must be zero", |meta| {
+            let q_any = meta.query_selector(q_first) + meta.query_selector(not_q_first);
+            let cur_enable = meta.query_advice(char_enable, Rotation::cur());
+            let not_cur_enable = Expression::Constant(F::from(1)) - cur_enable;
+            let character = meta.query_advice(characters, Rotation::cur());
+            vec![q_any * not_cur_enable * character]
+        });
+
+        meta.lookup_any("lookup committed transitions", |meta| {
+            let enable = meta.query_advice(char_enable, Rotation::cur());
+            let cur_state = meta.query_advice(state, Rotation::cur());
+            let next_state = meta.query_advice(state, Rotation::next());
+            let character = meta.query_advice(characters, Rotation::cur());
+            let q_rows = meta.query_fixed(q_transition_rows, Rotation::cur());
+            vec![
+                (
+                    enable.clone(),
+                    q_rows.clone() * meta.query_advice(table_enabled, Rotation::cur()),
+                ),
+                (
+                    enable.clone() * cur_state,
+                    q_rows.clone() * meta.query_advice(table_prev_state, Rotation::cur()),
+                ),
+                (
+                    enable.clone() * next_state,
+                    q_rows.clone() * meta.query_advice(table_next_state, Rotation::cur()),
+                ),
+                (
+                    enable * character,
+                    q_rows * meta.query_advice(table_character, Rotation::cur()),
+                ),
+            ]
+        });
+
+        meta.lookup_any(
+            "The final state must be a committed accepted state",
+            |meta| {
+                let q_frist = meta.query_selector(q_first);
+                let not_q_frist = meta.query_selector(not_q_first);
+                let cur_state = meta.query_advice(state, Rotation::cur());
+                let cur_enable = meta.query_advice(char_enable, Rotation::cur());
+                let prev_enable = meta.query_advice(char_enable, Rotation::prev());
+                let not_cur_enable = Expression::Constant(F::from(1)) - cur_enable.clone();
+                // The end of the input, or a first row that is padding for an empty input.
+                let enable_change =
+                    not_q_frist * (prev_enable - cur_enable) + q_frist * not_cur_enable;
+                let q_rows = meta.query_fixed(q_accepted_rows, Rotation::cur());
+                vec![
+                    (
+                        enable_change.clone(),
+                        q_rows.clone() * meta.query_advice(accepted_enabled, Rotation::cur()),
+                    ),
+                    (
+                        enable_change * cur_state,
+                        q_rows * meta.query_advice(accepted_states, Rotation::cur()),
+                    ),
+                ]
+            },
+        );
+
+        Self {
+            characters,
+            state,
+            char_enable,
+            q_first,
+            not_q_first,
+            q_last,
+            table_enabled,
+            table_prev_state,
+            table_next_state,
+            table_character,
+            accepted_enabled,
+            accepted_states,
+            q_transition_rows,
+            q_accepted_rows,
+            first_state_val,
+            max_chars_size,
+            max_transitions,
+            max_accepted_states,
+            well_formed: None,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Constrains the assigned transition table to be well-formed: the enabled rows come first,
+    /// their characters are bytes, and they are sorted strictly by `(prev_state, character)`, so
+    /// no two rows leave the same state on the same character and the committed table is a DFA.
+    ///
+    /// The sorting is checked with byte-range lookups, so consecutive source states in the table
+    /// may differ by at most 256, and the region needs at least 256 rows.
+    pub fn configure_well_formed(&mut self, meta: &mut ConstraintSystem<F>) {
+        let q_table = meta.complex_selector();
+        let q_table_end = meta.selector();
+        let same_prev = meta.advice_column();
+        let byte_range = meta.fixed_column();
+        let table_enabled = self.table_enabled;
+        let table_prev_state = self.table_prev_state;
+        let table_character = self.table_character;
+
+        meta.create_gate("The committed table must be well-formed", |meta| {
+            let q = meta.query_selector(q_table);
+            let cur_enabled = meta.query_advice(table_enabled, Rotation::cur());
+            let next_enabled = meta.query_advice(table_enabled, Rotation::next());
+            let same = meta.query_advice(same_prev, Rotation::cur());
+            let cur_prev = meta.query_advice(table_prev_state, Rotation::cur());
+            let next_prev = meta.query_advice(table_prev_state, Rotation::next());
+            let one = Expression::Constant(F::from(1));
+            vec![
+                q.clone() * cur_enabled.clone() * (one.clone() - cur_enabled.clone()),
+                q.clone() * next_enabled.clone() * (one.clone() - cur_enabled),
+                q.clone() * same.clone() * (one - same.clone()),
+                q * next_enabled * same * (next_prev - cur_prev),
+            ]
+        });
+
+        meta.create_gate("The last committed row must be disabled", |meta| {
+            let q = meta.query_selector(q_table_end);
+            vec![q * meta.query_advice(table_enabled, Rotation::cur())]
+        });
+
+        meta.lookup_any("The committed characters must be bytes", |meta| {
+            let q = meta.query_selector(q_table);
+            let enabled = meta.query_advice(table_enabled, Rotation::cur());
+            let character = meta.query_advice(table_character, Rotation::cur());
+            vec![(
+                q * enabled * character,
+                meta.query_fixed(byte_range, Rotation::cur()),
+            )]
+        });
+
+        meta.lookup_any("The committed transitions must be sorted", |meta| {
+            let q = meta.query_selector(q_table);
+            let next_enabled = meta.query_advice(table_enabled, Rotation::next());
+            let same = meta.query_advice(same_prev, Rotation::cur());
+            let one = Expression::Constant(F::from(1));
+            let char_gap = meta.query_advice(table_character, Rotation::next())
+                - meta.query_advice(table_character, Rotation::cur())
+                - one.clone();
+            let prev_gap = meta.query_advice(table_prev_state, Rotation::next())
+                - meta.query_advice(table_prev_state, Rotation::cur())
+                - one.clone();
+            vec![(
+                q * next_enabled * (same.clone() * char_gap + (one - same) * prev_gap),
+                meta.query_fixed(byte_range, Rotation::cur()),
+            )]
+        });
+
+        self.well_formed = Some(WellFormedConfig {
+            q_table,
+            q_table_end,
+            same_prev,
+            byte_range,
+        });
+    }
+
+    /// Assigns the tables of `regex_def`, padded with disabled rows. Fails if it has more
+    /// transitions or accepted states than configured, or a different first state.
+    pub fn assign_table(
+        &self,
+        region: &mut Region<F>,
+        regex_def: &RegexDef,
+    ) -> Result<AssignedCommittedTable<F>, Error> {
+        if regex_def.state_lookup.len() > self.max_transitions
+            || regex_def.accepted_state_vals.len() > self.max_accepted_states
+            || regex_def.first_state_val != self.first_state_val
+        {
+            return Err(Error::Synthesis);
+        }
+        let mut rows: Vec<[u64; 4]> = regex_def
+            .state_lookup
+            .iter()
+            .map(|((char, prev_state), next_state)| [1, *prev_state, *next_state, *char as u64])
+            .collect();
+        rows.sort_unstable_by_key(|row| (row[1], row[3], row[2]));
+        // The rows after the transitions, at least one, are the dummy row [0, 0, 0, 0].
+        rows.resize(self.max_transitions + 1, [0; 4]);
+        if let Some(well_formed) = self.well_formed.as_ref() {
+            self.assign_well_formed(region, well_formed, &rows)?;
+        }
+        let columns = [
+            self.table_enabled,
+            self.table_prev_state,
+            self.table_next_state,
+            self.table_character,
+        ];
+        let mut transitions = Vec::new();
+        for (offset, row) in rows.iter().enumerate() {
+            region.assign_fixed(
+                || format!("committed transition row at {}", offset),
+                self.q_transition_rows,
+                offset,
+                || Value::known(F::from(1)),
+            )?;
+            let mut cells = Vec::new();
+            for (column, val) in columns.iter().zip(row.iter()) {
+                cells.push(region.assign_advice(
+                    || format!("committed transition at {}", offset),
+                    *column,
+                    offset,
+                    || Value::known(F::from(*val)),
+                )?);
+            }
+            transitions.push([
+                cells[0].clone(),
+                cells[1].clone(),
+                cells[2].clone(),
+                cells[3].clone(),
+            ]);
+        }
+
+        let mut accepted_rows: Vec<[u64; 2]> = regex_def
+            .accepted_state_vals
+            .iter()
+            .map(|state| [1, *state])
+            .collect();
+        accepted_rows.resize(self.max_accepted_states + 1, [0; 2]);
+        let mut accepted_states = Vec::new();
+        for (offset, row) in accepted_rows.iter().enumerate() {
+            region.assign_fixed(
+                || format!("committed accepted row at {}", offset),
+                self.q_accepted_rows,
+                offset,
+                || Value::known(F::from(1)),
+            )?;
+            let enabled = region.assign_advice(
+                || format!("committed accepted flag at {}", offset),
+                self.accepted_enabled,
+                offset,
+                || Value::known(F::from(row[0])),
+            )?;
+            let state = region.assign_advice(
+                || format!("committed accepted state at {}", offset),
+                self.accepted_states,
+                offset,
+                || Value::known(F::from(row[1])),
+            )?;
+            accepted_states.push([enabled, state]);
+        }
+        Ok(AssignedCommittedTable {
+            transitions,
+            accepted_states,
+        })
+    }
+
+    fn assign_well_formed(
+        &self,
+        region: &mut Region<F>,
+        well_formed: &WellFormedConfig,
+        rows: &[[u64; 4]],
+    ) -> Result<(), Error> {
+        for byte in 0..256u64 {
+            region.assign_fixed(
+                || format!("byte range at {}", byte),
+                well_formed.byte_range,
+                byte as usize,
+                || Value::known(F::from(byte)),
+            )?;
+        }
+        for (offset, pair) in rows.windows(2).enumerate() {
+            let (cur, next) = (pair[0], pair[1]);
+            let same = next[0] == 1 && next[1] == cur[1];
+            if next[0] == 1 && !same && next[1] - cur[1] > 256 {
+                return Err(Error::Synthesis);
+            }
+            well_formed.q_table.enable(region, offset)?;
+            region.assign_advice(
+                || format!("same source state at {}", offset),
+                well_formed.same_prev,
+                offset,
+                || Value::known(F::from(same as u64)),
+            )?;
+        }
+        well_formed
+            .q_table_end
+            .enable(region, self.max_transitions)?;
+        Ok(())
+    }
+
+    /// Assigns `characters` and the states of `regex_def` on them. Returns the character cells.
+    pub fn assign_values(
+        &self,
+        region: &mut Region<F>,
+        regex_def: &RegexDef,
+        characters: &[u8],
+    ) -> Result<Vec<AssignedCell<F, F>>, Error> {
+        if characters.len() > self.max_chars_size {
+            return Err(Error::Synthesis);
+        }
+        let states = regex_def.witness_states(characters);
+        self.q_first.enable(region, 0)?;
+        for idx in 1..=self.max_chars_size {
+            self.not_q_first.enable(region, idx)?;
+        }
+        self.q_last.enable(region, self.max_chars_size)?;
+        let mut assigned_characters = Vec::new();
+        for idx in 0..=self.max_chars_size {
+            let (enable, char) = match characters.get(idx) {
+                Some(char) => (1, *char as u64),
+                None => (0, 0),
+            };
+            region.assign_advice(
+                || format!("char_enable at {}", idx),
+                self.char_enable,
+                idx,
+                || Value::known(F::from(enable)),
+            )?;
+            let assigned_c = region.assign_advice(
+                || format!("character at {}", idx),
+                self.characters,
+                idx,
+                || Value::known(F::from(char)),
+            )?;
+            if idx < self.max_chars_size {
+                assigned_characters.push(assigned_c);
+            }
+            let state = if idx <= characters.len() {
+                states[idx]
+            } else {
+                0
+            };
+            region.assign_advice(
+                || format!("state at {}", idx),
+                self.state,
+                idx,
+                || Value::known(F::from(state)),
+            )?;
+        }
+        Ok(assigned_characters)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use halo2_base::halo2_proofs::{
+        circuit::{Layouter, SimpleFloorPlanner},
+        dev::MockProver,
+        halo2curves::bn256::Fr,
+        plonk::Circuit,
+    };
+
+    use super::*;
+    use crate::compile_regex;
+
+    const MAX_STRING_LEN: usize = 16;
+
+    #[derive(Default, Clone, Debug)]
+    struct TestCommittedRegexCircuit<F: PrimeField> {
+        regex_def: RegexDef,
+        characters: Vec<u8>,
+        _marker: PhantomData<F>,
+    }
+
+    impl<F: PrimeField> Circuit<F> for TestCommittedRegexCircuit<F> {
+        type Config = CommittedRegexCheckConfig<F>;
+        type FloorPlanner = SimpleFloorPlanner;
+
+        fn without_witnesses(&self) -> Self {
+            Self {
+                regex_def: RegexDef {
+                    first_state_val: 1,
+                    ..Default::default()
+                },
+                characters: vec![],
+                _marker: PhantomData,
+            }
+        }
+
+        fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+            CommittedRegexCheckConfig::configure(meta, 1, MAX_STRING_LEN, 32, 4)
+        }
+
+        fn synthesize(
+            &self,
+            config: Self::Config,
+            mut layouter: impl Layouter<F>,
+        ) -> Result<(), Error> {
+            layouter.assign_region(
+                || "committed regex",
+                |mut region| {
+                    config.assign_table(&mut region, &self.regex_def)?;
+                    config.assign_values(&mut region, &self.regex_def, &self.characters)?;
+                    Ok(())
+                },
+            )?;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_committed_regex() {
+        let regex_def = compile_regex("ab+c").unwrap().regex_def();
+        let circuit = TestCommittedRegexCircuit::<Fr> {
+            regex_def: regex_def.clone(),
+            characters: b"abbc".to_vec(),
+            _marker: PhantomData,
+        };
+        let prover = MockProver::run(6, &circuit, vec![]).unwrap();
+        prover.assert_satisfied();
+
+        let circuit = TestCommittedRegexCircuit::<Fr> {
+            regex_def,
+            characters: b"ac".to_vec(),
+            _marker: PhantomData,
+        };
+        let prover = MockProver::run(6, &circuit, vec![]).unwrap();
+        assert!(prover.verify().is_err());
+
+        // An empty input passes iff the first state is accepted.
+        for (pattern, accepted) in [("ab+c", false), ("(ab)*", true)] {
+            let circuit = TestCommittedRegexCircuit::<Fr> {
+                regex_def: compile_regex(pattern).unwrap().regex_def(),
+                characters: vec![],
+                _marker: PhantomData,
+            };
+            let prover = MockProver::run(6, &circuit, vec![]).unwrap();
+            assert_eq!(prover.verify().is_ok(), accepted);
+        }
+
+        // "ab" only passes if the committed table accepts it.
+        let mut accepting_ab = compile_regex("ab+c?").unwrap().regex_def();
+        let circuit = TestCommittedRegexCircuit::<Fr> {
+            regex_def: accepting_ab.clone(),
+            characters: b"ab".to_vec(),
+            _marker: PhantomData,
+        };
+        let prover = MockProver::run(6, &circuit, vec![]).unwrap();
+        prover.assert_satisfied();
+        accepting_ab.accepted_state_vals = compile_regex("ab+c")
+            .unwrap()
+            .regex_def()
+            .accepted_state_vals;
+        let circuit = TestCommittedRegexCircuit::<Fr> {
+            regex_def: accepting_ab,
+            characters: b"ab".to_vec(),
+            _marker: PhantomData,
+        };
+        let prover = MockProver::run(6, &circuit, vec![]).unwrap();
+        assert!(prover.verify().is_err());
+    }
+
+    /// Checks `characters` against `regex_def`, or with `forged_row` against its table plus a
+    /// second transition on `a` from the first state to the state after `ab` at that row, which
+    /// accepts `ac`.
+    #[derive(Default, Clone, Debug)]
+    struct TestWellFormedCircuit<F: PrimeField, const WELL_FORMED: bool> {
+        regex_def: RegexDef,
+        characters: Vec<u8>,
+        forged_row: Option<usize>,
+        _marker: PhantomData<F>,
+    }
+
+    impl<F: PrimeField, const WELL_FORMED: bool> Circuit<F> for TestWellFormedCircuit<F, WELL_FORMED> {
+        type Config = CommittedRegexCheckConfig<F>;
+        type FloorPlanner = SimpleFloorPlanner;
+
+        fn without_witnesses(&self) -> Self {
+            Self {
+                regex_def: RegexDef {
+                    first_state_val: 1,
+                    ..Default::default()
+                },
+                ..Default::default()
+            }
+        }
+
+        fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+            let mut config = CommittedRegexCheckConfig::configure(meta, 1, MAX_STRING_LEN, 32, 4);
+            if WELL_FORMED {
+                config.configure_well_formed(meta);
+            }
+            config
+        }
+
+        fn synthesize(
+            &self,
+            config: Self::Config,
+            mut layouter: impl Layouter<F>,
+        ) -> Result<(), Error> {
+            layouter.assign_region(
+                || "committed regex",
+                |mut region| {
+                    config.assign_table(&mut region, &self.regex_def)?;
+                    config.assign_values(&mut region, &self.regex_def, &self.characters)?;
+                    if let Some(row) = self.forged_row {
+                        let after_ab = self.regex_def.witness_states(b"ab")[2];
+                        let after_abc = self.regex_def.witness_states(b"abc")[3];
+                        for (column, val) in [
+                            (config.table_enabled, 1),
+                            (config.table_prev_state, self.regex_def.first_state_val),
+                            (config.table_next_state, after_ab),
+                            (config.table_character, b'a' as u64),
+                        ] {
+                            region.assign_advice(
+                                || "forged transition",
+                                column,
+                                row,
+                                || Value::known(F::from(val)),
+                            )?;
+                        }
+                        for (idx, state) in [(1, after_ab), (2, after_abc)] {
+                            region.assign_advice(
+                                || "forged state",
+                                config.state,
+                                idx,
+                                || Value::known(F::from(state)),
+                            )?;
+                        }
+                    }
+                    Ok(())
+                },
+            )?;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_committed_regex_well_formed() {
+        // Different patterns are proven with one circuit shape, and so one verifying key.
+        for (pattern, input) in [
+            ("ab+c", "abbc"),
+            ("(a|b)*c", "babac"),
+            ("[a-f]+@x", "fade@x"),
+        ] {
+            let circuit = TestWellFormedCircuit::<Fr, true> {
+                regex_def: compile_regex(pattern).unwrap().regex_def(),
+                characters: input.as_bytes().to_vec(),
+                ..Default::default()
+            };
+            let prover = MockProver::run(9, &circuit, vec![]).unwrap();
+            prover.assert_satisfied();
+        }
+
+        // The non-deterministic table accepts `ac` unless the table is checked.
+        let regex_def = compile_regex("ab+c").unwrap().regex_def();
+        let circuit = TestWellFormedCircuit::<Fr, false> {
+            regex_def: regex_def.clone(),
+            characters: b"ac".to_vec(),
+            forged_row: Some(regex_def.state_lookup.len()),
+            ..Default::default()
+        };
+        let prover = MockProver::run(9, &circuit, vec![]).unwrap();
+        prover.assert_satisfied();
+        let circuit = TestWellFormedCircuit::<Fr, true> {
+            regex_def: regex_def.clone(),
+            characters: b"ac".to_vec(),
+            forged_row: Some(regex_def.state_lookup.len()),
+            ..Default::default()
+        };
+        let prover = MockProver::run(9, &circuit, vec![]).unwrap();
+        let errors = format!("{:?}", prover.verify().unwrap_err());
+        assert!(errors.contains("The committed transitions must be sorted"));
+
+        // A transition after the assigned table rows is outside the well-formedness check, and
+        // must not be part of the lookup table either.
+        let circuit = TestWellFormedCircuit::<Fr, true> {
+            regex_def,
+            characters: b"ac".to_vec(),
+            forged_row: Some(32 + 1),
+            ..Default::default()
+        };
+        let prover = MockProver::run(9, &circuit, vec![]).unwrap();
+        let errors = format!("{:?}", prover.verify().unwrap_err());
+        assert!(errors.contains("lookup committed transitions"));
+    }
+
+    /// Checks `characters` against `regex_def`, then forges the rows after them: `trailing` bytes
+    /// on the disabled rows, or with `enable_last` a self-loop on `a` from the last row into the
+    /// row after the check.
+    #[derive(Default, Clone, Debug)]
+    struct TestCommittedPaddingCircuit<F: PrimeField> {
+        regex_def: RegexDef,
+        characters: Vec<u8>,
+        trailing: Vec<u8>,
+        enable_last: bool,
+        _marker: PhantomData<F>,
+    }
+
+    impl<F: PrimeField> Circuit<F> for TestCommittedPaddingCircuit<F> {
+        type Config = CommittedRegexCheckConfig<F>;
+        type FloorPlanner = SimpleFloorPlanner;
+
+        fn without_witnesses(&self) -> Self {
+            Self {
+                regex_def: RegexDef {
+                    first_state_val: 1,
+                    ..Default::default()
+                },
+                ..Default::default()
+            }
+        }
+
+        fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+            CommittedRegexCheckConfig::configure(meta, 1, MAX_STRING_LEN, 32, 4)
+        }
+
+        fn synthesize(
+            &self,
+            config: Self::Config,
+            mut layouter: impl Layouter<F>,
+        ) -> Result<(), Error> {
+            layouter.assign_region(
+                || "committed regex",
+                |mut region| {
+                    config.assign_table(&mut region, &self.regex_def)?;
+                    config.assign_values(&mut region, &self.regex_def, &self.characters)?;
+                    let len = self.characters.len();
+                    for (idx, char) in self.trailing.iter().enumerate() {
+                        region.assign_advice(
+                            || "forged character",
+                            config.characters,
+                            len + idx,
+                            || Value::known(F::from(*char as u64)),
+                        )?;
+                    }
+                    if self.enable_last {
+                        let state = self.regex_def.witness_states(&self.characters)[len];
+                        for (column, row, val) in [
+                            (config.char_enable, MAX_STRING_LEN, 1),
+                            (config.characters, MAX_STRING_LEN, b'a' as u64),
+                            (config.state, MAX_STRING_LEN + 1, state),
+                        ] {
+                            region.assign_advice(
+                                || "forged last row",
+                                column,
+                                row,
+                                || Value::known(F::from(val)),
+                            )?;
+                        }
+                    }
+                    Ok(())
+                },
+            )?;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_committed_regex_padding() {
+        let run = |pattern: &str, characters: &[u8], trailing: &[u8], enable_last: bool| {
+            let circuit = TestCommittedPaddingCircuit::<Fr> {
+                regex_def: compile_regex(pattern).unwrap().regex_def(),
+                characters: characters.to_vec(),
+                trailing: trailing.to_vec(),
+                enable_last,
+                _marker: PhantomData,
+            };
+            MockProver::run(6, &circuit, vec![]).unwrap().verify()
+        };
+        assert_eq!(run("ab+c?", b"ab", b"", false), Ok(()));
+        // Bytes appended to "ab" on the disabled rows would be returned with the input.
+        let failures = format!("{:?}", run("ab+c?", b"ab", b"zzz", false).unwrap_err());
+        assert!(failures.contains("The padding characters must be zero"));
+
+        let rejected = [b'a'; MAX_STRING_LEN];
+        let mut accepted = rejected;
+        accepted[MAX_STRING_LEN - 1] = b'b';
+        assert_eq!(run("a+b", &accepted, b"", false), Ok(()));
+        assert!(run("a+b", &rejected, b"", false).is_err());
+        // Enabling the last row would skip the accept check of the full-length input.
+        let failures = run("a+b", &rejected, b"", true).unwrap_err();
+        assert_eq!(failures.len(), 1);
+        assert!(format!("{:?}", failures[0]).contains("The last row must not be enabled"));
+    }
+}
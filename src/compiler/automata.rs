@@ -0,0 +1,84 @@
+use alloc::{collections::BTreeMap, string::String, vec, vec::Vec};
+use regex_automata::dfa::{dense, Automaton};
+
+use super::{CompiledRegex, DfaGraph, DfaState};
+
+impl CompiledRegex {
+    /// Converts a `regex-automata` dense DFA into a [`CompiledRegex`] without capture groups.
+    ///
+    /// Build the DFA with `dense::Config::new().anchored(true)` so that it matches the whole input
+    /// like [`crate::compile_regex`]. Since `regex-automata` reports matches one byte late, a state
+    /// accepts if its end-of-input transition is a match state. Dead and quit states reject.
+    pub fn from_dense_dfa<T: AsRef<[u32]>>(dfa: &dense::DFA<T>) -> Self {
+        let start = dfa.start_state_forward(None, &[], 0, 0);
+        let mut ids = BTreeMap::new();
+        ids.insert(start, 0);
+        let mut order = vec![start];
+        let mut states = Vec::new();
+        let mut idx = 0;
+        while idx < order.len() {
+            let id = order[idx];
+            let mut state = DfaState {
+                accept: dfa.is_match_state(dfa.next_eoi_state(id)),
+                ..Default::default()
+            };
+            // `next_state` maps raw bytes through the DFA's byte classes, so all 256 are queried.
+            for byte in 0..=255u8 {
+                let next = dfa.next_state(id, byte);
+                if dfa.is_dead_state(next) || dfa.is_quit_state(next) {
+                    continue;
+                }
+                let next_idx = *ids.entry(next).or_insert_with(|| {
+                    order.push(next);
+                    order.len() - 1
+                });
+                state.edges.insert(byte, next_idx);
+            }
+            states.push(state);
+            idx += 1;
+        }
+        Self {
+            pattern: String::new(),
+            dfa: DfaGraph { states, start: 0 }.minimize(),
+            num_captures: 0,
+            capture_names: vec![],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compile_regex;
+    use regex_automata::SyntaxConfig;
+
+    fn anchored_dfa(pattern: &str) -> dense::DFA<Vec<u32>> {
+        dense::Builder::new()
+            .configure(dense::Config::new().anchored(true))
+            .syntax(SyntaxConfig::new().unicode(false).utf8(false))
+            .build(pattern)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_from_dense_dfa() {
+        let pattern = r"[a-z]+@[a-z]+\.(?:com|org)";
+        let converted = CompiledRegex::from_dense_dfa(&anchored_dfa(pattern));
+        let compiled = compile_regex(pattern).unwrap();
+        assert_eq!(converted.dfa, compiled.dfa);
+        for input in [
+            "bob@mail.com",
+            "a@b.org",
+            "bob@mail.net",
+            "@mail.com",
+            "bob@mail.com.",
+        ] {
+            assert_eq!(
+                converted.is_match(input.as_bytes()),
+                compiled.is_match(input.as_bytes()),
+                "{}",
+                input
+            );
+        }
+    }
+}
@@ -0,0 +1,538 @@
+use alloc::{
+    collections::{BTreeMap, BTreeSet, VecDeque},
+    string::String,
+    vec,
+    vec::Vec,
+};
+use core::fmt::Write;
+
+use crate::RegexError;
+
+/// A state of a [`DfaGraph`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DfaState {
+    pub accept: bool,
+    /// Outgoing transitions keyed by the consumed byte. Missing bytes reject.
+    pub edges: BTreeMap<u8, usize>,
+    /// The capture groups that the transition on each byte belongs to.
+    pub captures: BTreeMap<u8, BTreeSet<usize>>,
+    /// The ids of the patterns an accept state matches, in increasing order, if the DFA tells
+    /// the patterns of a union apart (see [`crate::CompileOptions::pattern_ids`]); empty
+    /// otherwise.
+    pub patterns: Vec<usize>,
+}
+
+/// A deterministic automaton whose states are indexed by their position in `states`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DfaGraph {
+    pub states: Vec<DfaState>,
+    pub start: usize,
+}
+
+impl DfaGraph {
+    pub fn num_states(&self) -> usize {
+        self.states.len()
+    }
+
+    pub fn accept_states(&self) -> Vec<usize> {
+        (0..self.states.len())
+            .filter(|idx| self.states[*idx].accept)
+            .collect()
+    }
+
+    /// Whether no input is accepted, i.e. no accept state is reachable from the start.
+    pub fn is_empty_language(&self) -> bool {
+        !self.live_states()[self.start]
+    }
+
+    /// The length of the longest accepted input, or `None` if accepted inputs are unbounded, i.e.
+    /// a cycle runs through states that are reachable from the start and can still reach an
+    /// accept state. Loops in dead states, like a trap state, do not count. An empty language
+    /// gives `Some(0)`.
+    pub fn max_match_len(&self) -> Option<usize> {
+        let live = self.live_states();
+        if !live[self.start] {
+            return Some(0);
+        }
+        // An iterative DFS over the live states computing the longest path to an accept state,
+        // where reaching a state that is still on the stack closes a cycle.
+        const UNVISITED: u8 = 0;
+        const ON_STACK: u8 = 1;
+        const DONE: u8 = 2;
+        let mut marks = vec![UNVISITED; self.states.len()];
+        let mut longest = vec![0; self.states.len()];
+        let mut stack = vec![(self.start, self.states[self.start].edges.values())];
+        marks[self.start] = ON_STACK;
+        while let Some((idx, edges)) = stack.last_mut() {
+            let idx = *idx;
+            match edges.find(|next| live[**next]) {
+                Some(next) => match marks[*next] {
+                    ON_STACK => return None,
+                    DONE => longest[idx] = longest[idx].max(longest[*next] + 1),
+                    _ => {
+                        marks[*next] = ON_STACK;
+                        stack.push((*next, self.states[*next].edges.values()));
+                    }
+                },
+                None => {
+                    marks[idx] = DONE;
+                    stack.pop();
+                    if let Some((parent, _)) = stack.last() {
+                        longest[*parent] = longest[*parent].max(longest[idx] + 1);
+                    }
+                }
+            }
+        }
+        Some(longest[self.start])
+    }
+
+    /// Checks that some input is accepted, which a DFA built by hand or imported may violate.
+    pub fn validate(&self) -> Result<(), RegexError> {
+        if self.is_empty_language() {
+            return Err(RegexError::EmptyLanguage);
+        }
+        Ok(())
+    }
+
+    /// Runs the DFA from the start state and returns whether it ends in an accept state.
+    pub fn is_match(&self, input: &[u8]) -> bool {
+        let mut state = self.start;
+        for byte in input.iter() {
+            match self.states[state].edges.get(byte) {
+                Some(next) => state = *next,
+                None => return false,
+            }
+        }
+        self.states[state].accept
+    }
+
+    /// Partitions all 256 bytes into classes whose bytes lead to the same state, with the same
+    /// capture groups, from every state, so a table may store one transition per class. Classes
+    /// are ordered by their smallest byte; bytes without any transition form one class.
+    pub fn char_classes(&self) -> Vec<Vec<u8>> {
+        let mut classes =
+            BTreeMap::<Vec<(Option<usize>, Option<&BTreeSet<usize>>)>, Vec<u8>>::new();
+        for byte in 0..=255u8 {
+            let signature = self
+                .states
+                .iter()
+                .map(|state| (state.edges.get(&byte).copied(), state.captures.get(&byte)))
+                .collect();
+            classes.entry(signature).or_default().push(byte);
+        }
+        let mut classes: Vec<Vec<u8>> = classes.into_values().collect();
+        classes.sort_unstable();
+        classes
+    }
+
+    /// Returns a shortest input accepted by exactly one of `self` and `other`, the smallest in
+    /// byte order among those, or `None` if both accept the same language. It runs a BFS over
+    /// pairs of states of both DFAs, where `None` stands for the implicit dead state.
+    pub fn distinguishing_input(&self, other: &DfaGraph) -> Option<Vec<u8>> {
+        type Pair = (Option<usize>, Option<usize>);
+        let accepts = |dfa: &DfaGraph, state: Option<usize>| matches!(state, Some(idx) if dfa.states[idx].accept);
+        let step = |dfa: &DfaGraph, state: Option<usize>, byte: u8| {
+            state.and_then(|idx| dfa.states[idx].edges.get(&byte).copied())
+        };
+        let start: Pair = (Some(self.start), Some(other.start));
+        let mut parents = BTreeMap::<Pair, Option<(Pair, u8)>>::new();
+        parents.insert(start, None);
+        let mut queue = VecDeque::from([start]);
+        while let Some(pair) = queue.pop_front() {
+            if accepts(self, pair.0) != accepts(other, pair.1) {
+                let mut input = Vec::new();
+                let mut cur = pair;
+                while let Some((parent, byte)) = parents[&cur] {
+                    input.push(byte);
+                    cur = parent;
+                }
+                input.reverse();
+                return Some(input);
+            }
+            for byte in 0..=255u8 {
+                let next = (step(self, pair.0, byte), step(other, pair.1, byte));
+                if next != (None, None) && !parents.contains_key(&next) {
+                    parents.insert(next, Some((pair, byte)));
+                    queue.push_back(next);
+                }
+            }
+        }
+        None
+    }
+
+    /// Renders the DFA in Graphviz DOT. Accept states are double circles, and all bytes leading
+    /// from one state to another share an edge whose label collapses contiguous ranges (`a-z`).
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph dfa {\n    rankdir=LR;\n");
+        dot.push_str("    start [shape=point];\n");
+        for (idx, state) in self.states.iter().enumerate() {
+            let shape = if state.accept {
+                "doublecircle"
+            } else {
+                "circle"
+            };
+            writeln!(dot, "    {} [shape={}];", idx, shape).unwrap();
+        }
+        writeln!(dot, "    start -> {};", self.start).unwrap();
+        for (idx, state) in self.states.iter().enumerate() {
+            let mut targets = BTreeMap::<usize, Vec<u8>>::new();
+            for (byte, next) in state.edges.iter() {
+                targets.entry(*next).or_default().push(*byte);
+            }
+            for (next, bytes) in targets {
+                writeln!(
+                    dot,
+                    "    {} -> {} [label=\"{}\"];",
+                    idx,
+                    next,
+                    range_label(&bytes)
+                )
+                .unwrap();
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// Returns a minimal DFA for the reversed language: it accepts `input` iff `self` accepts
+    /// `input` read back to front. To check a pattern anchored at the end of a string, compile
+    /// it, reverse it, and feed the characters in reverse order (e.g. `input.iter().rev()`).
+    /// Capture groups keep their numbering on the reversed transitions.
+    pub fn reverse(&self) -> Self {
+        let mut incoming = vec![BTreeMap::<u8, Vec<usize>>::new(); self.states.len()];
+        for (idx, state) in self.states.iter().enumerate() {
+            for (byte, next) in state.edges.iter() {
+                incoming[*next].entry(*byte).or_default().push(idx);
+            }
+        }
+        let start_set: BTreeSet<usize> = self.accept_states().into_iter().collect();
+        let mut set_ids = BTreeMap::new();
+        let mut sets = vec![start_set.clone()];
+        set_ids.insert(start_set, 0);
+        let mut states = Vec::new();
+        let mut idx = 0;
+        while idx < sets.len() {
+            let set = sets[idx].clone();
+            let mut reversed = DfaState {
+                accept: set.contains(&self.start),
+                ..Default::default()
+            };
+            let mut targets = BTreeMap::<u8, (BTreeSet<usize>, BTreeSet<usize>)>::new();
+            for state in set.iter() {
+                for (byte, prevs) in incoming[*state].iter() {
+                    let (target_set, captures) = targets.entry(*byte).or_default();
+                    for prev in prevs.iter() {
+                        target_set.insert(*prev);
+                        if let Some(groups) = self.states[*prev].captures.get(byte) {
+                            captures.extend(groups.iter().copied());
+                        }
+                    }
+                }
+            }
+            for (byte, (target_set, captures)) in targets {
+                let next = match set_ids.get(&target_set) {
+                    Some(id) => *id,
+                    None => {
+                        set_ids.insert(target_set.clone(), sets.len());
+                        sets.push(target_set);
+                        sets.len() - 1
+                    }
+                };
+                reversed.edges.insert(byte, next);
+                if !captures.is_empty() {
+                    reversed.captures.insert(byte, captures);
+                }
+            }
+            states.push(reversed);
+            idx += 1;
+        }
+        Self { states, start: 0 }.minimize()
+    }
+
+    /// Returns a minimal DFA accepting the inputs of exactly `length` bytes that `self` accepts.
+    /// Each state is paired with the number of bytes still to read, so the accept check reduces
+    /// to reaching an accept state with none remaining and no separate length constraint is
+    /// needed. The pairing multiplies the states by up to `length + 1` before minimization, so
+    /// long lengths give large tables.
+    pub fn with_exact_length(&self, length: usize) -> Self {
+        let mut ids = BTreeMap::new();
+        let mut pairs = vec![(self.start, length)];
+        ids.insert((self.start, length), 0);
+        let mut states = Vec::new();
+        let mut idx = 0;
+        while idx < pairs.len() {
+            let (state, remaining) = pairs[idx];
+            let mut counted = DfaState {
+                accept: self.states[state].accept && remaining == 0,
+                ..Default::default()
+            };
+            if counted.accept {
+                counted.patterns = self.states[state].patterns.clone();
+            }
+            if remaining > 0 {
+                for (byte, next) in self.states[state].edges.iter() {
+                    let pair = (*next, remaining - 1);
+                    let next = *ids.entry(pair).or_insert_with(|| {
+                        pairs.push(pair);
+                        pairs.len() - 1
+                    });
+                    counted.edges.insert(*byte, next);
+                    if let Some(captures) = self.states[state].captures.get(byte) {
+                        counted.captures.insert(*byte, captures.clone());
+                    }
+                }
+            }
+            states.push(counted);
+            idx += 1;
+        }
+        Self { states, start: 0 }.minimize()
+    }
+
+    /// Removes the states that cannot be reached from the start, e.g. of an imported DFA. The
+    /// remaining states keep their relative order.
+    pub fn prune_unreachable(&self) -> Self {
+        let mut reachable = vec![false; self.states.len()];
+        reachable[self.start] = true;
+        let mut stack = vec![self.start];
+        while let Some(idx) = stack.pop() {
+            for next in self.states[idx].edges.values() {
+                if !reachable[*next] {
+                    reachable[*next] = true;
+                    stack.push(*next);
+                }
+            }
+        }
+        self.retain_states(&reachable)
+    }
+
+    /// Removes the dead states, from which no accept state can be reached, with the transitions
+    /// into them: a missing transition rejects just as well. The start state is kept even if
+    /// it is dead, leaving a DFA with no transitions for the empty language.
+    pub fn prune_dead(&self) -> Self {
+        let mut live = self.live_states();
+        let start_is_dead = !live[self.start];
+        live[self.start] = true;
+        let mut pruned = self.retain_states(&live);
+        if start_is_dead {
+            let start = pruned.start;
+            for state in pruned.states.iter_mut() {
+                state.edges.retain(|_, next| *next != start);
+                let edges = &state.edges;
+                state.captures.retain(|byte, _| edges.contains_key(byte));
+            }
+        }
+        pruned
+    }
+
+    // Keeps the states flagged in `keep`, in order, dropping the transitions to the others.
+    fn retain_states(&self, keep: &[bool]) -> Self {
+        let mut new_ids = vec![None; self.states.len()];
+        let mut num_kept = 0;
+        for (idx, kept) in keep.iter().enumerate() {
+            if *kept {
+                new_ids[idx] = Some(num_kept);
+                num_kept += 1;
+            }
+        }
+        let states = self
+            .states
+            .iter()
+            .enumerate()
+            .filter(|(idx, _)| keep[*idx])
+            .map(|(_, state)| {
+                let edges: BTreeMap<u8, usize> = state
+                    .edges
+                    .iter()
+                    .filter_map(|(byte, next)| new_ids[*next].map(|next| (*byte, next)))
+                    .collect();
+                DfaState {
+                    accept: state.accept,
+                    captures: state
+                        .captures
+                        .iter()
+                        .filter(|(byte, _)| edges.contains_key(*byte))
+                        .map(|(byte, captures)| (*byte, captures.clone()))
+                        .collect(),
+                    edges,
+                    patterns: state.patterns.clone(),
+                }
+            })
+            .collect();
+        Self {
+            states,
+            start: new_ids[self.start].unwrap(),
+        }
+    }
+
+    /// Returns the minimal equivalent DFA with states renumbered in BFS order from the start.
+    pub(crate) fn minimize(&self) -> Self {
+        let live = self.live_states();
+        // Moore's partition refinement. Missing transitions and transitions into dead states
+        // both reject, so they are treated alike. Capture groups are part of the signature so
+        // that states with different capture behaviour are never merged, and so are the
+        // patterns of accept states.
+        let mut initial = BTreeMap::new();
+        let mut blocks: Vec<usize> = self
+            .states
+            .iter()
+            .map(|state| {
+                let num_initial = initial.len();
+                *initial
+                    .entry((state.accept, &state.patterns))
+                    .or_insert(num_initial)
+            })
+            .collect();
+        let mut num_blocks = 0;
+        loop {
+            let mut signatures = BTreeMap::new();
+            let mut new_blocks = vec![0; self.states.len()];
+            for (idx, state) in self.states.iter().enumerate() {
+                let edges: Vec<(u8, usize, Option<&BTreeSet<usize>>)> = state
+                    .edges
+                    .iter()
+                    .filter(|(_, next)| live[**next])
+                    .map(|(byte, next)| (*byte, blocks[*next], state.captures.get(byte)))
+                    .collect();
+                let num_signatures = signatures.len();
+                new_blocks[idx] = *signatures
+                    .entry((blocks[idx], edges))
+                    .or_insert(num_signatures);
+            }
+            blocks = new_blocks;
+            if signatures.len() == num_blocks {
+                break;
+            }
+            num_blocks = signatures.len();
+        }
+
+        let mut merged = vec![DfaState::default(); num_blocks];
+        for (idx, state) in self.states.iter().enumerate() {
+            let block = &mut merged[blocks[idx]];
+            block.accept = state.accept;
+            block.patterns = state.patterns.clone();
+            for (byte, next) in state.edges.iter().filter(|(_, next)| live[**next]) {
+                block.edges.insert(*byte, blocks[*next]);
+                if let Some(captures) = state.captures.get(byte) {
+                    block.captures.insert(*byte, captures.clone());
+                }
+            }
+        }
+        Self {
+            states: merged,
+            start: blocks[self.start],
+        }
+        .renumber()
+    }
+
+    // States from which an accept state is reachable.
+    fn live_states(&self) -> Vec<bool> {
+        let mut live: Vec<bool> = self.states.iter().map(|state| state.accept).collect();
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (idx, state) in self.states.iter().enumerate() {
+                if !live[idx] && state.edges.values().any(|next| live[*next]) {
+                    live[idx] = true;
+                    changed = true;
+                }
+            }
+        }
+        live
+    }
+
+    // The length of the shortest input leading from each state to an accept state, if any.
+    #[cfg(any(test, feature = "rand"))]
+    pub(crate) fn accept_distances(&self) -> Vec<Option<usize>> {
+        let mut distances: Vec<Option<usize>> = self
+            .states
+            .iter()
+            .map(|state| state.accept.then_some(0))
+            .collect();
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (idx, state) in self.states.iter().enumerate() {
+                let nearest = state
+                    .edges
+                    .values()
+                    .filter_map(|next| distances[*next])
+                    .min()
+                    .map(|distance| distance + 1);
+                if let Some(distance) = nearest {
+                    if !matches!(distances[idx], Some(cur) if cur <= distance) {
+                        distances[idx] = Some(distance);
+                        changed = true;
+                    }
+                }
+            }
+        }
+        distances
+    }
+
+    // Renumbers states in BFS order from the start (edges visited by byte), dropping unreachable ones.
+    fn renumber(&self) -> Self {
+        let mut new_ids = vec![None; self.states.len()];
+        let mut order = vec![self.start];
+        new_ids[self.start] = Some(0);
+        let mut queue = VecDeque::from([self.start]);
+        while let Some(idx) = queue.pop_front() {
+            for next in self.states[idx].edges.values() {
+                if new_ids[*next].is_none() {
+                    new_ids[*next] = Some(order.len());
+                    order.push(*next);
+                    queue.push_back(*next);
+                }
+            }
+        }
+        let states = order
+            .iter()
+            .map(|idx| {
+                let state = &self.states[*idx];
+                DfaState {
+                    accept: state.accept,
+                    edges: state
+                        .edges
+                        .iter()
+                        .map(|(byte, next)| (*byte, new_ids[*next].unwrap()))
+                        .collect(),
+                    captures: state.captures.clone(),
+                    patterns: state.patterns.clone(),
+                }
+            })
+            .collect();
+        Self { states, start: 0 }
+    }
+}
+
+// Formats sorted bytes as comma-separated ranges, e.g. `0-9,_,a-z`, escaped for a DOT label.
+fn range_label(bytes: &[u8]) -> String {
+    let mut label = String::new();
+    let mut idx = 0;
+    while idx < bytes.len() {
+        let mut end = idx;
+        while end + 1 < bytes.len() && bytes[end + 1] == bytes[end] + 1 {
+            end += 1;
+        }
+        if !label.is_empty() {
+            label.push(',');
+        }
+        push_label_byte(&mut label, bytes[idx]);
+        if end > idx {
+            label.push('-');
+            push_label_byte(&mut label, bytes[end]);
+        }
+        idx = end + 1;
+    }
+    label
+}
+
+fn push_label_byte(label: &mut String, byte: u8) {
+    match byte {
+        b'"' | b'\\' => {
+            label.push('\\');
+            label.push(byte as char);
+        }
+        b' '..=b'~' => label.push(byte as char),
+        _ => write!(label, "\\\\x{:02x}", byte).unwrap(),
+    }
+}
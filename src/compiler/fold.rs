@@ -0,0 +1,53 @@
+use alloc::{collections::BTreeMap, vec, vec::Vec};
+
+// No character above the Adlam block has a case mapping.
+const LAST_CASED: u32 = 0x1e943;
+
+fn single(mut chars: impl Iterator<Item = char>) -> Option<char> {
+    let c = chars.next()?;
+    match chars.next() {
+        Some(_) => None,
+        None => Some(c),
+    }
+}
+
+/// The simple case folding of `c`: the lowercase of its uppercase, where both are single
+/// characters. Characters whose case mappings expand into several characters, like `ß` (`SS`)
+/// or `İ` (`i` and a combining dot), only fold with the single-character mappings they have,
+/// so `ẞ` folds to `ß` but neither matches `ss`, and `İ` matches neither `i` nor `I`.
+pub(crate) fn simple_fold(c: char) -> char {
+    // Like `İ`, the dotless `ı` only folds with `I` in Turkic languages.
+    if c == 'ı' {
+        return c;
+    }
+    let upper = single(c.to_uppercase()).unwrap_or(c);
+    single(upper.to_lowercase())
+        .or_else(|| single(c.to_lowercase()))
+        .unwrap_or(c)
+}
+
+/// The characters with the same simple case folding, grouped by their folding. Characters that
+/// only fold to themselves are left out.
+#[derive(Debug, Clone)]
+pub(crate) struct CaseFolds(BTreeMap<char, Vec<char>>);
+
+impl CaseFolds {
+    pub(crate) fn new() -> Self {
+        let mut folds: BTreeMap<char, Vec<char>> = BTreeMap::new();
+        for c in (0..=LAST_CASED).filter_map(char::from_u32) {
+            let folded = simple_fold(c);
+            if folded != c {
+                folds.entry(folded).or_insert_with(|| vec![folded]).push(c);
+            }
+        }
+        Self(folds)
+    }
+
+    /// Every character that folds like `c`, including `c`.
+    pub(crate) fn equivalents(&self, c: char) -> Vec<char> {
+        match self.0.get(&simple_fold(c)) {
+            Some(chars) => chars.clone(),
+            None => vec![c],
+        }
+    }
+}
@@ -0,0 +1,100 @@
+use alloc::{format, string::String, vec::Vec};
+
+use super::{compile_regex, keywords::escape, CompiledRegex};
+use crate::RegexError;
+
+impl CompiledRegex {
+    /// Compiles a regex matching a block of `Key: Value\r\n` header lines that contains a line
+    /// with the literal `key`, capturing that line's value as group 0. Spaces after the colon are
+    /// skipped, so `value` should not match a leading space, e.g. `[^ \r\n][^\r\n]*`.
+    ///
+    /// The lines before the captured one must have other keys, so with several matching lines the
+    /// first one is captured and the later ones are matched as ordinary lines. Keys are compared
+    /// case-sensitively, and a folded continuation line is not part of the captured value.
+    pub fn header_value(key: &str, value: &str) -> Result<Self, RegexError> {
+        if let Some(position) = key.find([':', '\r', '\n']) {
+            return Err(RegexError::Syntax {
+                position,
+                message: "a header key cannot contain ':' or line breaks".into(),
+            });
+        }
+        let key_line = format!("{}:", key);
+        let key_bytes = key_line.as_bytes();
+        // A line that differs from `key:` at byte `i`, or ends before it.
+        let other_lines: Vec<String> = (0..key_bytes.len())
+            .map(|i| {
+                format!(
+                    r"{}(?:[^\x{:02x}\r\n][^\r\n]*)?",
+                    escape_bytes(&key_bytes[..i]),
+                    key_bytes[i]
+                )
+            })
+            .collect();
+        let pattern = format!(
+            r"(?:(?:{})\r\n)*{} *({})\r\n(?:[^\r\n]*\r\n)*",
+            other_lines.join("|"),
+            escape(&key_line),
+            value
+        );
+        compile_regex(&pattern)
+    }
+}
+
+fn escape_bytes(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|byte| format!(r"\x{:02x}", byte))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extract_captures;
+
+    const SUBJECT_VALUE: &str = r"[^ \r\n][^\r\n]*";
+
+    #[test]
+    fn test_header_value() {
+        let compiled = CompiledRegex::header_value("Subject", SUBJECT_VALUE).unwrap();
+        assert_eq!(compiled.num_captures, 1);
+        for (headers, subject) in [
+            (
+                "From: alice@mail.com\r\nSubject: hello world\r\nTo: bob@mail.com\r\n",
+                "hello world",
+            ),
+            ("Subject:hi\r\n", "hi"),
+            (
+                "To: bob@mail.com\r\n\r\nSubject:   hi there\r\n",
+                "hi there",
+            ),
+            // Keys that only share a prefix with the key are other lines.
+            (
+                "Subject-Id: 1\r\nSub: 2\r\nX-Subject: 3\r\nSubject: 4\r\n",
+                "4",
+            ),
+            // The first matching line is captured.
+            ("Subject: first\r\nTo: bob\r\nSubject: second\r\n", "first"),
+        ] {
+            let input = headers.as_bytes();
+            assert!(compiled.is_match(input), "{}", headers);
+            assert_eq!(
+                extract_captures(input, &compiled).unwrap()[0],
+                subject.as_bytes()
+            );
+        }
+
+        for headers in [
+            "From: alice@mail.com\r\nTo: bob@mail.com\r\n",
+            "subject: lowercase\r\n",
+            "Subject: missing line break",
+        ] {
+            assert!(!compiled.is_match(headers.as_bytes()), "{}", headers);
+        }
+
+        assert!(matches!(
+            CompiledRegex::header_value("Sub:ject", SUBJECT_VALUE),
+            Err(RegexError::Syntax { position: 3, .. })
+        ));
+    }
+}
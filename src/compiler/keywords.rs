@@ -0,0 +1,143 @@
+use alloc::{string::String, vec, vec::Vec};
+
+use super::{CompiledRegex, DfaGraph, DfaState};
+
+impl CompiledRegex {
+    /// Builds the DFA of the alternation `kw1|kw2|...` directly from a keyword trie, which is much
+    /// faster than [`crate::compile_regex`] for long keyword lists. Matching is anchored, so the
+    /// trie's goto function is already the whole automaton and no failure links are needed.
+    /// `pattern` is set to the equivalent alternation with punctuation escaped.
+    pub fn from_keywords(keywords: &[&str]) -> Self {
+        Self::keyword_trie(keywords, false)
+    }
+
+    /// Like [`Self::from_keywords`], but each accept state records the indices of the keywords
+    /// it matches in [`DfaState::patterns`], like the alternatives of a pattern compiled with
+    /// [`crate::CompileOptions::pattern_ids`], so [`Self::matched_patterns`] tells which keyword
+    /// matched. Keywords ending in the same state are no longer merged.
+    pub fn from_keywords_with_ids(keywords: &[&str]) -> Self {
+        Self::keyword_trie(keywords, true)
+    }
+
+    fn keyword_trie(keywords: &[&str], with_ids: bool) -> Self {
+        let mut states = vec![DfaState::default()];
+        for (id, keyword) in keywords.iter().enumerate() {
+            let mut state = 0;
+            for byte in keyword.bytes() {
+                state = match states[state].edges.get(&byte) {
+                    Some(next) => *next,
+                    None => {
+                        states.push(DfaState::default());
+                        let next = states.len() - 1;
+                        states[state].edges.insert(byte, next);
+                        next
+                    }
+                };
+            }
+            states[state].accept = true;
+            if with_ids {
+                states[state].patterns.push(id);
+            }
+        }
+        let pattern = keywords
+            .iter()
+            .map(|keyword| escape(keyword))
+            .collect::<Vec<_>>()
+            .join("|");
+        Self {
+            pattern,
+            dfa: DfaGraph { states, start: 0 }.minimize(),
+            num_captures: 0,
+            capture_names: vec![],
+        }
+    }
+}
+
+pub(super) fn escape(keyword: &str) -> String {
+    let mut escaped = String::new();
+    for c in keyword.chars() {
+        if c.is_ascii_punctuation() {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{compile_regex, compile_regex_with, CompileOptions};
+    use alloc::format;
+
+    fn keywords() -> Vec<String> {
+        let prefixes = ["spam", "free", "win", "offer", "cash"];
+        (0..100)
+            .map(|i| format!("{}{}", prefixes[i % prefixes.len()], i))
+            .collect()
+    }
+
+    #[test]
+    fn test_from_keywords() {
+        let keywords = keywords();
+        let keywords: Vec<&str> = keywords.iter().map(|keyword| keyword.as_str()).collect();
+        let compiled = CompiledRegex::from_keywords(&keywords);
+        for keyword in keywords.iter() {
+            assert!(compiled.is_match(keyword.as_bytes()), "{}", keyword);
+        }
+        for input in ["spam", "spam1", "free0", "win2x", "", "cash98"] {
+            assert!(!compiled.is_match(input.as_bytes()), "{}", input);
+        }
+        let alternation = compile_regex(&format!("(?:{})", keywords.join("|"))).unwrap();
+        assert_eq!(compiled.dfa, alternation.dfa);
+        assert_eq!(compile_regex(&compiled.pattern).unwrap().dfa, compiled.dfa);
+    }
+
+    #[test]
+    fn test_from_keywords_escapes_pattern() {
+        let compiled = CompiledRegex::from_keywords(&["a.b", "c+", ""]);
+        assert_eq!(compiled.pattern, r"a\.b|c\+|");
+        assert!(compiled.is_match(b""));
+        assert!(!compiled.is_match(b"axb"));
+        assert_eq!(compile_regex(&compiled.pattern).unwrap().dfa, compiled.dfa);
+    }
+
+    #[test]
+    fn test_from_keywords_with_ids() {
+        let keywords = ["cat", "car", "cart", "dog", "cat"];
+        let compiled = CompiledRegex::from_keywords_with_ids(&keywords);
+        assert_eq!(compiled.matched_patterns(b"cat"), Some(&[0, 4][..]));
+        assert_eq!(compiled.matched_patterns(b"car"), Some(&[1][..]));
+        assert_eq!(compiled.matched_patterns(b"cart"), Some(&[2][..]));
+        assert_eq!(compiled.matched_patterns(b"dog"), Some(&[3][..]));
+        assert_eq!(compiled.matched_patterns(b"ca"), None);
+        // The state values the accept check ends in tell the keywords apart.
+        let regex_def = compiled.regex_def();
+        let accepted = |input: &[u8]| *regex_def.gen_states(input).unwrap().last().unwrap();
+        assert_eq!(compiled.accept_patterns(accepted(b"dog")), &[3]);
+        assert_ne!(accepted(b"cat"), accepted(b"dog"));
+        // Without ids, the keywords ending without a further transition share a state.
+        let plain = CompiledRegex::from_keywords(&keywords);
+        assert_eq!(plain.matched_patterns(b"cat"), Some(&[][..]));
+        assert!(plain.dfa.num_states() < compiled.dfa.num_states());
+
+        let options = CompileOptions {
+            pattern_ids: true,
+            ..Default::default()
+        };
+        let alternation = compile_regex_with(&compiled.pattern, &options).unwrap();
+        assert_eq!(alternation.dfa, compiled.dfa);
+    }
+
+    // $ cargo test --release test_from_keywords_many -- --ignored
+    #[cfg(feature = "std")]
+    #[test]
+    #[ignore]
+    fn test_from_keywords_many() {
+        let keywords: Vec<String> = (0..2000).map(|i| format!("keyword{}", i)).collect();
+        let keywords: Vec<&str> = keywords.iter().map(|keyword| keyword.as_str()).collect();
+        let compiled = CompiledRegex::from_keywords(&keywords);
+        let alternation = compile_regex(&keywords.join("|")).unwrap();
+        assert_eq!(compiled.dfa, alternation.dfa);
+    }
+}
@@ -0,0 +1,1251 @@
+#[cfg(feature = "regex-automata")]
+mod automata;
+mod dfa;
+mod fold;
+mod headers;
+mod keywords;
+mod nfa;
+mod options;
+mod parser;
+mod pipeline;
+mod scan;
+
+pub use dfa::{DfaGraph, DfaState};
+pub use options::{CompileOptions, DotMatchesNewline, LineEndings};
+pub use parser::DEFAULT_MAX_DEPTH;
+pub use pipeline::{split_regex, RegexPipeline};
+
+use alloc::{
+    collections::BTreeSet,
+    format,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+use core::fmt;
+#[cfg(feature = "std")]
+use std::collections::{HashMap, HashSet};
+
+use crate::RegexError;
+#[cfg(feature = "std")]
+use crate::{RegexDef, SubstrDef};
+use nfa::Nfa;
+use parser::{Ast, Parser};
+
+/// A regex compiled into a minimal DFA. State `i` of `dfa` is assigned the value `i + 1` in
+/// the transition table so that 0 stays reserved for padding rows.
+///
+/// Its `Debug` output summarizes the DFA instead of listing every transition.
+#[derive(Clone)]
+pub struct CompiledRegex {
+    pub pattern: String,
+    pub dfa: DfaGraph,
+    pub num_captures: usize,
+    /// The name of each capture group, if it has one. It may be shorter than `num_captures`,
+    /// e.g. empty for a DFA built without a pattern.
+    pub capture_names: Vec<Option<String>>,
+}
+
+/// Compiles `pattern` so that it matches the whole input.
+///
+/// Supported syntax: literals, `.` (any byte but `\n`, see
+/// [`CompileOptions::dot_matches_newline`]), `[...]`/`[^...]` classes, the ASCII escape classes
+/// `\d \D \w \W \s \S`, escaped literals (`\n`, `\r`, `\t`, `\xHH`, `\.` etc.), groups
+/// `(...)` and `(?:...)`, alternation `|` and the quantifiers `*`, `+`, `?`, `{m}`, `{m,}` and
+/// `{m,n}`. As in Perl, `\s` matches the vertical tab `\x0b` and the form feed `\x0c` besides
+/// space, `\t`, `\n` and `\r`; write `[ \t\r\n]` to exclude them.
+/// Every `(...)` and `(?P<name>...)` group is a capture group, numbered by its opening
+/// parenthesis from 0. Names must be unique and are only metadata: naming a group does not
+/// change the DFA.
+pub fn compile_regex(pattern: &str) -> Result<CompiledRegex, RegexError> {
+    compile_regex_with(pattern, &CompileOptions::default())
+}
+
+/// Compiles `pattern` like [`compile_regex`], applying `options`.
+pub fn compile_regex_with(
+    pattern: &str,
+    options: &CompileOptions,
+) -> Result<CompiledRegex, RegexError> {
+    let max_depth = options.max_depth.unwrap_or(DEFAULT_MAX_DEPTH);
+    let (ast, capture_names) = Parser::new(pattern)
+        .with_max_depth(max_depth)
+        .with_max_alternatives(options.max_alternatives)
+        .with_case_insensitive(options.case_insensitive)
+        .with_dot_matches_newline(options.dot_matches_newline)
+        .parse()?;
+    let ast = options.apply(ast);
+    let nfa = match ast {
+        Ast::Alternate(branches) if options.pattern_ids => Nfa::from_patterns(&branches),
+        ast if options.pattern_ids => Nfa::from_patterns(&[ast]),
+        ast => Nfa::from_ast(&ast),
+    };
+    let mut dfa = nfa.to_dfa().minimize();
+    if let Some(length) = options.exact_length {
+        dfa = dfa.with_exact_length(length);
+    }
+    Ok(CompiledRegex {
+        pattern: pattern.to_string(),
+        dfa,
+        num_captures: capture_names.len(),
+        capture_names,
+    })
+}
+
+/// Returns the bytes of `input` captured by each group of `compiled`, as the masked characters of
+/// `SubstrMatchConfig::match_substrs` would for a `SubstrDef` of the group whose positions cover
+/// the input and whose `max_length` fits the capture: the bytes at every position whose
+/// `(state, next_state)` transition belongs to the group. Returns `None` if no proof exists:
+/// `input` is not matched, or the positions of a group are not one contiguous run.
+pub fn extract_captures(input: &[u8], compiled: &CompiledRegex) -> Option<Vec<Vec<u8>>> {
+    if !compiled.is_match(input) {
+        return None;
+    }
+    let mut states = vec![compiled.dfa.start];
+    for byte in input.iter() {
+        states.push(compiled.dfa.states[*states.last().unwrap()].edges[byte]);
+    }
+    (0..compiled.num_captures)
+        .map(|group| {
+            let transitions = compiled.capture_transition_set(group);
+            let positions: Vec<usize> = (0..input.len())
+                .filter(|position| {
+                    transitions.contains(&(
+                        CompiledRegex::state_val(states[*position]),
+                        CompiledRegex::state_val(states[*position + 1]),
+                    ))
+                })
+                .collect();
+            match (positions.first(), positions.last()) {
+                (Some(start), Some(end)) if end - start + 1 != positions.len() => None,
+                _ => Some(positions.iter().map(|position| input[*position]).collect()),
+            }
+        })
+        .collect()
+}
+
+/// Returns the captures of [`extract_captures`] as a JSON object mapping each group's name to
+/// its captured string, for debugging. Unnamed groups are keyed by their index, and bytes that are
+/// not UTF-8 are replaced by U+FFFD.
+pub fn extract_captures_json(input: &[u8], compiled: &CompiledRegex) -> Option<String> {
+    let fields: Vec<String> = extract_captures(input, compiled)?
+        .iter()
+        .enumerate()
+        .map(|(group, capture)| {
+            let key = match compiled.capture_name(group) {
+                Some(name) => json_string(name),
+                None => json_string(&group.to_string()),
+            };
+            format!("{}:{}", key, json_string(&String::from_utf8_lossy(capture)))
+        })
+        .collect();
+    Some(format!("{{{}}}", fields.join(",")))
+}
+
+fn json_string(value: &str) -> String {
+    let mut escaped = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+impl fmt::Debug for CompiledRegex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let alphabet: BTreeSet<u8> = self
+            .dfa
+            .states
+            .iter()
+            .flat_map(|state| state.edges.keys().copied())
+            .collect();
+        let mut summary = f.debug_struct("CompiledRegex");
+        if !self.pattern.is_empty() {
+            summary.field("pattern", &self.pattern);
+        }
+        summary
+            .field("num_states", &self.dfa.num_states())
+            .field("alphabet_size", &alphabet.len())
+            .field("accept_states", &self.dfa.accept_states())
+            .field(
+                "capture_transitions",
+                &(0..self.num_captures)
+                    .map(|group| self.capture_transition_set(group).len())
+                    .collect::<Vec<_>>(),
+            )
+            .finish()
+    }
+}
+
+impl CompiledRegex {
+    pub fn state_val(idx: usize) -> u64 {
+        idx as u64 + 1
+    }
+
+    pub fn is_match(&self, input: &[u8]) -> bool {
+        self.dfa.is_match(input)
+    }
+
+    /// The length of the longest input the regex matches, or `None` if it is unbounded, e.g. due
+    /// to `*` or `+`; see [`DfaGraph::max_match_len`]. A bounded regex fits a circuit with
+    /// `max_chars_size` of this length.
+    pub fn max_match_len(&self) -> Option<usize> {
+        self.dfa.max_match_len()
+    }
+
+    /// A random input of at most `max_len` bytes that the regex matches, e.g. for benchmarks and
+    /// fuzzing, or `None` if every match is longer. It walks the DFA from the start, choosing
+    /// uniformly between stopping in an accept state and each byte after which an accept state
+    /// is still reachable within `max_len`, so walks through unbounded patterns end in time.
+    #[cfg(any(test, feature = "rand"))]
+    pub fn random_match<R: rand::Rng + ?Sized>(
+        &self,
+        rng: &mut R,
+        max_len: usize,
+    ) -> Option<Vec<u8>> {
+        let distances = self.dfa.accept_distances();
+        let mut state = self.dfa.start;
+        if distances[state]? > max_len {
+            return None;
+        }
+        let mut input = vec![];
+        // An accept state is at most `max_len - input.len()` bytes away from `state`, so there
+        // is always a choice.
+        loop {
+            let remaining = max_len - input.len();
+            let edges: Vec<(u8, usize)> = self.dfa.states[state]
+                .edges
+                .iter()
+                .filter(
+                    |(_, next)| matches!(distances[**next], Some(distance) if distance < remaining),
+                )
+                .map(|(byte, next)| (*byte, *next))
+                .collect();
+            let choice = rng.gen_range(0..edges.len() + self.dfa.states[state].accept as usize);
+            match edges.get(choice) {
+                Some((byte, next)) => {
+                    input.push(*byte);
+                    state = *next;
+                }
+                None => return Some(input),
+            }
+        }
+    }
+
+    /// The ids of the patterns the state with value `state_val` accepts, e.g. the
+    /// [`crate::AssignedRegexResult::accepted_state`] of a check, if the regex was compiled with
+    /// [`CompileOptions::pattern_ids`] or by [`Self::from_keywords_with_ids`]. Empty for other
+    /// states and regexes.
+    pub fn accept_patterns(&self, state_val: u64) -> &[usize] {
+        match (state_val as usize).checked_sub(1) {
+            Some(idx) if idx < self.dfa.num_states() => &self.dfa.states[idx].patterns,
+            _ => &[],
+        }
+    }
+
+    /// The ids of the patterns that match the whole `input`, like [`Self::accept_patterns`] of
+    /// the state it ends in, or `None` if it is rejected.
+    pub fn matched_patterns(&self, input: &[u8]) -> Option<&[usize]> {
+        let mut state = self.dfa.start;
+        for byte in input.iter() {
+            state = *self.dfa.states[state].edges.get(byte)?;
+        }
+        let state = &self.dfa.states[state];
+        state.accept.then_some(&state.patterns[..])
+    }
+
+    /// The name of capture `group`, if it has one.
+    pub fn capture_name(&self, group: usize) -> Option<&str> {
+        self.capture_names.get(group)?.as_deref()
+    }
+
+    /// The index of the capture group named `name`.
+    pub fn capture_index(&self, name: &str) -> Option<usize> {
+        self.capture_names
+            .iter()
+            .position(|group_name| group_name.as_deref() == Some(name))
+    }
+
+    /// Names the capture groups in order; `names` may be shorter than the number of groups.
+    pub fn with_capture_names(mut self, names: &[&str]) -> Self {
+        self.capture_names = (0..self.num_captures)
+            .map(|group| names.get(group).map(|name| name.to_string()))
+            .collect();
+        self
+    }
+
+    /// Adds a transition from the state value `prev` to `next` on `char`, for transitions the
+    /// pattern cannot express, e.g. on a sentinel byte. It is taken by [`Self::is_match`] and
+    /// included in [`Self::regex_def`] like any other, but belongs to no capture. Fails with
+    /// [`RegexError::UnknownState`] if a state does not exist, and with
+    /// [`RegexError::ConflictingTransition`] if `prev` already leads elsewhere on `char`, which
+    /// would make the DFA nondeterministic. The DFA is not minimized again.
+    pub fn add_transition(&mut self, prev: u64, char: u8, next: u64) -> Result<(), RegexError> {
+        let index = |state: u64| match (state as usize).checked_sub(1) {
+            Some(idx) if idx < self.dfa.num_states() => Ok(idx),
+            _ => Err(RegexError::UnknownState { state }),
+        };
+        let (prev_idx, next_idx) = (index(prev)?, index(next)?);
+        let edges = &mut self.dfa.states[prev_idx].edges;
+        match edges.get(&char) {
+            Some(existing) if *existing != next_idx => Err(RegexError::ConflictingTransition {
+                state: prev,
+                char,
+                existing: Self::state_val(*existing),
+            }),
+            _ => {
+                edges.insert(char, next_idx);
+                Ok(())
+            }
+        }
+    }
+
+    #[cfg(feature = "std")]
+    pub fn regex_def(&self) -> RegexDef {
+        let mut state_lookup = HashMap::new();
+        for (idx, state) in self.dfa.states.iter().enumerate() {
+            for (char, next) in state.edges.iter() {
+                state_lookup.insert((*char, Self::state_val(idx)), Self::state_val(*next));
+            }
+        }
+        RegexDef {
+            state_lookup,
+            first_state_val: Self::state_val(self.dfa.start),
+            accepted_state_vals: self
+                .dfa
+                .accept_states()
+                .into_iter()
+                .map(Self::state_val)
+                .collect(),
+            largest_state_val: self.dfa.num_states() as u64,
+        }
+    }
+
+    /// The `(prev_state, next_state)` transitions that consume a character of capture `group`.
+    #[cfg(feature = "std")]
+    pub fn capture_transitions(&self, group: usize) -> HashSet<(u64, u64)> {
+        self.capture_transition_set(group).into_iter().collect()
+    }
+
+    /// The pairs of capture groups that share a `(prev_state, next_state)` transition, such as
+    /// nested groups or adjacent groups that both repeat the same characters. Since substrings
+    /// are extracted by transition, a character consumed by a shared transition is extracted
+    /// into both groups.
+    pub fn ambiguous_captures(&self) -> Vec<(usize, usize)> {
+        let transitions: Vec<BTreeSet<(u64, u64)>> = (0..self.num_captures)
+            .map(|group| self.capture_transition_set(group))
+            .collect();
+        let mut pairs = vec![];
+        for first in 0..self.num_captures {
+            for second in first + 1..self.num_captures {
+                if !transitions[first].is_disjoint(&transitions[second]) {
+                    pairs.push((first, second));
+                }
+            }
+        }
+        pairs
+    }
+
+    fn capture_transition_set(&self, group: usize) -> BTreeSet<(u64, u64)> {
+        let mut transitions = BTreeSet::new();
+        for (idx, state) in self.dfa.states.iter().enumerate() {
+            for (char, captures) in state.captures.iter() {
+                if captures.contains(&group) {
+                    transitions.insert((Self::state_val(idx), Self::state_val(state.edges[char])));
+                }
+            }
+        }
+        transitions
+    }
+
+    #[cfg(feature = "std")]
+    pub fn substr_def(
+        &self,
+        group: usize,
+        max_length: usize,
+        min_position: u64,
+        max_position: u64,
+    ) -> SubstrDef {
+        let substr_def = SubstrDef::new(
+            max_length,
+            min_position,
+            max_position,
+            self.capture_transitions(group),
+        );
+        match self.capture_name(group) {
+            Some(name) => substr_def.with_name(name),
+            None => substr_def,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compile_literal_and_quantifiers() {
+        let compiled = compile_regex("ab+c*d{2}").unwrap();
+        assert!(compiled.is_match(b"abdd"));
+        assert!(compiled.is_match(b"abbbccdd"));
+        assert!(!compiled.is_match(b"acdd"));
+        assert!(!compiled.is_match(b"abddd"));
+        let regex_def = compiled.regex_def();
+        assert_eq!(regex_def.first_state_val, 1);
+        assert_eq!(
+            regex_def.largest_state_val as usize,
+            compiled.dfa.num_states()
+        );
+    }
+
+    #[test]
+    fn test_compile_optional() {
+        let compiled = compile_regex("ab?c").unwrap();
+        assert!(compiled.is_match(b"ac"));
+        assert!(compiled.is_match(b"abc"));
+        assert!(!compiled.is_match(b"abbc"));
+        // The bypass of `b?` merges into the state after `a`, so "ac" and "abc" end in the
+        // same accept state.
+        assert_eq!(compiled.dfa.accept_states().len(), 1);
+
+        let compiled = compile_regex("x(abc)?y").unwrap();
+        assert!(compiled.is_match(b"xy"));
+        assert!(compiled.is_match(b"xabcy"));
+        assert!(!compiled.is_match(b"xaby"));
+        assert!(!compiled.is_match(b"xabcabcy"));
+        assert_eq!(
+            extract_captures(b"xabcy", &compiled),
+            Some(vec![b"abc".to_vec()])
+        );
+        assert_eq!(extract_captures(b"xy", &compiled), Some(vec![vec![]]));
+
+        let compiled = compile_regex("[0-9]?[a-c]?").unwrap();
+        for input in ["", "5", "b", "5b"] {
+            assert!(compiled.is_match(input.as_bytes()), "{}", input);
+        }
+        for input in ["b5", "55", "d"] {
+            assert!(!compiled.is_match(input.as_bytes()), "{}", input);
+        }
+        // `a??` is `(a?)?`, the same language as `a?`.
+        assert_eq!(
+            compile_regex("a??").unwrap().dfa,
+            compile_regex("a?").unwrap().dfa
+        );
+    }
+
+    #[test]
+    fn test_compile_empty_alternative() {
+        let compiled = compile_regex("x(abc|)y").unwrap();
+        assert!(compiled.is_match(b"xy"));
+        assert!(compiled.is_match(b"xabcy"));
+        assert!(!compiled.is_match(b"xaby"));
+        assert!(!compiled.is_match(b"xabcabcy"));
+        assert_eq!(
+            extract_captures(b"xabcy", &compiled),
+            Some(vec![b"abc".to_vec()])
+        );
+        assert_eq!(extract_captures(b"xy", &compiled), Some(vec![vec![]]));
+        assert_eq!(compiled.dfa, compile_regex("x(abc)?y").unwrap().dfa);
+
+        // The empty branch at the start, in the middle and at the end of a group.
+        for pattern in ["x(|abc)y", "x(?:abc|)y", "x(a||bc)y", "x(a|bc|)y"] {
+            let compiled = compile_regex(pattern).unwrap();
+            assert!(compiled.is_match(b"xy"), "{}", pattern);
+            assert!(!compiled.is_match(b"xaby"), "{}", pattern);
+        }
+        assert!(compile_regex("x(a||bc)y").unwrap().is_match(b"xbcy"));
+        assert_eq!(
+            compile_regex("(?:|a)(?:b|)").unwrap().dfa,
+            compile_regex("a?b?").unwrap().dfa
+        );
+
+        // A top-level empty branch matches the empty input.
+        let compiled = compile_regex("abc|").unwrap();
+        assert!(compiled.is_match(b""));
+        assert!(compiled.is_match(b"abc"));
+        assert!(!compiled.is_match(b"ab"));
+    }
+
+    #[test]
+    fn test_compile_escape_s() {
+        let compiled = compile_regex(r"a\sb").unwrap();
+        for space in [b' ', b'\t', b'\r', b'\n', 0x0b, 0x0c] {
+            assert!(compiled.is_match(&[b'a', space, b'b']));
+        }
+        assert!(!compiled.is_match(b"a_b"));
+        let compiled = compile_regex(r"\S+").unwrap();
+        assert!(compiled.is_match(b"abc"));
+        for space in [b' ', b'\t', b'\r', b'\n', 0x0b, 0x0c] {
+            assert!(!compiled.is_match(&[b'a', space]));
+        }
+    }
+
+    #[test]
+    fn test_compile_escape_d() {
+        let compiled = compile_regex(r"\d+").unwrap();
+        assert!(compiled.is_match(b"0123456789"));
+        assert!(!compiled.is_match(b"12a"));
+        let compiled = compile_regex(r"\D+").unwrap();
+        assert!(compiled.is_match(b"ab -"));
+        assert!(!compiled.is_match(b"a1"));
+    }
+
+    #[test]
+    fn test_compile_escape_w() {
+        let compiled = compile_regex(r"\w+").unwrap();
+        assert!(compiled.is_match(b"azAZ09_"));
+        assert!(!compiled.is_match(b"a-b"));
+        let compiled = compile_regex(r"\W").unwrap();
+        assert!(compiled.is_match(b"@"));
+        assert!(!compiled.is_match(b"_"));
+    }
+
+    #[test]
+    fn test_compile_escape_in_class() {
+        let compiled = compile_regex(r"[\d\s.]+").unwrap();
+        assert!(compiled.is_match(b"1. 2\r\n"));
+        assert!(!compiled.is_match(b"1a"));
+    }
+
+    #[test]
+    fn test_compile_captures() {
+        let compiled = compile_regex(r"[a-z]+@([a-z]+)\.com").unwrap();
+        assert_eq!(compiled.num_captures, 1);
+        assert!(compiled.is_match(b"alice@mail.com"));
+        // The domain self-loop and the transition entering it belong to the capture.
+        assert_eq!(compiled.capture_transitions(0).len(), 2);
+    }
+
+    #[test]
+    fn test_extract_captures_email() {
+        let compiled = compile_regex(r"[a-z0-9._]+@([a-z0-9-]+\.[a-z]+)").unwrap();
+        let captures = extract_captures(b"alice.smith@example.com", &compiled);
+        assert_eq!(captures, Some(vec![b"example.com".to_vec()]));
+        // No proof exists for an input that is not matched, even if a prefix captures bytes.
+        assert_eq!(extract_captures(b"alice.smith@example", &compiled), None);
+
+        let compiled = compile_regex(r"([a-z.]+)@([a-z.]+)").unwrap();
+        let captures = extract_captures(b"bob@mail.org", &compiled);
+        assert_eq!(captures, Some(vec![b"bob".to_vec(), b"mail.org".to_vec()]));
+
+        // Every transition of a repeated group is masked, which is not one contiguous run.
+        let compiled = compile_regex(r"(?:([a-z])[0-9])+").unwrap();
+        assert_eq!(
+            extract_captures(b"a1", &compiled),
+            Some(vec![b"a".to_vec()])
+        );
+        assert_eq!(extract_captures(b"a1b2", &compiled), None);
+    }
+
+    #[test]
+    fn test_extract_captures_json() {
+        let compiled = compile_regex(r"([a-z.]+)@([a-z.]+)")
+            .unwrap()
+            .with_capture_names(&["local", "domain"]);
+        assert_eq!(compiled.capture_name(1), Some("domain"));
+        let json = extract_captures_json(b"alice.smith@mail.org", &compiled).unwrap();
+        assert_eq!(json, r#"{"local":"alice.smith","domain":"mail.org"}"#);
+        #[cfg(feature = "std")]
+        assert_eq!(
+            compiled.substr_def(1, 8, 0, 20).name.as_deref(),
+            Some("domain")
+        );
+
+        // Unnamed groups are keyed by index, and strings are escaped.
+        let compiled = compile_regex(r#"([a-z]+)=([^;]*);"#)
+            .unwrap()
+            .with_capture_names(&["key"]);
+        let json = extract_captures_json(b"quote=a\"b\\\n;", &compiled).unwrap();
+        assert_eq!(json, r#"{"key":"quote","1":"a\"b\\\n"}"#);
+        assert_eq!(
+            extract_captures_json(b"k=;", &compiled).as_deref(),
+            Some(r#"{"key":"k","1":""}"#)
+        );
+    }
+
+    #[test]
+    fn test_dfa_reverse() {
+        let reversed = compile_regex("abc").unwrap().dfa.reverse();
+        assert!(reversed.is_match(b"cba"));
+        assert!(!reversed.is_match(b"abc"));
+
+        let compiled = compile_regex("a(b|cd)*e").unwrap();
+        let reversed = compiled.dfa.reverse();
+        for input in ["ae", "abe", "acdbe", "abcdcde"] {
+            let rev_input: Vec<u8> = input.bytes().rev().collect();
+            assert!(reversed.is_match(&rev_input), "{}", input);
+            assert!(!reversed.is_match(input.as_bytes()), "{}", input);
+        }
+        assert!(!reversed.is_match(b"eca"));
+        assert_eq!(reversed.reverse(), compiled.dfa);
+    }
+
+    #[test]
+    fn test_dfa_char_classes() {
+        let classes = compile_regex("[a-z]+").unwrap().dfa.char_classes();
+        assert_eq!(classes.len(), 2);
+        assert_eq!(classes[1], (b'a'..=b'z').collect::<Vec<u8>>());
+        assert_eq!(classes[0].len(), 256 - 26);
+
+        // `c` alone leads elsewhere after `a`, and the capture separates `x` from `y`.
+        let classes = compile_regex("a[b-d]+(x)|ac[xy]")
+            .unwrap()
+            .dfa
+            .char_classes();
+        let class_of = |byte: u8| classes.iter().position(|class| class.contains(&byte));
+        assert_eq!(class_of(b'b'), class_of(b'd'));
+        assert_ne!(class_of(b'b'), class_of(b'c'));
+        assert_ne!(class_of(b'x'), class_of(b'y'));
+        assert_eq!(classes.iter().map(|class| class.len()).sum::<usize>(), 256);
+    }
+
+    #[test]
+    fn test_dfa_distinguishing_input() {
+        let dfa = |pattern: &str| compile_regex(pattern).unwrap().dfa;
+        assert_eq!(dfa("a+").distinguishing_input(&dfa("aa*")), None);
+        assert_eq!(dfa("a+").distinguishing_input(&dfa("a*")), Some(vec![]));
+        assert_eq!(
+            dfa("a{2,}").distinguishing_input(&dfa("a+")),
+            Some(b"a".to_vec())
+        );
+        // Both `by` and `bxy` tell them apart; only the shorter one is returned.
+        assert_eq!(
+            dfa("[a-c]x").distinguishing_input(&dfa("[a-c]x|bxy|by")),
+            Some(b"by".to_vec())
+        );
+        let input = dfa("[a-z]+@[a-z]+\\.com")
+            .distinguishing_input(&dfa("[a-z]+@[a-z]+\\.(com|org)"))
+            .unwrap();
+        assert_eq!(input, b"a@a.org".to_vec());
+    }
+
+    #[test]
+    fn test_ambiguous_captures() {
+        // Both groups can consume the `a`s in the middle.
+        let compiled = compile_regex("(a+)(a+)b").unwrap();
+        assert_eq!(compiled.ambiguous_captures(), vec![(0, 1)]);
+        // A nested group shares every transition with its parent.
+        let compiled = compile_regex("x((a)b)y").unwrap();
+        assert_eq!(compiled.ambiguous_captures(), vec![(0, 1)]);
+
+        let compiled = compile_regex(r"([a-z]+)@([a-z]+\.com)").unwrap();
+        assert!(compiled.ambiguous_captures().is_empty());
+
+        #[cfg(feature = "std")]
+        {
+            let compiled = compile_regex("(a+)(a+)b").unwrap();
+            let substr_defs = [
+                compiled.substr_def(0, 8, 0, 8),
+                compiled.substr_def(1, 8, 0, 8),
+            ];
+            assert_eq!(
+                SubstrDef::check_disjoint(&substr_defs),
+                Err(RegexError::AmbiguousCaptures {
+                    first: 0,
+                    second: 1
+                })
+            );
+            let compiled = compile_regex(r"([a-z]+)@([a-z]+\.com)").unwrap();
+            let substr_defs = [
+                compiled.substr_def(0, 8, 0, 8),
+                compiled.substr_def(1, 8, 0, 8),
+            ];
+            assert_eq!(SubstrDef::check_disjoint(&substr_defs), Ok(()));
+        }
+    }
+
+    #[test]
+    fn test_dfa_validate() {
+        for pattern in ["ab+", "a*", "(cat|dog)s?"] {
+            let compiled = compile_regex(pattern).unwrap();
+            assert!(!compiled.dfa.is_empty_language());
+            assert_eq!(compiled.dfa.validate(), Ok(()));
+        }
+
+        // The accept state has no incoming transition.
+        let mut dfa = DfaGraph {
+            states: vec![
+                DfaState {
+                    edges: [(b'a', 1)].into_iter().collect(),
+                    ..Default::default()
+                },
+                DfaState {
+                    edges: [(b'a', 1)].into_iter().collect(),
+                    ..Default::default()
+                },
+                DfaState {
+                    accept: true,
+                    ..Default::default()
+                },
+            ],
+            start: 0,
+        };
+        assert!(dfa.is_empty_language());
+        assert_eq!(dfa.validate(), Err(RegexError::EmptyLanguage));
+        dfa.states[1].edges.insert(b'b', 2);
+        assert!(!dfa.is_empty_language());
+        assert_eq!(dfa.validate(), Ok(()));
+    }
+
+    #[test]
+    fn test_dfa_prune() {
+        let compiled = compile_regex("ab+").unwrap();
+        let mut dfa = compiled.dfa.clone();
+        let num_states = dfa.num_states();
+        // An orphan accept state that only loops to itself and to the start.
+        dfa.states.push(DfaState {
+            accept: true,
+            edges: [(b'x', num_states), (b'y', 0)].into_iter().collect(),
+            ..Default::default()
+        });
+        // A dead state reachable on `c` from the start.
+        dfa.states.push(DfaState {
+            edges: [(b'z', num_states + 1)].into_iter().collect(),
+            ..Default::default()
+        });
+        dfa.states[0].edges.insert(b'c', num_states + 1);
+
+        let reachable = dfa.prune_unreachable();
+        assert_eq!(reachable.num_states(), num_states + 1);
+        assert_eq!(reachable.accept_states(), compiled.dfa.accept_states());
+        let pruned = reachable.prune_dead();
+        assert_eq!(pruned, compiled.dfa);
+        for input in ["ab", "abbb", "a", "c", "cz", "x"] {
+            assert_eq!(
+                pruned.is_match(input.as_bytes()),
+                dfa.is_match(input.as_bytes())
+            );
+        }
+
+        // Pruning the dead states of an empty language keeps the start.
+        let empty = DfaGraph {
+            states: vec![DfaState {
+                edges: [(b'a', 0)].into_iter().collect(),
+                ..Default::default()
+            }],
+            start: 0,
+        };
+        let pruned = empty.prune_dead();
+        assert_eq!(pruned.num_states(), 1);
+        assert!(pruned.states[0].edges.is_empty());
+    }
+
+    #[test]
+    fn test_dfa_to_dot() {
+        let dfa = compile_regex(r#"[a-c]x|y+|"\n"#).unwrap().dfa;
+        let dot = dfa.to_dot();
+        let count = |needle: &str| dot.lines().filter(|line| line.contains(needle)).count();
+        assert_eq!(count("circle]"), dfa.num_states());
+        assert_eq!(count("doublecircle"), dfa.accept_states().len());
+        // One edge per connected pair of states, plus the start arrow.
+        assert_eq!(count("->"), 7);
+        assert_eq!(count("[label="), 6);
+        assert!(dot.contains(r#"[label="a-c"]"#));
+        assert!(dot.contains(r#"[label="\""]"#));
+        assert!(dot.contains(r#"[label="\\x0a"]"#));
+        assert!(dot.starts_with("digraph dfa {") && dot.ends_with("}\n"));
+    }
+
+    #[test]
+    fn test_compile_line_endings() {
+        let pattern = r"subject:[a-z ]+\nto:[a-z]+\r\n";
+        let exact = compile_regex(pattern).unwrap();
+        assert_eq!(
+            compile_regex_with(pattern, &CompileOptions::default())
+                .unwrap()
+                .dfa,
+            exact.dfa
+        );
+        assert!(exact.is_match(b"subject:hi\nto:bob\r\n"));
+        assert!(!exact.is_match(b"subject:hi\r\nto:bob\r\n"));
+
+        let options = CompileOptions {
+            line_endings: LineEndings::AcceptCrlf,
+            ..Default::default()
+        };
+        let crlf = compile_regex_with(pattern, &options).unwrap();
+        assert!(crlf.is_match(b"subject:hi\nto:bob\r\n"));
+        assert!(crlf.is_match(b"subject:hi\r\nto:bob\r\n"));
+        assert!(!crlf.is_match(b"subject:hi\nto:bob\n"));
+        assert!(!crlf.is_match(b"subject:hi\r\r\nto:bob\r\n"));
+
+        let options = CompileOptions {
+            line_endings: LineEndings::Any,
+            ..Default::default()
+        };
+        let any = compile_regex_with(pattern, &options).unwrap();
+        for input in [
+            "subject:hi\nto:bob\n",
+            "subject:hi\r\nto:bob\n",
+            "subject:hi\nto:bob\r\n",
+            "subject:hi\r\nto:bob\r\n",
+        ] {
+            assert!(any.is_match(input.as_bytes()), "{:?}", input);
+        }
+        assert!(!any.is_match(b"subject:hi\rto:bob\n"));
+        // Classes containing a line feed among other bytes are left as written.
+        let class = compile_regex_with(r"a\sb", &options).unwrap();
+        assert!(!class.is_match(b"a\r\nb"));
+    }
+
+    #[test]
+    fn test_compile_crlf_literals() {
+        // Escaped and raw line breaks are ordinary literals.
+        for pattern in [r"A\r\nB", "A\r\nB", r"A\x0d\x0aB"] {
+            let compiled = compile_regex(pattern).unwrap();
+            assert!(compiled.is_match(b"A\r\nB"), "{:?}", pattern);
+            for input in ["A\nB", "A\rB", "A\n\rB", "A\r\n\r\nB", "AB"] {
+                assert!(!compiled.is_match(input.as_bytes()), "{:?}", input);
+            }
+            assert_eq!(compiled.dfa, compile_regex(r"A\r\nB").unwrap().dfa);
+        }
+
+        // The DFA tells `\r` and `\n` apart: only a `\n` may follow a `\r`.
+        let regex_def = compile_regex(r"A(?:\r\n|\n)B").unwrap().regex_def();
+        let states = regex_def.gen_states(b"A\r\nB").unwrap();
+        assert_ne!(
+            regex_def.next_state(states[1], b'\r'),
+            regex_def.next_state(states[1], b'\n')
+        );
+        assert_eq!(regex_def.next_state(states[2], b'\r'), None);
+        assert!(regex_def.gen_states(b"A\nB").is_ok());
+        assert!(regex_def.gen_states(b"A\n\rB").is_err());
+    }
+
+    #[test]
+    fn test_compiled_regex_debug() {
+        let compiled = compile_regex(r"[a-z]+@([a-z]+)\.com").unwrap();
+        assert_eq!(
+            format!("{:?}", compiled),
+            r#"CompiledRegex { pattern: "[a-z]+@([a-z]+)\\.com", num_states: 8, alphabet_size: 28, accept_states: [7], capture_transitions: [2] }"#
+        );
+        let unnamed = CompiledRegex {
+            pattern: String::new(),
+            ..compiled
+        };
+        assert!(format!("{:?}", unnamed).starts_with("CompiledRegex { num_states: 8,"));
+    }
+
+    #[test]
+    fn test_compile_unsupported_features() {
+        for (pattern, feature) in [
+            (r"(a+)b\1", "backreference"),
+            (r"(?:x)[a-z]\9", "backreference"),
+            (r"(a)\k<first>", "backreference"),
+            ("a(?=b)", "lookahead"),
+            ("a(?!b)", "lookahead"),
+            ("(?<=a)b", "lookbehind"),
+            ("(?<!a)b", "lookbehind"),
+            (r"\bword\b", "word boundary"),
+            (r"a\Bb", "word boundary"),
+        ] {
+            assert_eq!(
+                compile_regex(pattern).unwrap_err(),
+                RegexError::UnsupportedFeature(feature),
+                "{}",
+                pattern
+            );
+        }
+        // `\0` is a NUL byte, not a backreference.
+        assert!(compile_regex(r"a\0").unwrap().is_match(b"a\0"));
+    }
+
+    #[test]
+    fn test_named_captures() {
+        let compiled = compile_regex(r"[a-z]+@(?P<domain>[a-z]+)").unwrap();
+        assert_eq!(compiled.num_captures, 1);
+        assert_eq!(compiled.capture_name(0), Some("domain"));
+        assert_eq!(compiled.capture_index("domain"), Some(0));
+        #[cfg(feature = "std")]
+        assert_eq!(
+            compiled.substr_def(0, 8, 0, 16).name.as_deref(),
+            Some("domain")
+        );
+        assert_eq!(compiled.dfa, compile_regex(r"[a-z]+@([a-z]+)").unwrap().dfa);
+
+        let compiled = compile_regex(r"(?P<local>[a-z.]+)@([a-z]+)\.(?P<tld_2>[a-z]+)").unwrap();
+        assert_eq!(
+            compiled.capture_names,
+            vec![Some("local".into()), None, Some("tld_2".into())]
+        );
+        assert_eq!(
+            extract_captures_json(b"bob@mail.org", &compiled).as_deref(),
+            Some(r#"{"local":"bob","1":"mail","tld_2":"org"}"#)
+        );
+
+        for (pattern, position) in [
+            ("(?P<a>x)(?P<a>y)", 12),
+            ("(?P<>x)", 4),
+            ("(?P<1a>x)", 4),
+            ("(?P<a-b>x)", 5),
+            ("(?P<ab", 6),
+        ] {
+            assert!(
+                matches!(
+                    compile_regex(pattern),
+                    Err(RegexError::Syntax { position: p, .. }) if p == position
+                ),
+                "{}",
+                pattern
+            );
+        }
+    }
+
+    #[test]
+    fn test_compile_too_deep() {
+        let nested = |depth: usize| format!("{}a{}", "(".repeat(depth), ")".repeat(depth));
+        assert!(compile_regex(&nested(DEFAULT_MAX_DEPTH))
+            .unwrap()
+            .is_match(b"a"));
+        assert_eq!(
+            compile_regex(&nested(100_000)).unwrap_err(),
+            RegexError::TooDeep {
+                position: DEFAULT_MAX_DEPTH,
+                max_depth: DEFAULT_MAX_DEPTH,
+            }
+        );
+        let stacked = format!("a{}", "?".repeat(100_000));
+        assert!(matches!(
+            compile_regex(&stacked),
+            Err(RegexError::TooDeep { position: 129, .. })
+        ));
+
+        let options = CompileOptions {
+            max_depth: Some(2),
+            ..Default::default()
+        };
+        assert!(compile_regex_with("(a(b)*)", &options).is_ok());
+        assert!(compile_regex_with("(a(b)*)*", &options).is_ok());
+        assert_eq!(
+            compile_regex_with("(a(?:b*))", &options).unwrap_err(),
+            RegexError::TooDeep {
+                position: 6,
+                max_depth: 2,
+            }
+        );
+        assert!(compile_regex_with("((()))", &options).is_err());
+    }
+
+    #[test]
+    fn test_compile_too_many_alternatives() {
+        let branches: Vec<String> = (0..5000).map(|i| format!("k{}", i)).collect();
+        let wide = format!("x({})", branches.join("|"));
+        assert!(compile_regex(&wide).unwrap().is_match(b"xk4999"));
+
+        let options = CompileOptions {
+            max_alternatives: Some(1000),
+            ..Default::default()
+        };
+        // The error points at the 1000th `|`, which would start the 1001st branch.
+        let position = 2 + branches[..1000].iter().map(|b| b.len() + 1).sum::<usize>() - 1;
+        assert_eq!(
+            compile_regex_with(&wide, &options).unwrap_err(),
+            RegexError::TooManyAlternatives {
+                position,
+                max_alternatives: 1000,
+            }
+        );
+        let narrow = format!("x({})(?:a|b)", branches[..1000].join("|"));
+        assert!(compile_regex_with(&narrow, &options)
+            .unwrap()
+            .is_match(b"xk999b"));
+
+        // Each alternation is counted on its own, including the top-level one.
+        let options = CompileOptions {
+            max_alternatives: Some(2),
+            ..Default::default()
+        };
+        assert!(compile_regex_with("(a|b)(c|d)|e", &options).is_ok());
+        assert!(matches!(
+            compile_regex_with("a|b|c", &options),
+            Err(RegexError::TooManyAlternatives { position: 3, .. })
+        ));
+    }
+
+    #[test]
+    fn test_compile_case_insensitive() {
+        let options = CompileOptions {
+            case_insensitive: true,
+            ..Default::default()
+        };
+        let compiled = compile_regex_with("stra(?:ß|ss)e", &options).unwrap();
+        for input in ["strasse", "STRASSE", "Straße", "STRAẞE", "StraSSe"] {
+            assert!(compiled.is_match(input.as_bytes()), "{}", input);
+        }
+        assert!(!compiled.is_match("STRASE".as_bytes()));
+        assert!(!compile_regex("stra(?:ß|ss)e")
+            .unwrap()
+            .is_match("STRASSE".as_bytes()));
+
+        // Simple folding maps one character to one.
+        let compiled = compile_regex_with("ß", &options).unwrap();
+        assert!(compiled.is_match("ẞ".as_bytes()));
+        assert!(!compiled.is_match(b"ss"));
+        assert!(!compiled.is_match(b"SS"));
+        let compiled = compile_regex_with("i", &options).unwrap();
+        assert!(compiled.is_match(b"I"));
+        assert!(!compiled.is_match("İ".as_bytes()));
+        assert!(!compiled.is_match("ı".as_bytes()));
+
+        for (pattern, inputs) in [
+            ("k", &["K", "\u{212a}"][..]),
+            ("σ", &["Σ", "ς"]),
+            ("é", &["É"]),
+            ("s", &["S", "ſ"]),
+        ] {
+            let compiled = compile_regex_with(pattern, &options).unwrap();
+            for input in inputs.iter() {
+                assert!(compiled.is_match(input.as_bytes()), "{} {}", pattern, input);
+            }
+        }
+
+        // Classes fold their ASCII letters, negated ones too.
+        let compiled = compile_regex_with("[a-c]+[^x]", &options).unwrap();
+        assert!(compiled.is_match(b"AbCy"));
+        assert!(!compiled.is_match(b"abcX"));
+    }
+
+    #[test]
+    fn test_compile_pattern_ids() {
+        let options = CompileOptions {
+            pattern_ids: true,
+            ..Default::default()
+        };
+        let pattern = r"[a-z]+@gmail\.com|[a-z]+@[a-z]+\.com|admin";
+        let compiled = compile_regex_with(pattern, &options).unwrap();
+        assert_eq!(
+            compiled.matched_patterns(b"bob@gmail.com"),
+            Some(&[0, 1][..])
+        );
+        assert_eq!(compiled.matched_patterns(b"bob@mail.com"), Some(&[1][..]));
+        assert_eq!(compiled.matched_patterns(b"admin"), Some(&[2][..]));
+        assert_eq!(compiled.matched_patterns(b"admin@"), None);
+        let states = compiled.regex_def().gen_states(b"bob@gmail.com").unwrap();
+        assert_eq!(compiled.accept_patterns(*states.last().unwrap()), &[0, 1]);
+        assert_eq!(compiled.accept_patterns(states[0]), &[] as &[usize]);
+        assert_eq!(compiled.accept_patterns(0), &[] as &[usize]);
+        // The same language, with the accept states of the alternatives kept apart.
+        let plain = compile_regex(pattern).unwrap();
+        assert_eq!(plain.matched_patterns(b"admin"), Some(&[][..]));
+        assert_eq!(plain.dfa.distinguishing_input(&compiled.dfa), None);
+        assert!(plain.dfa.num_states() < compiled.dfa.num_states());
+        // A pattern without a top-level alternation is pattern 0.
+        let compiled = compile_regex_with("(a|b)+", &options).unwrap();
+        assert_eq!(compiled.matched_patterns(b"ab"), Some(&[0][..]));
+    }
+
+    #[test]
+    fn test_compile_dot_matches_newline() {
+        let options = |dot_matches_newline| CompileOptions {
+            dot_matches_newline,
+            ..Default::default()
+        };
+        // By default `.` stops at a line feed, but not at a carriage return.
+        let compiled = compile_regex("a.*b").unwrap();
+        assert!(compiled.is_match(b"a x\rb"));
+        assert!(!compiled.is_match(b"a x\nb"));
+        assert!(compile_regex(r"a.*\n.*b").unwrap().is_match(b"a x\nb"));
+
+        let compiled = compile_regex_with("a.*b", &options(DotMatchesNewline::NoCrLf)).unwrap();
+        assert!(compiled.is_match(b"a xb"));
+        assert!(!compiled.is_match(b"a x\rb"));
+        assert!(!compiled.is_match(b"a x\nb"));
+
+        let compiled = compile_regex_with("a.*b", &options(DotMatchesNewline::Yes)).unwrap();
+        assert!(compiled.is_match(b"a x\r\nb"));
+        assert!(compiled.is_match(b"a\x00\xffb"));
+
+        // The wildcard expands into a transition per byte it matches.
+        let rows = |mode| {
+            compile_regex_with(".", &options(mode))
+                .unwrap()
+                .regex_def()
+                .state_lookup
+                .len()
+        };
+        assert_eq!(rows(DotMatchesNewline::No), 255);
+        assert_eq!(rows(DotMatchesNewline::NoCrLf), 254);
+        assert_eq!(rows(DotMatchesNewline::Yes), 256);
+    }
+
+    #[test]
+    fn test_negated_class_captures() {
+        let pattern = r"([^@]+)@([a-z]+)\.com";
+        let compiled = compile_regex(pattern).unwrap();
+        assert_eq!(
+            extract_captures(b"alice.smith+tag@mail.com", &compiled),
+            Some(vec![b"alice.smith+tag".to_vec(), b"mail".to_vec()])
+        );
+        assert!(!compiled.is_match(b"alice@bob@mail.com"));
+        // All bytes but `@` are in the class, including the padding byte.
+        assert_eq!(
+            extract_captures(b"a\0b@mail.com", &compiled).unwrap()[0],
+            b"a\0b"
+        );
+
+        let options = CompileOptions {
+            alphabet: Some((0x20..=0x7e).collect()),
+            ..Default::default()
+        };
+        let printable = compile_regex_with(pattern, &options).unwrap();
+        assert_eq!(
+            extract_captures(b"alice.smith+tag@mail.com", &printable).unwrap()[0],
+            b"alice.smith+tag"
+        );
+        assert!(!printable.is_match(b"a\0b@mail.com"));
+        assert!(!printable.is_match(b"a\nb@mail.com"));
+        // Trailing padding is not taken as part of a capture.
+        let domain = compile_regex_with("x@([^@]+)", &options).unwrap();
+        assert!(compile_regex("x@([^@]+)").unwrap().is_match(b"x@ab\0\0"));
+        assert!(!domain.is_match(b"x@ab\0\0"));
+        assert_eq!(extract_captures(b"x@ab", &domain).unwrap()[0], b"ab");
+    }
+
+    #[test]
+    fn test_max_match_len() {
+        for (pattern, max_len) in [
+            ("abc", Some(3)),
+            ("a(b|cd)?e{2,4}", Some(7)),
+            (r"\d{1,3}(\.\d{1,3}){3}", Some(15)),
+            ("", Some(0)),
+            ("a*", None),
+            ("x(ab)+y", None),
+            ("a{3,}", None),
+        ] {
+            let compiled = compile_regex(pattern).unwrap();
+            assert_eq!(compiled.max_match_len(), max_len, "{}", pattern);
+        }
+        // The loop of a trap state is not a match.
+        let compiled = compile_regex("ab").unwrap();
+        let mut dfa = compiled.dfa.clone();
+        let trap = dfa.states.len();
+        dfa.states.push(DfaState::default());
+        for state in dfa.states.iter_mut() {
+            state.edges.entry(b'z').or_insert(trap);
+        }
+        assert_eq!(dfa.max_match_len(), Some(2));
+    }
+
+    #[test]
+    fn test_random_match() {
+        use rand::{rngs::StdRng, SeedableRng};
+
+        let mut rng = StdRng::seed_from_u64(187);
+        for pattern in [
+            r"[a-z0-9]+@[a-z0-9]+\.(com|org)",
+            "x(ab|cd)*y",
+            "a{3,5}",
+            r"email was meant for @([a-z]+)( and [a-z]+)*\.",
+        ] {
+            let compiled = compile_regex(pattern).unwrap();
+            let mut lengths = BTreeSet::new();
+            for _ in 0..100 {
+                let input = compiled.random_match(&mut rng, 40).unwrap();
+                assert!(compiled.is_match(&input), "{} {:?}", pattern, input);
+                assert!(input.len() <= 40);
+                lengths.insert(input.len());
+            }
+            assert!(lengths.len() > 1, "{}", pattern);
+        }
+        let compiled = compile_regex("").unwrap();
+        assert_eq!(compiled.random_match(&mut rng, 4), Some(vec![]));
+        // Every match is longer than `max_len`, or there is none.
+        let compiled = compile_regex("a{5}").unwrap();
+        assert_eq!(compiled.random_match(&mut rng, 4), None);
+        assert_eq!(compiled.random_match(&mut rng, 5), Some(b"aaaaa".to_vec()));
+        let compiled = CompiledRegex {
+            dfa: DfaGraph {
+                states: vec![DfaState::default()],
+                start: 0,
+            },
+            ..compiled
+        };
+        assert_eq!(compiled.random_match(&mut rng, 4), None);
+    }
+
+    #[test]
+    fn test_add_transition() {
+        // A sentinel byte 0xff may stand in for the `@`.
+        let mut compiled = compile_regex("[a-z]+@[a-z]+").unwrap();
+        let regex_def = compiled.regex_def();
+        let states = regex_def.gen_states(b"a@b").unwrap();
+        assert!(!compiled.is_match(b"a\xffb"));
+        compiled.add_transition(states[1], 0xff, states[2]).unwrap();
+        assert!(compiled.is_match(b"a\xffb"));
+        assert!(compiled.is_match(b"a@b"));
+        let regex_def = compiled.regex_def();
+        assert_eq!(regex_def.next_state(states[1], 0xff), Some(states[2]));
+        assert_eq!(
+            regex_def.gen_states(b"ab\xffcd").unwrap(),
+            regex_def.gen_states(b"ab@cd").unwrap()
+        );
+
+        // Adding an existing transition again is fine, redirecting it is not.
+        assert_eq!(compiled.add_transition(states[1], b'@', states[2]), Ok(()));
+        assert_eq!(
+            compiled.add_transition(states[1], 0xff, states[1]),
+            Err(RegexError::ConflictingTransition {
+                state: states[1],
+                char: 0xff,
+                existing: states[2],
+            })
+        );
+        let missing = compiled.dfa.num_states() as u64 + 1;
+        assert_eq!(
+            compiled.add_transition(states[1], b'!', missing),
+            Err(RegexError::UnknownState { state: missing })
+        );
+        assert_eq!(
+            compiled.add_transition(0, b'!', states[1]),
+            Err(RegexError::UnknownState { state: 0 })
+        );
+    }
+
+    #[test]
+    fn test_compile_exact_length() {
+        let exact = |pattern: &str, length: usize| {
+            let options = CompileOptions {
+                exact_length: Some(length),
+                ..Default::default()
+            };
+            compile_regex_with(pattern, &options).unwrap()
+        };
+        let compiled = exact("([a-z]+)@?", 8);
+        assert!(compiled.is_match(b"abcdefgh"));
+        assert!(compiled.is_match(b"abcdefg@"));
+        for input in ["abcdefg", "abcdefghi", "abcdefgh@", ""] {
+            assert!(!compiled.is_match(input.as_bytes()), "{}", input);
+        }
+        // Only states with nothing remaining accept, and the captures are kept.
+        assert_eq!(compiled.dfa.accept_states().len(), 1);
+        let compiled = exact("([a-z]+)@", 8);
+        assert_eq!(
+            extract_captures(b"abcdefg@", &compiled).unwrap()[0],
+            b"abcdefg"
+        );
+        let unbounded = compile_regex("([a-z]+)@").unwrap();
+        assert!(compiled.dfa.num_states() <= 9 * unbounded.dfa.num_states());
+
+        assert!(exact("(?:ab)*", 8).is_match(b"abababab"));
+        assert!(exact("(?:ab)*", 7).dfa.is_empty_language());
+        assert!(exact("a*", 0).is_match(b""));
+    }
+
+    #[test]
+    fn test_compile_syntax_errors() {
+        for pattern in ["(ab", "ab)", "[a-", "*a", r"a\q", "a{3,1}"] {
+            assert!(
+                matches!(compile_regex(pattern), Err(RegexError::Syntax { .. })),
+                "{}",
+                pattern
+            );
+        }
+    }
+}
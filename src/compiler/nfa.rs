@@ -0,0 +1,279 @@
+use alloc::{
+    collections::{BTreeMap, BTreeSet},
+    vec,
+    vec::Vec,
+};
+
+use super::dfa::{DfaGraph, DfaState};
+use super::parser::{Ast, ByteSet};
+
+#[derive(Debug, Clone)]
+struct NfaEdge {
+    bytes: ByteSet,
+    next: usize,
+    captures: Vec<usize>,
+}
+
+#[derive(Debug, Clone, Default)]
+struct NfaState {
+    epsilons: Vec<usize>,
+    edges: Vec<NfaEdge>,
+}
+
+/// A Thompson NFA with a single start and a single accept state.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Nfa {
+    states: Vec<NfaState>,
+    start: usize,
+    accept: usize,
+    // The end state of each pattern of a union, if the patterns are told apart.
+    pattern_ends: Vec<usize>,
+}
+
+impl Nfa {
+    pub(crate) fn from_ast(ast: &Ast) -> Self {
+        let mut nfa = Self::default();
+        let (start, accept) = nfa.build(ast, &mut Vec::new());
+        nfa.start = start;
+        nfa.accept = accept;
+        nfa
+    }
+
+    /// The union of `patterns`, whose DFA states record the patterns they accept.
+    pub(crate) fn from_patterns(patterns: &[Ast]) -> Self {
+        let mut nfa = Self::default();
+        nfa.start = nfa.add_state();
+        nfa.accept = nfa.add_state();
+        for pattern in patterns.iter() {
+            let (start, end) = nfa.build(pattern, &mut Vec::new());
+            nfa.add_epsilon(nfa.start, start);
+            nfa.add_epsilon(end, nfa.accept);
+            nfa.pattern_ends.push(end);
+        }
+        nfa
+    }
+
+    fn add_state(&mut self) -> usize {
+        self.states.push(NfaState::default());
+        self.states.len() - 1
+    }
+
+    fn add_epsilon(&mut self, from: usize, to: usize) {
+        self.states[from].epsilons.push(to);
+    }
+
+    // Returns the (start, end) states of the fragment for `ast`.
+    fn build(&mut self, ast: &Ast, captures: &mut Vec<usize>) -> (usize, usize) {
+        match ast {
+            Ast::Empty => {
+                let state = self.add_state();
+                (state, state)
+            }
+            Ast::Class(bytes) => {
+                let start = self.add_state();
+                let end = self.add_state();
+                self.states[start].edges.push(NfaEdge {
+                    bytes: *bytes,
+                    next: end,
+                    captures: captures.clone(),
+                });
+                (start, end)
+            }
+            Ast::Concat(items) => {
+                let (start, mut end) = self.build(&items[0], captures);
+                for item in items[1..].iter() {
+                    let (item_start, item_end) = self.build(item, captures);
+                    self.add_epsilon(end, item_start);
+                    end = item_end;
+                }
+                (start, end)
+            }
+            Ast::Alternate(branches) => {
+                let start = self.add_state();
+                let end = self.add_state();
+                for branch in branches.iter() {
+                    let (branch_start, branch_end) = self.build(branch, captures);
+                    self.add_epsilon(start, branch_start);
+                    self.add_epsilon(branch_end, end);
+                }
+                (start, end)
+            }
+            Ast::Repeat { inner, min, max } => {
+                let start = self.add_state();
+                let mut end = start;
+                for _ in 0..*min {
+                    let (item_start, item_end) = self.build(inner, captures);
+                    self.add_epsilon(end, item_start);
+                    end = item_end;
+                }
+                match max {
+                    None => {
+                        let (item_start, item_end) = self.build(inner, captures);
+                        let loop_end = self.add_state();
+                        self.add_epsilon(end, item_start);
+                        self.add_epsilon(end, loop_end);
+                        self.add_epsilon(item_end, item_start);
+                        self.add_epsilon(item_end, loop_end);
+                        end = loop_end;
+                    }
+                    Some(max) => {
+                        let optional_end = self.add_state();
+                        for _ in *min..*max {
+                            let (item_start, item_end) = self.build(inner, captures);
+                            self.add_epsilon(end, item_start);
+                            self.add_epsilon(end, optional_end);
+                            end = item_end;
+                        }
+                        self.add_epsilon(end, optional_end);
+                        end = optional_end;
+                    }
+                }
+                (start, end)
+            }
+            Ast::Group { inner, capture } => {
+                if let Some(capture) = capture {
+                    captures.push(*capture);
+                }
+                let fragment = self.build(inner, captures);
+                if capture.is_some() {
+                    captures.pop();
+                }
+                fragment
+            }
+        }
+    }
+
+    fn epsilon_closure(&self, states: impl IntoIterator<Item = usize>) -> BTreeSet<usize> {
+        let mut closure = BTreeSet::new();
+        let mut stack: Vec<usize> = states.into_iter().collect();
+        while let Some(state) = stack.pop() {
+            if closure.insert(state) {
+                stack.extend(self.states[state].epsilons.iter().copied());
+            }
+        }
+        closure
+    }
+
+    /// Converts the NFA into a DFA by subset construction. Missing transitions reject.
+    pub(crate) fn to_dfa(&self) -> DfaGraph {
+        let start_set = self.epsilon_closure([self.start]);
+        let mut set_ids = BTreeMap::new();
+        let mut sets = vec![start_set.clone()];
+        set_ids.insert(start_set, 0);
+        let mut states = Vec::new();
+        let mut idx = 0;
+        while idx < sets.len() {
+            let set = sets[idx].clone();
+            let mut dfa_state = DfaState {
+                accept: set.contains(&self.accept),
+                patterns: (0..self.pattern_ends.len())
+                    .filter(|id| set.contains(&self.pattern_ends[*id]))
+                    .collect(),
+                ..Default::default()
+            };
+            let edges: Vec<&NfaEdge> = set
+                .iter()
+                .flat_map(|state| self.states[*state].edges.iter())
+                .collect();
+            let mut bytes = ByteSet::default();
+            for edge in edges.iter() {
+                bytes.union(&edge.bytes);
+            }
+            for byte in bytes.iter() {
+                let mut targets = Vec::new();
+                let mut captures = BTreeSet::new();
+                for edge in edges.iter().filter(|edge| edge.bytes.contains(byte)) {
+                    targets.push(edge.next);
+                    captures.extend(edge.captures.iter().copied());
+                }
+                let target_set = self.epsilon_closure(targets);
+                let next = match set_ids.get(&target_set) {
+                    Some(id) => *id,
+                    None => {
+                        set_ids.insert(target_set.clone(), sets.len());
+                        sets.push(target_set);
+                        sets.len() - 1
+                    }
+                };
+                dfa_state.edges.insert(byte, next);
+                if !captures.is_empty() {
+                    dfa_state.captures.insert(byte, captures);
+                }
+            }
+            states.push(dfa_state);
+            idx += 1;
+        }
+        DfaGraph { states, start: 0 }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::{compile_regex, parser::Parser};
+
+    fn unminimized_dfa(pattern: &str) -> DfaGraph {
+        let (ast, _) = Parser::new(pattern).parse().unwrap();
+        Nfa::from_ast(&ast).to_dfa()
+    }
+
+    #[test]
+    fn test_nested_epsilon_loops() {
+        // Each pair has the same language; the left side nests quantifiers whose bodies can
+        // match the empty string, so their epsilon closures contain cycles.
+        for (nested, flat) in [
+            ("(a?)*b", "a*b"),
+            ("(a|)*", "a*"),
+            ("(|a)+", "a*"),
+            ("a**", "a*"),
+            ("a*?", "a*"),
+            ("(a*)*", "a*"),
+            ("((a?)*)*", "a*"),
+            ("(a*b*)*", "[ab]*"),
+            ("((a|b)*)*c", "[ab]*c"),
+            ("(a?b?)+c", "[ab]*c"),
+            ("(()|a)*", "a*"),
+        ] {
+            let dfa = unminimized_dfa(nested);
+            let expected = compile_regex(flat).unwrap().dfa;
+            for input in [
+                "", "a", "b", "c", "aa", "ab", "ba", "abc", "bac", "aab", "abab",
+            ] {
+                assert_eq!(
+                    dfa.is_match(input.as_bytes()),
+                    expected.is_match(input.as_bytes()),
+                    "{} on {:?}",
+                    nested,
+                    input
+                );
+            }
+            // Captures aside, minimization yields the same DFA.
+            let mut minimized = dfa.minimize();
+            for state in minimized.states.iter_mut() {
+                state.captures.clear();
+            }
+            assert_eq!(minimized, expected, "{}", nested);
+        }
+    }
+
+    #[test]
+    fn test_epsilon_closure_accept_sets() {
+        // The start state is accepting iff the closure of the NFA start reaches its accept state.
+        for (pattern, accepts_empty) in [
+            ("(a|)*", true),
+            ("(a?)*b", false),
+            ("(a?)?", true),
+            ("(a|b?)c?", true),
+            ("(a|b?)c", false),
+            ("(a*)+", true),
+        ] {
+            let dfa = unminimized_dfa(pattern);
+            assert_eq!(dfa.states[dfa.start].accept, accepts_empty, "{}", pattern);
+        }
+
+        // Only the state after `b` accepts, however many epsilon paths lead into it.
+        let dfa = compile_regex("(a?)*b").unwrap().dfa;
+        assert_eq!(dfa.accept_states().len(), 1);
+        assert_eq!(dfa.num_states(), 2);
+    }
+}
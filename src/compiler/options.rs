@@ -0,0 +1,174 @@
+use alloc::{boxed::Box, vec, vec::Vec};
+
+use super::parser::{Ast, ByteSet};
+
+/// How line endings written in a pattern match the input.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LineEndings {
+    /// Every byte matches as written.
+    #[default]
+    Exact,
+    /// A line feed that is not preceded by a carriage return also matches `\r\n`.
+    AcceptCrlf,
+    /// Both `\n` and `\r\n` match either `\n` or `\r\n`.
+    Any,
+}
+
+/// Which line breaks the wildcard `.` matches.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DotMatchesNewline {
+    /// Every byte but `\n`, like most regex engines without the `s` flag.
+    #[default]
+    No,
+    /// Every byte but `\n` and `\r`, so that `.*` also stops before a `\r\n`.
+    NoCrLf,
+    /// Every byte.
+    Yes,
+}
+
+/// Options of [`super::compile_regex_with`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CompileOptions {
+    /// Only line feeds matched on their own (not by classes like `\s` that contain other bytes
+    /// too) are normalized.
+    pub line_endings: LineEndings,
+    /// How deep groups and stacked quantifiers may nest before compilation fails with
+    /// [`crate::RegexError::TooDeep`]; [`super::DEFAULT_MAX_DEPTH`] if `None`. The parser and the
+    /// NFA construction recurse on the nesting, so untrusted patterns need a bound.
+    pub max_depth: Option<usize>,
+    /// How many branches one alternation may have before compilation fails with
+    /// [`crate::RegexError::TooManyAlternatives`]; unlimited if `None`. Each `(a|b|...)` is
+    /// counted on its own, so the limit bounds the fan-out an untrusted pattern can give a
+    /// single state rather than the total size. Oversized alternations are rejected instead of
+    /// split, since the DFA of their union has the same width either way; a large keyword set
+    /// compiles faster with [`super::CompiledRegex::from_keywords`].
+    pub max_alternatives: Option<usize>,
+    /// Only inputs of exactly this many bytes are matched, by counting the remaining length in
+    /// the states (see [`super::DfaGraph::with_exact_length`]). The DFA can grow by a factor of
+    /// up to the length plus one.
+    pub exact_length: Option<usize>,
+    /// The bytes the input may contain, if not all of them. Every class is restricted to them,
+    /// so negated classes like `[^@]` and `\S` and the wildcard `.` only match these bytes. Leaving
+    /// out the padding byte 0 keeps a capture like `([^@]+)` from taking trailing padding as
+    /// input.
+    pub alphabet: Option<Vec<u8>>,
+    /// Literals match every character with the same Unicode simple case folding, e.g. `k` also
+    /// matches `K` and the Kelvin sign `K`, and `ß` matches `ẞ`. Simple folding maps one
+    /// character to one, so `ß` does not match `ss` and `İ` does not match `i`; write
+    /// `(?:ß|ss)` to accept both spellings. Classes only fold their ASCII letters, since they
+    /// hold bytes.
+    pub case_insensitive: bool,
+    /// The line breaks `.` matches: by default all bytes but `\n`, so that `.*` does not run
+    /// past the end of a line. The wildcard expands into one transition row per byte it matches,
+    /// from each state it is taken in, so it adds 255, 254 or 256 rows per such state.
+    pub dot_matches_newline: DotMatchesNewline,
+    /// Numbers the top-level alternatives of the pattern from 0, e.g. `cat|dog` as 0 and 1, and
+    /// records in each accept state which of them it matches (see
+    /// [`super::CompiledRegex::accept_patterns`]), so that one circuit can tell by the accepting
+    /// state which alternative matched. Accept states of different alternatives are then never
+    /// merged, so the DFA may have more states.
+    pub pattern_ids: bool,
+}
+
+impl CompileOptions {
+    pub(crate) fn apply(&self, ast: Ast) -> Ast {
+        let ast = match self.line_endings {
+            LineEndings::Exact => ast,
+            mode => normalize_line_endings(ast, mode),
+        };
+        match self.alphabet.as_ref() {
+            Some(alphabet) => {
+                let mut set = ByteSet::default();
+                for byte in alphabet.iter() {
+                    set.insert(*byte);
+                }
+                restrict_alphabet(ast, &set)
+            }
+            None => ast,
+        }
+    }
+}
+
+fn restrict_alphabet(ast: Ast, alphabet: &ByteSet) -> Ast {
+    match ast {
+        Ast::Class(set) => Ast::Class(set.intersect(alphabet)),
+        Ast::Concat(items) => Ast::Concat(
+            items
+                .into_iter()
+                .map(|item| restrict_alphabet(item, alphabet))
+                .collect(),
+        ),
+        Ast::Alternate(branches) => Ast::Alternate(
+            branches
+                .into_iter()
+                .map(|branch| restrict_alphabet(branch, alphabet))
+                .collect(),
+        ),
+        Ast::Repeat { inner, min, max } => Ast::Repeat {
+            inner: Box::new(restrict_alphabet(*inner, alphabet)),
+            min,
+            max,
+        },
+        Ast::Group { inner, capture } => Ast::Group {
+            inner: Box::new(restrict_alphabet(*inner, alphabet)),
+            capture,
+        },
+        ast => ast,
+    }
+}
+
+fn is_byte(ast: &Ast, byte: u8) -> bool {
+    matches!(ast, Ast::Class(set) if *set == ByteSet::single(byte))
+}
+
+fn optional_cr() -> Ast {
+    Ast::Repeat {
+        inner: Box::new(Ast::Class(ByteSet::single(b'\r'))),
+        min: 0,
+        max: Some(1),
+    }
+}
+
+fn normalize_line_endings(ast: Ast, mode: LineEndings) -> Ast {
+    match ast {
+        Ast::Class(set) if set == ByteSet::single(b'\n') => {
+            Ast::Concat(vec![optional_cr(), Ast::Class(set)])
+        }
+        Ast::Concat(items) => {
+            let mut normalized = Vec::new();
+            let mut items = items.into_iter().peekable();
+            while let Some(item) = items.next() {
+                let crlf = is_byte(&item, b'\r')
+                    && matches!(items.peek(), Some(next) if is_byte(next, b'\n'));
+                if crlf {
+                    let lf = items.next().unwrap();
+                    if mode == LineEndings::Any {
+                        normalized.push(optional_cr());
+                    } else {
+                        normalized.push(item);
+                    }
+                    normalized.push(lf);
+                } else {
+                    normalized.push(normalize_line_endings(item, mode));
+                }
+            }
+            Ast::Concat(normalized)
+        }
+        Ast::Alternate(branches) => Ast::Alternate(
+            branches
+                .into_iter()
+                .map(|branch| normalize_line_endings(branch, mode))
+                .collect(),
+        ),
+        Ast::Repeat { inner, min, max } => Ast::Repeat {
+            inner: Box::new(normalize_line_endings(*inner, mode)),
+            min,
+            max,
+        },
+        Ast::Group { inner, capture } => Ast::Group {
+            inner: Box::new(normalize_line_endings(*inner, mode)),
+            capture,
+        },
+        ast => ast,
+    }
+}
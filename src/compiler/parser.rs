@@ -0,0 +1,606 @@
+use alloc::{
+    boxed::Box,
+    format,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+
+use super::{fold::CaseFolds, DotMatchesNewline};
+use crate::RegexError;
+
+// Upper bound for `{m,n}` so that a typo cannot blow up the NFA.
+const MAX_REPEAT: u32 = 1000;
+
+/// The default limit of [`super::CompileOptions::max_depth`].
+pub const DEFAULT_MAX_DEPTH: usize = 128;
+
+/// A set of bytes, stored as a 256-bit bitmap.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub(crate) struct ByteSet([u64; 4]);
+
+impl ByteSet {
+    pub(crate) fn single(byte: u8) -> Self {
+        let mut set = Self::default();
+        set.insert(byte);
+        set
+    }
+
+    pub(crate) fn range(lo: u8, hi: u8) -> Self {
+        let mut set = Self::default();
+        set.insert_range(lo, hi);
+        set
+    }
+
+    pub(crate) fn full() -> Self {
+        Self([u64::MAX; 4])
+    }
+
+    pub(crate) fn insert(&mut self, byte: u8) {
+        self.0[(byte / 64) as usize] |= 1 << (byte % 64);
+    }
+
+    pub(crate) fn insert_range(&mut self, lo: u8, hi: u8) {
+        for byte in lo..=hi {
+            self.insert(byte);
+        }
+    }
+
+    pub(crate) fn contains(&self, byte: u8) -> bool {
+        self.0[(byte / 64) as usize] & (1 << (byte % 64)) != 0
+    }
+
+    pub(crate) fn union(&mut self, other: &ByteSet) {
+        for (word, other_word) in self.0.iter_mut().zip(other.0.iter()) {
+            *word |= *other_word;
+        }
+    }
+
+    pub(crate) fn intersect(&self, other: &ByteSet) -> Self {
+        Self([
+            self.0[0] & other.0[0],
+            self.0[1] & other.0[1],
+            self.0[2] & other.0[2],
+            self.0[3] & other.0[3],
+        ])
+    }
+
+    pub(crate) fn negate(&self) -> Self {
+        Self([!self.0[0], !self.0[1], !self.0[2], !self.0[3]])
+    }
+
+    pub(crate) fn iter(&self) -> impl Iterator<Item = u8> + '_ {
+        (0..=255u8).filter(move |byte| self.contains(*byte))
+    }
+}
+
+/// The parsed form of a pattern.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Ast {
+    Empty,
+    Class(ByteSet),
+    Concat(Vec<Ast>),
+    Alternate(Vec<Ast>),
+    Repeat {
+        inner: Box<Ast>,
+        min: u32,
+        max: Option<u32>,
+    },
+    Group {
+        inner: Box<Ast>,
+        capture: Option<usize>,
+    },
+}
+
+pub(crate) struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+    capture_names: Vec<Option<String>>,
+    // The number of groups enclosing `pos`.
+    depth: usize,
+    max_depth: usize,
+    max_alternatives: Option<usize>,
+    // Set when matching case-insensitively.
+    case_folds: Option<CaseFolds>,
+    // The bytes `.` matches.
+    dot: ByteSet,
+}
+
+impl Parser {
+    pub(crate) fn new(pattern: &str) -> Self {
+        Self {
+            chars: pattern.chars().collect(),
+            pos: 0,
+            capture_names: vec![],
+            depth: 0,
+            max_depth: DEFAULT_MAX_DEPTH,
+            max_alternatives: None,
+            case_folds: None,
+            dot: ByteSet::single(b'\n').negate(),
+        }
+    }
+
+    pub(crate) fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    pub(crate) fn with_max_alternatives(mut self, max_alternatives: Option<usize>) -> Self {
+        self.max_alternatives = max_alternatives;
+        self
+    }
+
+    /// Makes literals match every character with the same simple case folding, and classes
+    /// their ASCII letters in either case.
+    pub(crate) fn with_case_insensitive(mut self, case_insensitive: bool) -> Self {
+        self.case_folds = case_insensitive.then(CaseFolds::new);
+        self
+    }
+
+    pub(crate) fn with_dot_matches_newline(mut self, mode: DotMatchesNewline) -> Self {
+        self.dot = match mode {
+            DotMatchesNewline::No => ByteSet::single(b'\n').negate(),
+            DotMatchesNewline::NoCrLf => {
+                let mut line_breaks = ByteSet::single(b'\n');
+                line_breaks.insert(b'\r');
+                line_breaks.negate()
+            }
+            DotMatchesNewline::Yes => ByteSet::full(),
+        };
+        self
+    }
+
+    /// Parses the whole pattern, returning the AST and the name of each capture group, if any.
+    pub(crate) fn parse(mut self) -> Result<(Ast, Vec<Option<String>>), RegexError> {
+        self.strip_anchors();
+        let ast = self.parse_alternate()?;
+        match self.peek() {
+            None => Ok((ast, self.capture_names)),
+            Some(')') => Err(self.error("unmatched ')'")),
+            Some(c) => Err(self.error(&format!("unexpected '{}'", c))),
+        }
+    }
+
+    /// Splits a valid pattern into the source of its top-level concatenated items, e.g.
+    /// `a+(b|c)d` into `a+`, `(b|c)` and `d`. A top-level alternation is a single item.
+    pub(crate) fn concat_items(mut self) -> Result<Vec<String>, RegexError> {
+        self.strip_anchors();
+        let start = self.pos;
+        let mut bounds = vec![start];
+        while let Some(c) = self.peek() {
+            if c == '|' || c == ')' {
+                break;
+            }
+            let atom = self.parse_atom()?;
+            self.parse_quantifiers(atom)?;
+            bounds.push(self.pos);
+        }
+        if self.peek().is_some() {
+            return Ok(vec![self.chars[start..].iter().collect()]);
+        }
+        Ok(bounds
+            .windows(2)
+            .map(|bound| self.chars[bound[0]..bound[1]].iter().collect())
+            .collect())
+    }
+
+    // Matching is always anchored, so leading `^` and trailing `$` are accepted as no-ops.
+    fn strip_anchors(&mut self) {
+        if self.peek() == Some('^') {
+            self.pos += 1;
+        }
+        if self.chars.last() == Some(&'$') && !self.is_escaped(self.chars.len() - 1) {
+            self.chars.pop();
+        }
+    }
+
+    fn parse_alternate(&mut self) -> Result<Ast, RegexError> {
+        let mut branches = vec![self.parse_concat()?];
+        while self.peek() == Some('|') {
+            if let Some(max_alternatives) = self.max_alternatives {
+                if branches.len() == max_alternatives {
+                    return Err(RegexError::TooManyAlternatives {
+                        position: self.pos,
+                        max_alternatives,
+                    });
+                }
+            }
+            self.pos += 1;
+            branches.push(self.parse_concat()?);
+        }
+        if branches.len() == 1 {
+            Ok(branches.pop().unwrap())
+        } else {
+            Ok(Ast::Alternate(branches))
+        }
+    }
+
+    fn parse_concat(&mut self) -> Result<Ast, RegexError> {
+        let mut items = Vec::new();
+        while let Some(c) = self.peek() {
+            if c == '|' || c == ')' {
+                break;
+            }
+            let atom = self.parse_atom()?;
+            items.push(self.parse_quantifiers(atom)?);
+        }
+        match items.len() {
+            0 => Ok(Ast::Empty),
+            1 => Ok(items.pop().unwrap()),
+            _ => Ok(Ast::Concat(items)),
+        }
+    }
+
+    fn parse_quantifiers(&mut self, mut atom: Ast) -> Result<Ast, RegexError> {
+        // Stacked quantifiers like `a**` nest the AST as deep as groups do.
+        let mut depth = self.depth;
+        loop {
+            let start = self.pos;
+            let (min, max) = match self.next() {
+                Some('*') => (0, None),
+                Some('+') => (1, None),
+                Some('?') => (0, Some(1)),
+                Some('{') => match self.parse_bounds() {
+                    Some(bounds) => bounds,
+                    None => {
+                        self.pos = start;
+                        return Err(self.error("invalid repetition"));
+                    }
+                },
+                _ => {
+                    self.pos = start;
+                    return Ok(atom);
+                }
+            };
+            if let Some(max) = max {
+                if min > max {
+                    return Err(self.error("repetition minimum exceeds maximum"));
+                }
+            }
+            if min > MAX_REPEAT || matches!(max, Some(max) if max > MAX_REPEAT) {
+                return Err(self.error(&format!("repetition exceeds {}", MAX_REPEAT)));
+            }
+            depth += 1;
+            if depth > self.max_depth {
+                return Err(self.too_deep(start));
+            }
+            atom = Ast::Repeat {
+                inner: Box::new(atom),
+                min,
+                max,
+            };
+        }
+    }
+
+    // Parses `m}`, `m,}` or `m,n}` after a `{`, leaving `pos` just past the `}`.
+    fn parse_bounds(&mut self) -> Option<(u32, Option<u32>)> {
+        let min = self.parse_number()?;
+        match self.next()? {
+            '}' => Some((min, Some(min))),
+            ',' => {
+                if self.peek() == Some('}') {
+                    self.pos += 1;
+                    return Some((min, None));
+                }
+                let max = self.parse_number()?;
+                if self.next()? != '}' {
+                    return None;
+                }
+                Some((min, Some(max)))
+            }
+            _ => None,
+        }
+    }
+
+    fn parse_number(&mut self) -> Option<u32> {
+        let start = self.pos;
+        while matches!(self.peek(), Some(c) if c.is_ascii_digit()) {
+            self.pos += 1;
+        }
+        if start == self.pos {
+            return None;
+        }
+        self.chars[start..self.pos]
+            .iter()
+            .collect::<String>()
+            .parse()
+            .ok()
+    }
+
+    fn parse_atom(&mut self) -> Result<Ast, RegexError> {
+        let c = self.next().unwrap();
+        match c {
+            '(' => self.parse_group(),
+            '[' => Ok(Ast::Class(self.parse_class()?)),
+            '.' => Ok(Ast::Class(self.dot)),
+            '\\' => Ok(Ast::Class(self.parse_escape()?)),
+            '*' | '+' | '?' | '{' => {
+                self.pos -= 1;
+                Err(self.error("quantifier without a preceding atom"))
+            }
+            '^' | '$' => {
+                self.pos -= 1;
+                Err(self.error("anchors are only allowed at the pattern boundaries"))
+            }
+            c => Ok(self.literal(c)),
+        }
+    }
+
+    fn parse_group(&mut self) -> Result<Ast, RegexError> {
+        if self.depth == self.max_depth {
+            return Err(self.too_deep(self.pos - 1));
+        }
+        let capture = if self.peek() == Some('?') {
+            let flag: String = self.chars.iter().skip(self.pos + 1).take(2).collect();
+            if flag.starts_with('=') || flag.starts_with('!') {
+                return Err(RegexError::UnsupportedFeature("lookahead"));
+            }
+            if flag == "<=" || flag == "<!" {
+                return Err(RegexError::UnsupportedFeature("lookbehind"));
+            }
+            if flag == "P<" {
+                self.pos += 3;
+                let name = self.parse_group_name()?;
+                self.capture_names.push(Some(name));
+                Some(self.capture_names.len() - 1)
+            } else if flag.starts_with(':') {
+                self.pos += 2;
+                None
+            } else {
+                return Err(self.error("unsupported group syntax"));
+            }
+        } else {
+            self.capture_names.push(None);
+            Some(self.capture_names.len() - 1)
+        };
+        self.depth += 1;
+        let inner = self.parse_alternate()?;
+        self.depth -= 1;
+        if self.next() != Some(')') {
+            return Err(self.error("unclosed group"));
+        }
+        Ok(Ast::Group {
+            inner: Box::new(inner),
+            capture,
+        })
+    }
+
+    // Parses the `name>` of a `(?P<name>...)` group: a letter or `_` followed by letters, digits
+    // and `_`, not used by an earlier group.
+    fn parse_group_name(&mut self) -> Result<String, RegexError> {
+        let start = self.pos;
+        let mut name = String::new();
+        loop {
+            match self.next() {
+                Some('>') => break,
+                Some(c) if c == '_' || c.is_ascii_alphabetic() => name.push(c),
+                Some(c) if c.is_ascii_digit() && !name.is_empty() => name.push(c),
+                Some(_) => {
+                    self.pos -= 1;
+                    return Err(self.error("invalid character in capture group name"));
+                }
+                None => return Err(self.error("unclosed capture group name")),
+            }
+        }
+        if name.is_empty() {
+            self.pos = start;
+            return Err(self.error("empty capture group name"));
+        }
+        if self.capture_names.contains(&Some(name.clone())) {
+            self.pos = start;
+            return Err(self.error("duplicate capture group name"));
+        }
+        Ok(name)
+    }
+
+    fn parse_class(&mut self) -> Result<ByteSet, RegexError> {
+        let negated = self.peek() == Some('^');
+        if negated {
+            self.pos += 1;
+        }
+        let mut set = ByteSet::default();
+        let mut first = true;
+        loop {
+            let c = match self.next() {
+                Some(c) => c,
+                None => return Err(self.error("unclosed character class")),
+            };
+            if c == ']' && !first {
+                break;
+            }
+            first = false;
+            let lo = if c == '\\' {
+                let escaped = self.parse_escape()?;
+                match Self::single_byte(&escaped) {
+                    Some(byte) => byte,
+                    None => {
+                        set.union(&escaped);
+                        continue;
+                    }
+                }
+            } else {
+                self.class_byte(c)?
+            };
+            if self.peek() == Some('-') && !matches!(self.chars.get(self.pos + 1), Some(']') | None)
+            {
+                self.pos += 1;
+                let c = self.next().unwrap();
+                let hi = if c == '\\' {
+                    let escaped = self.parse_escape()?;
+                    match Self::single_byte(&escaped) {
+                        Some(byte) => byte,
+                        None => return Err(self.error("invalid class range")),
+                    }
+                } else {
+                    self.class_byte(c)?
+                };
+                if lo > hi {
+                    return Err(self.error("invalid class range"));
+                }
+                set.insert_range(lo, hi);
+            } else {
+                set.insert(lo);
+            }
+        }
+        if self.case_folds.is_some() {
+            for byte in set.iter().collect::<Vec<_>>() {
+                set.insert(byte.to_ascii_lowercase());
+                set.insert(byte.to_ascii_uppercase());
+            }
+        }
+        Ok(if negated { set.negate() } else { set })
+    }
+
+    // Parses the escape sequence following a `\`.
+    fn parse_escape(&mut self) -> Result<ByteSet, RegexError> {
+        let c = match self.next() {
+            Some(c) => c,
+            None => return Err(self.error("trailing backslash")),
+        };
+        let set = match c {
+            'n' => ByteSet::single(b'\n'),
+            'r' => ByteSet::single(b'\r'),
+            't' => ByteSet::single(b'\t'),
+            'f' => ByteSet::single(0x0c),
+            'v' => ByteSet::single(0x0b),
+            '0' => ByteSet::single(0),
+            'x' => {
+                let hex: String = self.chars.iter().skip(self.pos).take(2).collect();
+                match u8::from_str_radix(&hex, 16) {
+                    Ok(byte) if hex.len() == 2 => {
+                        self.pos += 2;
+                        ByteSet::single(byte)
+                    }
+                    _ => return Err(self.error("invalid hex escape")),
+                }
+            }
+            'd' => Self::digit(),
+            'D' => Self::digit().negate(),
+            'w' => Self::word(),
+            'W' => Self::word().negate(),
+            's' => Self::space(),
+            'S' => Self::space().negate(),
+            '1'..='9' => return Err(RegexError::UnsupportedFeature("backreference")),
+            'k' if self.peek() == Some('<') => {
+                return Err(RegexError::UnsupportedFeature("backreference"))
+            }
+            'b' | 'B' => return Err(RegexError::UnsupportedFeature("word boundary")),
+            c if c.is_ascii_punctuation() || c == ' ' => ByteSet::single(c as u8),
+            c => {
+                self.pos -= 1;
+                return Err(self.error(&format!("unknown escape '\\{}'", c)));
+            }
+        };
+        Ok(set)
+    }
+
+    fn digit() -> ByteSet {
+        ByteSet::range(b'0', b'9')
+    }
+
+    fn word() -> ByteSet {
+        let mut set = Self::digit();
+        set.insert_range(b'a', b'z');
+        set.insert_range(b'A', b'Z');
+        set.insert(b'_');
+        set
+    }
+
+    // Space, tab, LF, vertical tab, form feed and CR.
+    fn space() -> ByteSet {
+        let mut set = ByteSet::single(b' ');
+        set.insert_range(b'\t', b'\r');
+        set
+    }
+
+    fn literal(&self, c: char) -> Ast {
+        let case_folds = match self.case_folds.as_ref() {
+            Some(case_folds) => case_folds,
+            None => return Self::utf8_literal(c),
+        };
+        // The single-byte equivalents go into one class, alternated with the others.
+        let mut bytes = ByteSet::default();
+        let mut branches = vec![];
+        for c in case_folds.equivalents(c) {
+            if c.is_ascii() {
+                bytes.insert(c as u8);
+            } else {
+                branches.push(Self::utf8_literal(c));
+            }
+        }
+        if bytes != ByteSet::default() {
+            branches.insert(0, Ast::Class(bytes));
+        }
+        if branches.len() == 1 {
+            branches.pop().unwrap()
+        } else {
+            Ast::Alternate(branches)
+        }
+    }
+
+    fn utf8_literal(c: char) -> Ast {
+        let mut buf = [0u8; 4];
+        let bytes = c.encode_utf8(&mut buf).as_bytes();
+        if bytes.len() == 1 {
+            Ast::Class(ByteSet::single(bytes[0]))
+        } else {
+            Ast::Concat(
+                bytes
+                    .iter()
+                    .map(|byte| Ast::Class(ByteSet::single(*byte)))
+                    .collect(),
+            )
+        }
+    }
+
+    fn single_byte(set: &ByteSet) -> Option<u8> {
+        let mut iter = set.iter();
+        match (iter.next(), iter.next()) {
+            (Some(byte), None) => Some(byte),
+            _ => None,
+        }
+    }
+
+    fn class_byte(&self, c: char) -> Result<u8, RegexError> {
+        if c.is_ascii() {
+            Ok(c as u8)
+        } else {
+            Err(self.error("non-ASCII characters are not supported in classes"))
+        }
+    }
+
+    fn is_escaped(&self, idx: usize) -> bool {
+        let backslashes = self.chars[..idx]
+            .iter()
+            .rev()
+            .take_while(|c| **c == '\\')
+            .count();
+        backslashes % 2 == 1
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek();
+        if c.is_some() {
+            self.pos += 1;
+        }
+        c
+    }
+
+    fn too_deep(&self, position: usize) -> RegexError {
+        RegexError::TooDeep {
+            position,
+            max_depth: self.max_depth,
+        }
+    }
+
+    fn error(&self, message: &str) -> RegexError {
+        RegexError::Syntax {
+            position: self.pos,
+            message: message.to_string(),
+        }
+    }
+}
@@ -0,0 +1,216 @@
+use alloc::{string::String, vec, vec::Vec};
+#[cfg(feature = "std")]
+use std::collections::HashMap;
+
+use super::{compile_regex, parser::Parser, CompiledRegex};
+#[cfg(feature = "std")]
+use crate::RegexDef;
+use crate::RegexError;
+
+/// A regex split into stages that match consecutive segments of the input, one circuit each.
+///
+/// The stages are independent DFAs, so no state is handed over between them: a pipeline proof is
+/// a concatenation of independent proofs, one per segment, and the input matches because each
+/// segment matches its stage. Each stage circuit exposes the state it was entered in and the
+/// accepting state it exited in, as `RegexCheckConfig::expose_states` does, and
+/// [`RegexPipeline::check_links`] checks that every stage started in its start state and
+/// accepted. The segments must be bound to consecutive parts of the full input, e.g. by
+/// committing to them, which is out of the scope of this helper.
+#[derive(Debug, Clone)]
+pub struct RegexPipeline {
+    pub stages: Vec<CompiledRegex>,
+}
+
+/// Splits the top-level concatenation of `pattern` into stages of consecutive items, closing a
+/// stage before its DFA exceeds `max_states` states. An item whose DFA alone exceeds
+/// `max_states` becomes a stage of its own.
+pub fn split_regex(pattern: &str, max_states: usize) -> Result<RegexPipeline, RegexError> {
+    Parser::new(pattern).parse()?;
+    let items = Parser::new(pattern).concat_items()?;
+    let mut stages = Vec::new();
+    let mut current = String::new();
+    for item in items.iter() {
+        let candidate = current.clone() + item;
+        if !current.is_empty() && compile_regex(&candidate)?.dfa.num_states() > max_states {
+            stages.push(compile_regex(&current)?);
+            current = item.clone();
+        } else {
+            current = candidate;
+        }
+    }
+    stages.push(compile_regex(&current)?);
+    Ok(RegexPipeline { stages })
+}
+
+impl RegexPipeline {
+    /// The lengths of non-empty input segments matched by the stages in order, if any.
+    pub fn split_input(&self, input: &[u8]) -> Option<Vec<usize>> {
+        let mut failed = vec![vec![false; input.len() + 1]; self.stages.len()];
+        let mut lengths = Vec::new();
+        self.split_from(0, input, 0, &mut failed, &mut lengths)
+            .then_some(lengths)
+    }
+
+    /// Splits `input[position..]` among the stages from `stage` on. `failed` records the
+    /// `(stage, position)` pairs already known not to split, so that each is tried once.
+    fn split_from(
+        &self,
+        stage: usize,
+        input: &[u8],
+        position: usize,
+        failed: &mut [Vec<bool>],
+        lengths: &mut Vec<usize>,
+    ) -> bool {
+        let dfa = match self.stages.get(stage) {
+            Some(compiled) => &compiled.dfa,
+            None => return position == input.len(),
+        };
+        if failed[stage][position] {
+            return false;
+        }
+        let mut state = dfa.start;
+        for (idx, byte) in input[position..].iter().enumerate() {
+            state = match dfa.states[state].edges.get(byte) {
+                Some(next) => *next,
+                None => break,
+            };
+            if dfa.states[state].accept {
+                lengths.push(idx + 1);
+                if self.split_from(stage + 1, input, position + idx + 1, failed, lengths) {
+                    return true;
+                }
+                lengths.pop();
+            }
+        }
+        failed[stage][position] = true;
+        false
+    }
+
+    pub fn is_match(&self, input: &[u8]) -> bool {
+        self.split_input(input).is_some()
+    }
+
+    /// The value of state `idx` of `stage`. The values of all stages are disjoint, so that a
+    /// handed-over state also identifies the stage it belongs to.
+    pub fn state_val(&self, stage: usize, idx: usize) -> u64 {
+        let offset: usize = self.stages[..stage]
+            .iter()
+            .map(|compiled| compiled.dfa.num_states())
+            .sum();
+        CompiledRegex::state_val(offset + idx)
+    }
+
+    /// The transition table of each stage, numbered by [`RegexPipeline::state_val`].
+    #[cfg(feature = "std")]
+    pub fn regex_defs(&self) -> Vec<RegexDef> {
+        (0..self.stages.len())
+            .map(|stage| {
+                let dfa = &self.stages[stage].dfa;
+                let mut state_lookup = HashMap::new();
+                for (idx, state) in dfa.states.iter().enumerate() {
+                    for (char, next) in state.edges.iter() {
+                        state_lookup.insert(
+                            (*char, self.state_val(stage, idx)),
+                            self.state_val(stage, *next),
+                        );
+                    }
+                }
+                RegexDef {
+                    state_lookup,
+                    first_state_val: self.state_val(stage, dfa.start),
+                    accepted_state_vals: dfa
+                        .accept_states()
+                        .into_iter()
+                        .map(|idx| self.state_val(stage, idx))
+                        .collect(),
+                    largest_state_val: self.state_val(stage, dfa.num_states() - 1),
+                }
+            })
+            .collect()
+    }
+
+    /// Checks the `[entry_state, exit_state]` public inputs of the stage proofs: every stage is
+    /// entered in its start state and exits in one of its accepting states. The stages share no
+    /// state, so this does not link one stage to the next; see [`RegexPipeline`].
+    pub fn check_links(&self, states: &[[u64; 2]]) -> bool {
+        states.len() == self.stages.len()
+            && states.iter().enumerate().all(|(stage, [entry, exit])| {
+                let dfa = &self.stages[stage].dfa;
+                *entry == self.state_val(stage, dfa.start)
+                    && dfa
+                        .accept_states()
+                        .into_iter()
+                        .any(|idx| *exit == self.state_val(stage, idx))
+            })
+    }
+
+    /// The `[entry_state, exit_state]` of each stage on the segments given by `lengths`.
+    pub fn link_states(&self, input: &[u8], lengths: &[usize]) -> Vec<[u64; 2]> {
+        let mut position = 0;
+        let mut states = vec![];
+        for (stage, length) in lengths.iter().enumerate() {
+            let dfa = &self.stages[stage].dfa;
+            let mut state = dfa.start;
+            for byte in input[position..position + length].iter() {
+                state = dfa.states[state].edges[byte];
+            }
+            states.push([
+                self.state_val(stage, dfa.start),
+                self.state_val(stage, state),
+            ]);
+            position += length;
+        }
+        states
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEADERS: &str = r"from:[a-z]+@[a-z]+\.com\r\nto:[a-z]+@[a-z]+\.(com|org)\r\n";
+
+    #[test]
+    fn test_split_regex() {
+        let compiled = compile_regex(HEADERS).unwrap();
+        let pipeline = split_regex(HEADERS, compiled.dfa.num_states() / 2 + 1).unwrap();
+        assert_eq!(pipeline.stages.len(), 2);
+        for stage in pipeline.stages.iter() {
+            assert!(stage.dfa.num_states() < compiled.dfa.num_states());
+        }
+        assert_eq!(pipeline.stages[1].num_captures, 1);
+
+        for input in [
+            "from:alice@mail.com\r\nto:bob@mail.org\r\n",
+            "from:a@b.com\r\nto:c@d.com\r\n",
+            "from:alice@mail.org\r\nto:bob@mail.org\r\n",
+            "from:alice@mail.com\r\n",
+        ] {
+            let input = input.as_bytes();
+            assert_eq!(pipeline.is_match(input), compiled.is_match(input));
+        }
+
+        let input = b"from:alice@mail.com\r\nto:bob@mail.org\r\n";
+        let lengths = pipeline.split_input(input).unwrap();
+        assert_eq!(lengths.iter().sum::<usize>(), input.len());
+        let states = pipeline.link_states(input, &lengths);
+        assert!(pipeline.check_links(&states));
+        // The exit state of one stage is not accepted as the exit state of the other.
+        assert!(!pipeline.check_links(&[states[0], [states[1][0], states[0][1]]]));
+
+        // A top-level alternation cannot be split.
+        assert_eq!(split_regex("ab|cd", 1).unwrap().stages.len(), 1);
+        assert!(split_regex("(ab", 1).is_err());
+    }
+
+    #[test]
+    fn test_split_input_memoized() {
+        let pipeline = split_regex(&"(a|b)+".repeat(20), 2).unwrap();
+        assert_eq!(pipeline.stages.len(), 20);
+        // Without memoization, this tries every split of the 60 characters into 20 segments.
+        let mut input = vec![b'a'; 60];
+        assert!(pipeline.split_input(&input).is_some());
+        input.push(b'c');
+        assert!(pipeline.split_input(&input).is_none());
+    }
+}
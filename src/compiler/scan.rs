@@ -0,0 +1,78 @@
+use alloc::{format, string::ToString};
+
+use super::{compile_regex, CompiledRegex};
+use crate::RegexError;
+
+impl CompiledRegex {
+    /// Compiles a DFA that reads any input and enters an accept state right after each byte
+    /// that ends an occurrence of `pattern`, for counting occurrences with
+    /// [`CompiledRegex::count_matches`] or `RegexCheckConfig::configure_match_count`.
+    ///
+    /// With `overlapping`, every position where some occurrence ends is counted once, so `aa`
+    /// occurs 3 times in `aaaa`. Otherwise the scan restarts after each counted occurrence,
+    /// counting the shortest occurrences left to right, so `aa` occurs twice. `pattern` should
+    /// not match the empty string, whose occurrences are not counted. The DFA has a transition
+    /// for every byte from every state, so only its accept states carry information.
+    pub fn scanner(pattern: &str, overlapping: bool) -> Result<Self, RegexError> {
+        let mut compiled = compile_regex(&format!(r"[\x00-\xff]*(?:{})", pattern))?;
+        if !overlapping {
+            let start = compiled.dfa.states[compiled.dfa.start].clone();
+            for state in compiled.dfa.states.iter_mut().filter(|state| state.accept) {
+                state.edges = start.edges.clone();
+                state.captures = start.captures.clone();
+            }
+            compiled.dfa = compiled.dfa.prune_unreachable().minimize();
+        }
+        compiled.pattern = pattern.to_string();
+        Ok(compiled)
+    }
+
+    /// The number of bytes of `input` after which the DFA is in an accept state, i.e. the number
+    /// of occurrences for a [`CompiledRegex::scanner`]. Stops counting at a missing transition.
+    pub fn count_matches(&self, input: &[u8]) -> usize {
+        let mut state = self.dfa.start;
+        let mut count = 0;
+        for byte in input.iter() {
+            state = match self.dfa.states[state].edges.get(byte) {
+                Some(next) => *next,
+                None => break,
+            };
+            if self.dfa.states[state].accept {
+                count += 1;
+            }
+        }
+        count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scanner() {
+        let overlapping = CompiledRegex::scanner("ab", true).unwrap();
+        assert_eq!(overlapping.pattern, "ab");
+        assert_eq!(overlapping.count_matches(b"ababab"), 3);
+        assert_eq!(overlapping.count_matches(b"xxabyabab\n"), 3);
+        assert_eq!(overlapping.count_matches(b"ba"), 0);
+        for state in overlapping.dfa.states.iter() {
+            assert_eq!(state.edges.len(), 256);
+        }
+
+        let overlapping = CompiledRegex::scanner("aa", true).unwrap();
+        let non_overlapping = CompiledRegex::scanner("aa", false).unwrap();
+        assert_eq!(overlapping.count_matches(b"aaaa"), 3);
+        assert_eq!(non_overlapping.count_matches(b"aaaa"), 2);
+        assert_eq!(non_overlapping.count_matches(b"aaaaa"), 2);
+        assert_eq!(non_overlapping.count_matches(b"aabaa"), 2);
+        for state in non_overlapping.dfa.states.iter() {
+            assert_eq!(state.edges.len(), 256);
+        }
+
+        // The shortest occurrences are counted.
+        let non_overlapping = CompiledRegex::scanner("a+", false).unwrap();
+        assert_eq!(non_overlapping.count_matches(b"aaa"), 3);
+        assert!(CompiledRegex::scanner("(a", true).is_err());
+    }
+}
@@ -0,0 +1,431 @@
+use halo2_base::halo2_proofs::{
+    circuit::{AssignedCell, Layouter, Region, Value},
+    plonk::{Advice, Column, ConstraintSystem, Error, Expression, Selector, TableColumn},
+    poly::Rotation,
+};
+use halo2_base::utils::PrimeField;
+use std::marker::PhantomData;
+
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// The decoded bytes of an [`InputDecoder`] and their assigned cells.
+#[derive(Debug, Clone, Default)]
+pub struct DecodedInput<F: PrimeField> {
+    pub bytes: Vec<u8>,
+    /// The encoded characters, padded with 0 to the maximum length, for the caller to bind to
+    /// the encoded input, e.g. as instances.
+    pub encoded: Vec<AssignedCell<F, F>>,
+    /// The decoded byte at every position the decoder can output, 0 after the decoded bytes.
+    pub cells: Vec<AssignedCell<F, F>>,
+    /// 1 at the positions of the decoded bytes and 0 after them, constrained by the encoding.
+    pub enable_flags: Vec<AssignedCell<F, F>>,
+}
+
+/// An in-circuit pre-transform of the regex input (see `RegexCheckConfig::assign_values_decoded`).
+pub trait InputDecoder<F: PrimeField> {
+    /// Assigns `encoded` and returns its decoding, whose cells and enable flags are constrained
+    /// to be the decoding of the assigned encoded characters.
+    fn decode(&self, region: &mut Region<F>, encoded: &[u8]) -> Result<DecodedInput<F>, Error>;
+}
+
+/// Decodes canonical standard base64 (RFC 4648 alphabet, `=` padding), one encoded character
+/// per row, followed by rows of 0 up to `max_encoded_len`.
+///
+/// Every group of 4 rows holds the sextets `s0..s3` of 4 characters and the bytes `d0, d1, d2`
+/// (and a 0) with `s0 * 2^18 + s1 * 2^12 + s2 * 2^6 + s3 = d0 * 2^16 + d1 * 2^8 + d2`.
+/// `=` is looked up as the sextet 0. The rows holding alphabet characters come first, `=` may
+/// only pad the last one or two rows of the last group of the input, and the groups after the
+/// input are all 0. The byte at row `i` of a group is decoded iff row `i + 1` holds an alphabet
+/// character, which fixes the decoded length, and must be 0 otherwise. The encoded characters
+/// themselves are the prover's; bind [`DecodedInput::encoded`] to the verifier's input.
+#[derive(Debug, Clone)]
+pub struct Base64DecodeConfig<F: PrimeField> {
+    encoded: Column<Advice>,
+    sextets: Column<Advice>,
+    decoded: Column<Advice>,
+    // 1 on the rows holding alphabet characters.
+    data: Column<Advice>,
+    // 1 on the rows holding `=`.
+    pads: Column<Advice>,
+    q_char: Selector,
+    q_not_first: Selector,
+    q_group: Selector,
+    alphabet_chars: TableColumn,
+    alphabet_sextets: TableColumn,
+    alphabet_data: TableColumn,
+    alphabet_pads: TableColumn,
+    bytes: TableColumn,
+    max_encoded_len: usize,
+    _marker: PhantomData<F>,
+}
+
+impl<F: PrimeField> Base64DecodeConfig<F> {
+    pub fn configure(meta: &mut ConstraintSystem<F>, max_encoded_len: usize) -> Self {
+        assert_eq!(
+            max_encoded_len % 4,
+            0,
+            "base64 input is a multiple of 4 characters"
+        );
+        let encoded = meta.advice_column();
+        let sextets = meta.advice_column();
+        let decoded = meta.advice_column();
+        let data = meta.advice_column();
+        let pads = meta.advice_column();
+        let q_char = meta.complex_selector();
+        let q_not_first = meta.selector();
+        let q_group = meta.selector();
+        let alphabet_chars = meta.lookup_table_column();
+        let alphabet_sextets = meta.lookup_table_column();
+        let alphabet_data = meta.lookup_table_column();
+        let alphabet_pads = meta.lookup_table_column();
+        let bytes = meta.lookup_table_column();
+
+        meta.enable_equality(encoded);
+        meta.enable_equality(decoded);
+        meta.enable_equality(data);
+
+        meta.create_gate("base64 group decoding", |meta| {
+            let q = meta.query_selector(q_group);
+            let sextet = |idx: i32| meta.query_advice(sextets, Rotation(idx));
+            let (s0, s1, s2, s3) = (sextet(0), sextet(1), sextet(2), sextet(3));
+            let byte = |idx: i32| meta.query_advice(decoded, Rotation(idx));
+            let (d0, d1, d2, d3) = (byte(0), byte(1), byte(2), byte(3));
+            let constant = |val: u64| Expression::Constant(F::from(val));
+            let packed_sextets =
+                s0 * constant(1 << 18) + s1 * constant(1 << 12) + s2 * constant(1 << 6) + s3;
+            let packed_bytes = d0 * constant(1 << 16) + d1 * constant(1 << 8) + d2;
+            vec![q.clone() * (packed_sextets - packed_bytes), q * d3]
+        });
+
+        meta.create_gate("base64 padding", |meta| {
+            let q = meta.query_selector(q_group);
+            let data_flag = |idx: i32| meta.query_advice(data, Rotation(idx));
+            let (a0, a1, a2, a3) = (data_flag(0), data_flag(1), data_flag(2), data_flag(3));
+            let pad_flag = |idx: i32| meta.query_advice(pads, Rotation(idx));
+            let (p0, p1, p2, p3) = (pad_flag(0), pad_flag(1), pad_flag(2), pad_flag(3));
+            let byte = |idx: i32| meta.query_advice(decoded, Rotation(idx));
+            let (d0, d1, d2) = (byte(0), byte(1), byte(2));
+            let one = Expression::Constant(F::from(1));
+            vec![
+                // The first two characters of a group are both data or both 0, never `=`.
+                q.clone() * (a0.clone() - a1.clone()),
+                q.clone() * p0,
+                q.clone() * p1,
+                // A group after the input has no `=`, and one of the input no 0.
+                q.clone() * (one.clone() - a0.clone()) * p2.clone(),
+                q.clone() * (one.clone() - a0.clone()) * p3.clone(),
+                q.clone() * a0.clone() * (one.clone() - a2.clone() - p2),
+                q.clone() * a0 * (one.clone() - a3.clone() - p3),
+                // The bytes that are not decoded are 0.
+                q.clone() * (one.clone() - a1) * d0,
+                q.clone() * (one.clone() - a2) * d1,
+                q * (one - a3) * d2,
+            ]
+        });
+
+        // The data rows come first, so that `=` ends the input and the decoded bytes are a prefix
+        // of the positions.
+        meta.create_gate("base64 data comes first", |meta| {
+            let q = meta.query_selector(q_not_first);
+            let cur_data = meta.query_advice(data, Rotation::cur());
+            let prev_data = meta.query_advice(data, Rotation::prev());
+            vec![q * cur_data * (Expression::Constant(F::from(1)) - prev_data)]
+        });
+
+        meta.lookup("base64 alphabet", |meta| {
+            let q = meta.query_selector(q_char);
+            let char = meta.query_advice(encoded, Rotation::cur());
+            let sextet = meta.query_advice(sextets, Rotation::cur());
+            let data_flag = meta.query_advice(data, Rotation::cur());
+            let pad_flag = meta.query_advice(pads, Rotation::cur());
+            vec![
+                (q.clone() * char, alphabet_chars),
+                (q.clone() * sextet, alphabet_sextets),
+                (q.clone() * data_flag, alphabet_data),
+                (q * pad_flag, alphabet_pads),
+            ]
+        });
+
+        meta.lookup("decoded bytes", |meta| {
+            let q = meta.query_selector(q_char);
+            let byte = meta.query_advice(decoded, Rotation::cur());
+            vec![(q * byte, bytes)]
+        });
+
+        Self {
+            encoded,
+            sextets,
+            decoded,
+            data,
+            pads,
+            q_char,
+            q_not_first,
+            q_group,
+            alphabet_chars,
+            alphabet_sextets,
+            alphabet_data,
+            alphabet_pads,
+            bytes,
+            max_encoded_len,
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn load(&self, layouter: &mut impl Layouter<F>) -> Result<(), Error> {
+        layouter.assign_table(
+            || "base64 alphabet",
+            |mut table| {
+                // `[char, sextet, data flag, pad flag]`: a row [0, 0, 0, 0] for disabled rows and
+                // the rows after the input, then the padding `=` and the alphabet.
+                let rows = [(0, 0, 0, 0), (b'=', 0, 0, 1)].into_iter().chain(
+                    BASE64_ALPHABET
+                        .iter()
+                        .enumerate()
+                        .map(|(i, c)| (*c, i as u64, 1, 0)),
+                );
+                for (offset, (char, sextet, data, pad)) in rows.enumerate() {
+                    for (column, val) in [
+                        (self.alphabet_chars, char as u64),
+                        (self.alphabet_sextets, sextet),
+                        (self.alphabet_data, data),
+                        (self.alphabet_pads, pad),
+                    ] {
+                        table.assign_cell(
+                            || format!("alphabet at {}", offset),
+                            column,
+                            offset,
+                            || Value::known(F::from(val)),
+                        )?;
+                    }
+                }
+                Ok(())
+            },
+        )?;
+        layouter.assign_table(
+            || "bytes",
+            |mut table| {
+                for byte in 0..256 {
+                    table.assign_cell(
+                        || format!("byte {}", byte),
+                        self.bytes,
+                        byte,
+                        || Value::known(F::from(byte as u64)),
+                    )?;
+                }
+                Ok(())
+            },
+        )
+    }
+
+    fn sextet(char: u8) -> Option<u64> {
+        if char == b'=' {
+            return Some(0);
+        }
+        BASE64_ALPHABET
+            .iter()
+            .position(|c| *c == char)
+            .map(|idx| idx as u64)
+    }
+}
+
+impl<F: PrimeField> InputDecoder<F> for Base64DecodeConfig<F> {
+    fn decode(&self, region: &mut Region<F>, encoded: &[u8]) -> Result<DecodedInput<F>, Error> {
+        let num_padding = encoded.iter().rev().take_while(|c| **c == b'=').count();
+        if encoded.len() % 4 != 0
+            || encoded.len() > self.max_encoded_len
+            || num_padding > 2
+            || encoded[..encoded.len() - num_padding].contains(&b'=')
+        {
+            return Err(Error::Synthesis);
+        }
+        let mut sextets = encoded
+            .iter()
+            .map(|char| Self::sextet(*char).ok_or(Error::Synthesis))
+            .collect::<Result<Vec<u64>, Error>>()?;
+        sextets.resize(self.max_encoded_len, 0);
+        let mut bytes = Vec::new();
+        let mut encoded_cells = Vec::new();
+        let mut cells = Vec::new();
+        let mut enable_flags = Vec::new();
+        for (group, chunk) in sextets.chunks(4).enumerate() {
+            let offset = 4 * group;
+            self.q_group.enable(region, offset)?;
+            let packed = (chunk[0] << 18) | (chunk[1] << 12) | (chunk[2] << 6) | chunk[3];
+            let decoded = [packed >> 16, (packed >> 8) & 0xff, packed & 0xff, 0];
+            for idx in 0..4 {
+                let row = offset + idx;
+                let char = encoded.get(row).copied().unwrap_or(0);
+                self.q_char.enable(region, row)?;
+                if row > 0 {
+                    self.q_not_first.enable(region, row)?;
+                }
+                encoded_cells.push(region.assign_advice(
+                    || format!("encoded at {}", row),
+                    self.encoded,
+                    row,
+                    || Value::known(F::from(char as u64)),
+                )?);
+                region.assign_advice(
+                    || format!("sextet at {}", row),
+                    self.sextets,
+                    row,
+                    || Value::known(F::from(chunk[idx])),
+                )?;
+                let data = region.assign_advice(
+                    || format!("data flag at {}", row),
+                    self.data,
+                    row,
+                    || Value::known(F::from((char != 0 && char != b'=') as u64)),
+                )?;
+                region.assign_advice(
+                    || format!("pad flag at {}", row),
+                    self.pads,
+                    row,
+                    || Value::known(F::from((char == b'=') as u64)),
+                )?;
+                let assigned = region.assign_advice(
+                    || format!("decoded at {}", row),
+                    self.decoded,
+                    row,
+                    || Value::known(F::from(decoded[idx])),
+                )?;
+                if idx > 0 {
+                    enable_flags.push(data);
+                }
+                if idx < 3 {
+                    bytes.push(decoded[idx] as u8);
+                    cells.push(assigned);
+                }
+            }
+        }
+        bytes.truncate(encoded.len() / 4 * 3 - num_padding);
+        Ok(DecodedInput {
+            bytes,
+            encoded: encoded_cells,
+            cells,
+            enable_flags,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use halo2_base::halo2_proofs::{
+        circuit::SimpleFloorPlanner,
+        dev::{MockProver, VerifyFailure},
+        halo2curves::bn256::Fr,
+        plonk::{Circuit, Instance},
+    };
+
+    use super::*;
+    use crate::{compile_regex, RegexCheckConfig};
+
+    const MAX_ENCODED_LEN: usize = 32;
+    const MAX_STRING_LEN: usize = MAX_ENCODED_LEN / 4 * 3;
+
+    /// A decoder that drops the last `num_dropped` decoded bytes, as if the prover chose how
+    /// many bytes the padding drops.
+    #[derive(Debug, Clone)]
+    struct TruncatingDecoder<F: PrimeField> {
+        decoder: Base64DecodeConfig<F>,
+        num_dropped: usize,
+    }
+
+    impl<F: PrimeField> InputDecoder<F> for TruncatingDecoder<F> {
+        fn decode(&self, region: &mut Region<F>, encoded: &[u8]) -> Result<DecodedInput<F>, Error> {
+            let mut decoded = self.decoder.decode(region, encoded)?;
+            let len = decoded.bytes.len().saturating_sub(self.num_dropped);
+            decoded.bytes.truncate(len);
+            Ok(decoded)
+        }
+    }
+
+    #[derive(Default, Clone, Debug)]
+    struct TestBase64RegexCircuit<F: PrimeField> {
+        encoded: Vec<u8>,
+        num_dropped: usize,
+        _marker: PhantomData<F>,
+    }
+
+    impl<F: PrimeField> Circuit<F> for TestBase64RegexCircuit<F> {
+        type Config = (RegexCheckConfig<F>, Base64DecodeConfig<F>, Column<Instance>);
+        type FloorPlanner = SimpleFloorPlanner;
+
+        fn without_witnesses(&self) -> Self {
+            Self {
+                encoded: vec![],
+                num_dropped: 0,
+                _marker: PhantomData,
+            }
+        }
+
+        fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+            let regex_def = compile_regex("Subject: [A-Za-z ]+").unwrap().regex_def();
+            let instance = meta.instance_column();
+            meta.enable_equality(instance);
+            (
+                RegexCheckConfig::configure(meta, regex_def, MAX_STRING_LEN),
+                Base64DecodeConfig::configure(meta, MAX_ENCODED_LEN),
+                instance,
+            )
+        }
+
+        fn synthesize(
+            &self,
+            config: Self::Config,
+            mut layouter: impl Layouter<F>,
+        ) -> Result<(), Error> {
+            let (regex_config, decoder, instance) = config;
+            regex_config.load(&mut layouter)?;
+            decoder.load(&mut layouter)?;
+            let decoder = TruncatingDecoder {
+                decoder,
+                num_dropped: self.num_dropped,
+            };
+            let (decoded, _) = layouter.assign_region(
+                || "regex",
+                |mut region| {
+                    regex_config.assign_values_decoded(&mut region, &self.encoded, &decoder)
+                },
+            )?;
+            for (row, cell) in decoded.encoded.iter().enumerate() {
+                layouter.constrain_instance(cell.cell(), instance, row)?;
+            }
+            Ok(())
+        }
+    }
+
+    fn run(encoded: &[u8], num_dropped: usize) -> Result<(), Vec<VerifyFailure>> {
+        let circuit = TestBase64RegexCircuit::<Fr> {
+            encoded: encoded.to_vec(),
+            num_dropped,
+            _marker: PhantomData,
+        };
+        let mut instance: Vec<Fr> = encoded.iter().map(|char| Fr::from(*char as u64)).collect();
+        instance.resize(MAX_ENCODED_LEN, Fr::from(0));
+        MockProver::run(9, &circuit, vec![instance])
+            .unwrap()
+            .verify()
+    }
+
+    #[test]
+    fn test_base64_decoded_match() {
+        // "Subject: Hello World"
+        assert_eq!(run(b"U3ViamVjdDogSGVsbG8gV29ybGQ=", 0), Ok(()));
+        // "Subject: Hi"
+        assert_eq!(run(b"U3ViamVjdDogSGk=", 0), Ok(()));
+        // "Subject: Hello 2" is valid base64 but does not match.
+        assert!(run(b"U3ViamVjdDogSGVsbG8gMg==", 0).is_err());
+    }
+
+    #[test]
+    fn test_base64_forged_truncation() {
+        // Checking "Subject: Hello " instead of "Subject: Hello 2" would match, but the enable
+        // flags of the input are tied to the decoded length.
+        let failures = format!("{:?}", run(b"U3ViamVjdDogSGVsbG8gMg==", 1).unwrap_err());
+        assert!(failures.contains("Permutation"));
+        // Dropping a byte of an accepted input is caught the same way.
+        assert!(run(b"U3ViamVjdDogSGk=", 1).is_err());
+    }
+}
@@ -0,0 +1,207 @@
+use alloc::string::String;
+use core::fmt;
+
+/// Errors returned while compiling a regex or preparing its tables.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RegexError {
+    /// The pattern is not valid regex syntax. `position` is the char offset in the pattern.
+    Syntax { position: usize, message: String },
+    /// The table has no transition for `char` from `state`, the state reached after `offset`
+    /// characters of the input.
+    NoTransition { offset: usize, char: u8, state: u64 },
+    /// The CRC-32 of a table buffer differs from the expected one.
+    ChecksumMismatch { expected: u32, actual: u32 },
+    /// The pattern uses a construct that is valid in other regex engines but not regular, or
+    /// not expressible as a DFA over bytes, such as `"backreference"` or `"lookahead"`.
+    UnsupportedFeature(&'static str),
+    /// A table buffer is not in the expected format.
+    InvalidTable(&'static str),
+    /// The table file at `path` could not be read.
+    Io { path: String, message: String },
+    /// The table file at `path` is empty.
+    EmptyTable { path: String },
+    /// No accept state is reachable from the start state, so no input matches.
+    EmptyLanguage,
+    /// The captures `first` and `second` share a `(prev_state, next_state)` transition, so a
+    /// character consumed by it is extracted into both.
+    AmbiguousCaptures { first: usize, second: usize },
+    /// The witnessed `states[index]` is `actual`, but the table gives `expected`: the first state
+    /// for index 0, else the transition from `states[index - 1]` on `characters[index - 1]`
+    /// (`None` if there is no such transition).
+    InvalidWitness {
+        index: usize,
+        expected: Option<u64>,
+        actual: u64,
+    },
+    /// Groups or stacked quantifiers nest deeper than `max_depth` at char offset `position` of
+    /// the pattern.
+    TooDeep { position: usize, max_depth: usize },
+    /// An alternation has more than `max_alternatives` branches; `position` is the char offset
+    /// of the first `|` past the limit.
+    TooManyAlternatives {
+        position: usize,
+        max_alternatives: usize,
+    },
+    /// A witness for `expected - 1` characters has `actual` states instead of `expected`.
+    WitnessLength { expected: usize, actual: usize },
+    /// The input ends in `state`, which is not accepted.
+    NotAccepted { state: u64 },
+    /// The lookup tables need `rows` rows, but the circuit only has `capacity` usable rows for
+    /// the chosen `k`.
+    TableTooLargeForK { rows: usize, capacity: usize },
+    /// A capture definition names the transition from `prev_state` to `next_state`, which the
+    /// table does not have on any character.
+    MissingTransition { prev_state: u64, next_state: u64 },
+    /// The transitions of a capture definition fall apart into several regions; the one from
+    /// `prev_state` to `next_state` is not connected to the lowest one.
+    DisconnectedCapture { prev_state: u64, next_state: u64 },
+    /// The state value `state` is not a state of the DFA.
+    UnknownState { state: u64 },
+    /// A transition from `state` on `char` is added, but it already leads to `existing`.
+    ConflictingTransition { state: u64, char: u8, existing: u64 },
+    /// Capture `group` of a concatenation does not end exactly at its first delimiter: after
+    /// the transition from `prev_state` to `next_state` it can end without a delimiter, or
+    /// continue after one.
+    UndelimitedCapture {
+        group: usize,
+        prev_state: u64,
+        next_state: u64,
+    },
+    /// Capture `group` of a concatenation is empty on some accepted input, so it leaves no
+    /// delimiter either.
+    OptionalCapture { group: usize },
+}
+
+impl fmt::Display for RegexError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RegexError::Syntax { position, message } => {
+                write!(f, "regex syntax error at {}: {}", position, message)
+            }
+            RegexError::NoTransition {
+                offset,
+                char,
+                state,
+            } => write!(
+                f,
+                "no transition for character {} from state {} at offset {}",
+                char, state, offset
+            ),
+            RegexError::ChecksumMismatch { expected, actual } => write!(
+                f,
+                "table checksum mismatch: expected {:08x}, got {:08x}",
+                expected, actual
+            ),
+            RegexError::UnsupportedFeature(feature) => {
+                write!(f, "unsupported regex feature: {}", feature)
+            }
+            RegexError::InvalidTable(message) => write!(f, "invalid table: {}", message),
+            RegexError::Io { path, message } => {
+                write!(f, "failed to read table file {}: {}", path, message)
+            }
+            RegexError::EmptyTable { path } => write!(f, "table file {} is empty", path),
+            RegexError::EmptyLanguage => write!(f, "no accept state is reachable"),
+            RegexError::AmbiguousCaptures { first, second } => {
+                write!(f, "captures {} and {} share a transition", first, second)
+            }
+            RegexError::InvalidWitness {
+                index,
+                expected: Some(expected),
+                actual,
+            } => write!(
+                f,
+                "witness state {} is {}, expected {}",
+                index, actual, expected
+            ),
+            RegexError::InvalidWitness {
+                index,
+                expected: None,
+                actual,
+            } => write!(
+                f,
+                "witness state {} is {}, but its previous state has no transition on the character",
+                index, actual
+            ),
+            RegexError::TooDeep {
+                position,
+                max_depth,
+            } => write!(
+                f,
+                "the pattern nests deeper than {} at {}",
+                max_depth, position
+            ),
+            RegexError::TooManyAlternatives {
+                position,
+                max_alternatives,
+            } => write!(
+                f,
+                "an alternation has more than {} branches at {}",
+                max_alternatives, position
+            ),
+            RegexError::WitnessLength { expected, actual } => {
+                write!(f, "witness has {} states, expected {}", actual, expected)
+            }
+            RegexError::NotAccepted { state } => {
+                write!(
+                    f,
+                    "the input ends in state {}, which is not accepted",
+                    state
+                )
+            }
+            RegexError::TableTooLargeForK { rows, capacity } => write!(
+                f,
+                "the tables need {} rows, but only {} are usable with this k",
+                rows, capacity
+            ),
+            RegexError::MissingTransition {
+                prev_state,
+                next_state,
+            } => write!(
+                f,
+                "the capture names the transition {} -> {}, which the table does not have",
+                prev_state, next_state
+            ),
+            RegexError::DisconnectedCapture {
+                prev_state,
+                next_state,
+            } => write!(
+                f,
+                "the capture transition {} -> {} is not connected to the others",
+                prev_state, next_state
+            ),
+            RegexError::UnknownState { state } => write!(f, "unknown state {}", state),
+            RegexError::ConflictingTransition {
+                state,
+                char,
+                existing,
+            } => write!(
+                f,
+                "state {} already has a transition on character {} to state {}",
+                state, char, existing
+            ),
+            RegexError::UndelimitedCapture {
+                group,
+                prev_state,
+                next_state,
+            } => write!(
+                f,
+                "capture {} is not delimited after the transition {} -> {}",
+                group, prev_state, next_state
+            ),
+            RegexError::OptionalCapture { group } => write!(f, "capture {} may be empty", group),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for RegexError {}
+
+/// halo2's `Error` has no variant for errors of a chip, so the `RegexError` is kept as the
+/// source of an `io::Error` in `Error::Transcript`, from which
+/// `get_ref().and_then(|err| err.downcast_ref::<RegexError>())` recovers it.
+#[cfg(feature = "std")]
+impl From<RegexError> for halo2_base::halo2_proofs::plonk::Error {
+    fn from(err: RegexError) -> Self {
+        Self::Transcript(std::io::Error::new(std::io::ErrorKind::Other, err))
+    }
+}
@@ -1,5 +1,34 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+extern crate alloc;
+
+#[cfg(feature = "std")]
+mod committed;
+mod compiler;
+#[cfg(feature = "std")]
+mod decode;
+mod error;
+#[cfg(feature = "std")]
+mod macros;
+#[cfg(feature = "std")]
 mod regex;
+#[cfg(feature = "std")]
 mod substr;
+#[cfg(feature = "std")]
 pub mod table;
+#[cfg(all(feature = "std", any(test, feature = "test-utils")))]
+pub mod test_utils;
+#[cfg(feature = "std")]
+pub use committed::*;
+pub use compiler::*;
+#[cfg(feature = "std")]
+pub use decode::*;
+pub use error::*;
+#[cfg(feature = "std")]
 pub use regex::*;
+#[cfg(feature = "std")]
 pub use substr::*;
+
+// Used by `regex_circuit!`.
+#[cfg(feature = "std")]
+#[doc(hidden)]
+pub use halo2_base as __halo2_base;
@@ -0,0 +1,102 @@
+/// Generates a `Circuit` that checks its `characters` witness against a [`RegexCheckConfig`].
+///
+/// `regex_circuit!(pub EmailCircuit, regex_def, max_chars_size)` expands to:
+///
+/// - `pub struct EmailCircuit<F: PrimeField> { pub characters: Vec<u8>, .. }` deriving
+///   `Default`, `Clone` and `Debug`, with `EmailCircuit::new(characters)`;
+/// - an `impl Circuit<F>` with `Config = RegexCheckConfig<F>` and `SimpleFloorPlanner`, whose
+///   `without_witnesses` empties `characters`, whose `configure` calls
+///   `RegexCheckConfig::configure(meta, regex_def, max_chars_size)`, and whose `synthesize`
+///   loads the tables and assigns `characters` in one region.
+///
+/// `regex_def` and `max_chars_size` are expressions evaluated inside `configure`.
+///
+/// [`RegexCheckConfig`]: crate::RegexCheckConfig
+#[macro_export]
+macro_rules! regex_circuit {
+    ($vis:vis $name:ident, $regex_def:expr, $max_chars_size:expr) => {
+        #[derive(Default, Clone, Debug)]
+        $vis struct $name<F: $crate::__halo2_base::utils::PrimeField> {
+            pub characters: Vec<u8>,
+            _marker: ::std::marker::PhantomData<F>,
+        }
+
+        impl<F: $crate::__halo2_base::utils::PrimeField> $name<F> {
+            pub fn new(characters: Vec<u8>) -> Self {
+                Self {
+                    characters,
+                    _marker: ::std::marker::PhantomData,
+                }
+            }
+        }
+
+        impl<F: $crate::__halo2_base::utils::PrimeField>
+            $crate::__halo2_base::halo2_proofs::plonk::Circuit<F> for $name<F>
+        {
+            type Config = $crate::RegexCheckConfig<F>;
+            type FloorPlanner = $crate::__halo2_base::halo2_proofs::circuit::SimpleFloorPlanner;
+
+            fn without_witnesses(&self) -> Self {
+                Self::new(vec![])
+            }
+
+            fn configure(
+                meta: &mut $crate::__halo2_base::halo2_proofs::plonk::ConstraintSystem<F>,
+            ) -> Self::Config {
+                $crate::RegexCheckConfig::configure(meta, $regex_def, $max_chars_size)
+            }
+
+            fn synthesize(
+                &self,
+                config: Self::Config,
+                mut layouter: impl $crate::__halo2_base::halo2_proofs::circuit::Layouter<F>,
+            ) -> Result<(), $crate::__halo2_base::halo2_proofs::plonk::Error> {
+                config.load(&mut layouter)?;
+                $crate::__halo2_base::halo2_proofs::circuit::Layouter::assign_region(
+                    &mut layouter,
+                    || "regex",
+                    |mut region| {
+                        config.assign_values(&mut region, &self.characters)?;
+                        Ok(())
+                    },
+                )?;
+                Ok(())
+            }
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{compile_regex, RegexDef};
+    use halo2_base::halo2_proofs::{dev::MockProver, halo2curves::bn256::Fr};
+
+    crate::regex_circuit!(
+        SampleRegexCircuit,
+        RegexDef::read_from_text("./test_regexes/regex_test_lookup.txt"),
+        128
+    );
+    crate::regex_circuit!(
+        EmailRegexCircuit,
+        compile_regex(r"[a-z]+@[a-z]+\.com").unwrap().regex_def(),
+        32
+    );
+
+    #[test]
+    fn test_regex_circuit_macro() {
+        let circuit = SampleRegexCircuit::<Fr>::new(b"email was meant for @y.".to_vec());
+        MockProver::run(9, &circuit, vec![])
+            .unwrap()
+            .assert_satisfied();
+        let circuit = SampleRegexCircuit::<Fr>::new(b"email was meant for @y".to_vec());
+        assert!(MockProver::run(9, &circuit, vec![])
+            .unwrap()
+            .verify()
+            .is_err());
+
+        let circuit = EmailRegexCircuit::<Fr>::new(b"bob@mail.com".to_vec());
+        MockProver::run(7, &circuit, vec![])
+            .unwrap()
+            .assert_satisfied();
+    }
+}
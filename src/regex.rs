@@ -2,80 +2,352 @@ use halo2_base::halo2_proofs::{
     circuit::{AssignedCell, Layouter, Region, SimpleFloorPlanner, Value},
     plonk::{
         Advice, Assigned, Circuit, Column, ConstraintSystem, Constraints, Error, Expression, Fixed,
-        Instance, Selector, TableColumn,
+        Instance, Selector, TableColumn, VirtualCells,
     },
     poly::Rotation,
 };
 use halo2_base::{
     gates::{flex_gate::FlexGateConfig, range::RangeConfig, GateInstructions, RangeInstructions},
-    utils::{bigint_to_fe, biguint_to_fe, fe_to_biguint, modulus, PrimeField},
+    utils::{bigint_to_fe, biguint_to_fe, fe_to_biguint, modulus, value_to_option, PrimeField},
     AssignedValue, Context, QuantumCell,
 };
-use std::{collections::HashMap, marker::PhantomData};
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    marker::PhantomData,
+    sync::Mutex,
+};
 
-pub use crate::table::{RegexDef, TransitionTableConfig};
+use crate::table::table_checksum;
+pub use crate::table::{PreparedRegex, RegexChar, RegexDef, TransitionTableConfig};
+use crate::{DecodedInput, InputDecoder, RegexError};
 #[derive(Debug, Clone)]
 struct RangeConstrained<F: PrimeField>(AssignedCell<F, F>);
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone)]
 pub struct AssignedRegexResult<F: PrimeField> {
     pub enable_flags: Vec<AssignedCell<F, F>>,
     pub characters: Vec<AssignedCell<F, F>>,
     pub states: Vec<AssignedCell<F, F>>,
+    /// 1 on the padding rows after the input, which start at its end and continue to the end.
+    pub is_padding: Vec<AssignedCell<F, F>>,
+    /// `states[i + 1] - states[i]` for each enabled row and 0 on padding rows, if configured
+    /// with [`RegexCheckConfig::configure_state_deltas`]; empty otherwise.
+    pub state_deltas: Vec<AssignedCell<F, F>>,
+    /// The offset of each character in the buffer the input is a slice of, if configured with
+    /// [`RegexCheckConfig::configure_offsets`]; empty otherwise.
+    pub offsets: Vec<AssignedCell<F, F>>,
+    /// The input bytes before folding, if configured with [`RegexCheckConfig::configure_case_fold`];
+    /// empty otherwise. `characters` then holds the folded bytes.
+    pub raw_characters: Vec<AssignedCell<F, F>>,
+    /// The accepting state the input ended in, so that DFAs whose accepting states tell
+    /// alternatives apart can be branched on. An empty input ends in the first state.
+    pub accepted_state: AssignedCell<F, F>,
+    /// The number of characters after which the state was a hit state, if configured with
+    /// [`RegexCheckConfig::configure_match_count`].
+    pub match_count: Option<AssignedCell<F, F>>,
+    /// The flags of each tag on each character, if configured with
+    /// [`RegexCheckConfig::configure_transition_tags`]; empty otherwise.
+    pub transition_tags: Vec<Vec<AssignedCell<F, F>>>,
+    /// The row after the `max_chars_size + 1` rows of the check, where the next check of the
+    /// same config may start in the same region.
+    pub next_offset: usize,
+}
+
+/// Row usage of a [`RegexCheckConfig`] of the plain [`RegexCheckConfig::configure`], computed
+/// without synthesis. It is a lower bound: the blinding rows and the tables of the optional
+/// columns (length range, case folding, match count and transition tags) are not counted, see
+/// [`RegexCheckConfig::check_table_rows`] for a configured instance.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CostEstimate {
+    /// Rows of the advice columns, including the row holding the final state.
+    pub advice_rows: usize,
+    /// Rows whose transition is checked against the transition table.
+    pub lookup_rows: usize,
+    /// Rows of the largest lookup table, including the dummy row.
+    pub table_rows: usize,
+}
+
+/// A selector of the rows of each input, either a complex selector or a fixed column for backends
+/// that handle fixed columns better. Both are enabled on the same rows, which do not depend on the
+/// input: the fixed values are part of the verifying key, so they cannot follow the input length.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RowSelector {
+    Complex(Selector),
+    Fixed(Column<Fixed>),
+}
+
+impl RowSelector {
+    fn new<F: PrimeField>(meta: &mut ConstraintSystem<F>, fixed: bool) -> Self {
+        if fixed {
+            Self::Fixed(meta.fixed_column())
+        } else {
+            Self::Complex(meta.complex_selector())
+        }
+    }
+
+    fn query<F: PrimeField>(&self, meta: &mut VirtualCells<F>) -> Expression<F> {
+        match self {
+            Self::Complex(selector) => meta.query_selector(*selector),
+            Self::Fixed(column) => meta.query_fixed(*column, Rotation::cur()),
+        }
+    }
+
+    fn enable<F: PrimeField>(&self, region: &mut Region<F>, offset: usize) -> Result<(), Error> {
+        match self {
+            Self::Complex(selector) => selector.enable(region, offset),
+            Self::Fixed(column) => region
+                .assign_fixed(
+                    || format!("selector at {}", offset),
+                    *column,
+                    offset,
+                    || Value::known(F::from(1)),
+                )
+                .map(|_| ()),
+        }
+    }
+
+    fn is_fixed(&self) -> bool {
+        matches!(self, Self::Fixed(_))
+    }
+}
+
+/// Columns of the length-range check added by [`RegexCheckConfig::configure_length_range`].
+#[derive(Debug, Clone)]
+struct LengthRangeConfig {
+    min_length: usize,
+    max_length: usize,
+    // The number of non-padding rows up to each row.
+    count: Column<Advice>,
+    length_flags: TableColumn,
+    lengths: TableColumn,
+}
+
+/// Columns of the case folding added by [`RegexCheckConfig::configure_case_fold`].
+#[derive(Debug, Clone)]
+struct CaseFoldConfig {
+    raw_characters: Column<Advice>,
+    // Every byte next to its ASCII lowercase.
+    raw_bytes: TableColumn,
+    folded_bytes: TableColumn,
+}
+
+/// Columns of the match count added by [`RegexCheckConfig::configure_match_count`].
+#[derive(Debug, Clone)]
+struct MatchCountConfig {
+    hit_state_vals: Vec<u64>,
+    // 1 on the enabled rows whose next state is a hit state.
+    hits: Column<Advice>,
+    // The number of hits up to each row.
+    count: Column<Advice>,
+    // Every state next to whether it is a hit state.
+    enabled: TableColumn,
+    states: TableColumn,
+    hit_flags: TableColumn,
+}
+
+/// Columns of the transition tags added by [`RegexCheckConfig::configure_transition_tags`].
+#[derive(Debug, Clone)]
+struct TransitionTagsConfig<C: RegexChar> {
+    // The tag bits of each transition by its character and previous state; 0 if missing.
+    tags: HashMap<(C, u64), u64>,
+    // The flag of each tag, 1 on the enabled rows whose transition carries it.
+    flags: Vec<Column<Advice>>,
+    // Every transition next to its tag bits.
+    enabled: TableColumn,
+    characters: TableColumn,
+    prev_states: TableColumn,
+    tag_bits: TableColumn,
+}
+
+// The witness of one character row of `RegexCheckConfig::assign_rows_at`.
+#[derive(Debug, Clone, Copy)]
+struct RowWitness<F: PrimeField> {
+    raw_char: Value<F>,
+    // The character the transition is looked up on, the folded one with case folding.
+    char: Value<F>,
+    state: Value<F>,
+    next_state: Value<F>,
+    tag_bits: u64,
 }
 
 // Here we decompose a transition into 3-value lookups.
 
 #[derive(Debug, Clone)]
-pub struct RegexCheckConfig<F: PrimeField> {
+pub struct RegexCheckConfig<F: PrimeField, C: RegexChar = u8> {
     characters: Column<Advice>,
     state: Column<Advice>,
     transition_table: TransitionTableConfig<F>,
     char_enable: Column<Advice>,
-    q_first: Selector,
-    not_q_first: Selector,
+    is_padding: Column<Advice>,
+    accepted_state: Column<Advice>,
+    q_first: RowSelector,
+    not_q_first: RowSelector,
+    // The row after the last character.
+    q_last: RowSelector,
+    accepted_flags: TableColumn,
     accepted_states: TableColumn,
-    pub(crate) regex_def: RegexDef,
+    pub(crate) regex_def: RegexDef<C>,
     max_chars_size: usize,
+    length_range: Option<LengthRangeConfig>,
+    state_delta: Option<Column<Advice>>,
+    // The offset column and the offset of the first character.
+    offset: Option<(Column<Advice>, u64)>,
+    case_fold: Option<CaseFoldConfig>,
+    match_count: Option<MatchCountConfig>,
+    transition_tags: Option<TransitionTagsConfig<C>>,
+    // Prefixed to the names of gates, lookups and tables.
+    tag: Option<String>,
+    // The `k` of the circuit, if known, for `load` to check the table sizes against.
+    k: Option<u32>,
+    // The blinding factors of the constraint system as of the last configuration of this
+    // config, optional columns included, for the usable rows of `check_table_rows`.
+    blinding_factors: usize,
     _marker: PhantomData<F>,
 }
 
-impl<F: PrimeField> RegexCheckConfig<F> {
+// halo2 takes `&'static str` names for gates and lookups, so tagged names are leaked, once per
+// distinct name: configuring the same circuit again reuses them.
+fn tagged(tag: Option<&str>, name: &'static str) -> &'static str {
+    static NAMES: Mutex<BTreeSet<&'static str>> = Mutex::new(BTreeSet::new());
+    let tag = match tag {
+        Some(tag) => tag,
+        None => return name,
+    };
+    let tagged_name = format!("{}: {}", tag, name);
+    let mut names = NAMES.lock().unwrap();
+    match names.get(tagged_name.as_str()) {
+        Some(interned) => *interned,
+        None => {
+            let interned: &'static str = Box::leak(tagged_name.into_boxed_str());
+            names.insert(interned);
+            interned
+        }
+    }
+}
+
+impl<F: PrimeField, C: RegexChar> RegexCheckConfig<F, C> {
+    /// Configures the check of inputs of up to `max_chars_size` characters. `regex_def` is not
+    /// validated: a regex accepting no input configures fine, but no proof can be generated for
+    /// it, so check it with [`RegexDef::validate`] before key generation.
     pub fn configure(
         meta: &mut ConstraintSystem<F>,
-        regex_def: RegexDef,
+        regex_def: RegexDef<C>,
+        max_chars_size: usize,
+    ) -> Self {
+        Self::configure_with_selectors(meta, regex_def, max_chars_size, false, None, None)
+    }
+
+    /// Like [`Self::configure`], but prefixes `tag` to the names of the gates, lookups and tables
+    /// of this config, e.g. `"email: lookup characters and their state"`, so that `MockProver`
+    /// failures of circuits with several regexes can be told apart.
+    pub fn configure_tagged(
+        meta: &mut ConstraintSystem<F>,
+        regex_def: RegexDef<C>,
+        max_chars_size: usize,
+        tag: &str,
+    ) -> Self {
+        Self::configure_with_selectors(meta, regex_def, max_chars_size, false, Some(tag), None)
+    }
+
+    /// Like [`Self::configure`], but the rows of the input are selected by fixed columns instead
+    /// of complex selectors, including the one of [`Self::configure_length_range`]. The
+    /// constraints, their degree and the number of rows stay the same.
+    pub fn configure_with_fixed_selectors(
+        meta: &mut ConstraintSystem<F>,
+        regex_def: RegexDef<C>,
         max_chars_size: usize,
+    ) -> Self {
+        Self::configure_with_selectors(meta, regex_def, max_chars_size, true, None, None)
+    }
+
+    /// Configures a config for each of `regex_defs`, all looking up the same transition and
+    /// accepted state tables, which [`Self::load_all`] loads in one pass; [`Self::load`] on each
+    /// config would assign the shared tables more than once. The states of each
+    /// regex are shifted past those of the previous ones (see [`RegexDef::with_state_offset`]),
+    /// so an input can only reach the states of the regex it is checked against.
+    pub fn configure_all(
+        meta: &mut ConstraintSystem<F>,
+        regex_defs: Vec<RegexDef<C>>,
+        max_chars_size: usize,
+    ) -> Vec<Self> {
+        let mut tables = None;
+        let mut offset = 0;
+        regex_defs
+            .into_iter()
+            .map(|regex_def| {
+                let regex_def = regex_def.with_state_offset(offset);
+                offset = regex_def.largest_state_val + 1;
+                let config = Self::configure_with_selectors(
+                    meta,
+                    regex_def,
+                    max_chars_size,
+                    false,
+                    None,
+                    tables.clone(),
+                );
+                tables = Some((
+                    config.transition_table.clone(),
+                    config.accepted_flags,
+                    config.accepted_states,
+                ));
+                config
+            })
+            .collect()
+    }
+
+    fn configure_with_selectors(
+        meta: &mut ConstraintSystem<F>,
+        regex_def: RegexDef<C>,
+        max_chars_size: usize,
+        fixed_selectors: bool,
+        tag: Option<&str>,
+        // The tables of another config to look up instead of new ones.
+        tables: Option<(TransitionTableConfig<F>, TableColumn, TableColumn)>,
     ) -> Self {
         let characters = meta.advice_column();
         let state = meta.advice_column();
         let char_enable = meta.advice_column();
-        let q_first = meta.complex_selector();
-        let not_q_first = meta.complex_selector();
-        let transition_table = TransitionTableConfig::configure(meta);
-        let accepted_states = meta.lookup_table_column();
+        let is_padding = meta.advice_column();
+        let accepted_state = meta.advice_column();
+        let q_first = RowSelector::new(meta, fixed_selectors);
+        let not_q_first = RowSelector::new(meta, fixed_selectors);
+        let q_last = RowSelector::new(meta, fixed_selectors);
+        let (transition_table, accepted_flags, accepted_states) = match tables {
+            Some(tables) => tables,
+            None => (
+                match tag {
+                    Some(tag) => TransitionTableConfig::configure_tagged(meta, tag),
+                    None => TransitionTableConfig::configure(meta),
+                },
+                meta.lookup_table_column(),
+                meta.lookup_table_column(),
+            ),
+        };
 
         meta.enable_equality(characters);
         meta.enable_equality(state);
         meta.enable_equality(char_enable);
+        meta.enable_equality(is_padding);
+        meta.enable_equality(accepted_state);
 
         // let mut accepted_state_vals = regex_def.accepted_state_vals.to_vec();
         // accepted_state_vals.push(0);
 
-        meta.create_gate("The state must start from 1", |meta| {
-            let q_frist = meta.query_selector(q_first);
+        meta.create_gate(tagged(tag, "The state must start from 1"), |meta| {
+            let q_frist = q_first.query(meta);
             let cur_state = meta.query_advice(state, Rotation::cur());
             let cur_enable = meta.query_advice(char_enable, Rotation::cur());
             let not_cur_enable = Expression::Constant(F::from(1)) - cur_enable.clone();
+            // Also on padding, so that an empty input is checked from the first state.
             vec![
                 q_frist.clone()
-                    * cur_enable.clone()
                     * (cur_state - Expression::Constant(F::from(regex_def.first_state_val))),
                 q_frist * cur_enable * not_cur_enable,
             ]
         });
 
-        meta.create_gate("The transition of enable flags", |meta| {
-            let not_q_frist = meta.query_selector(not_q_first);
+        meta.create_gate(tagged(tag, "The transition of enable flags"), |meta| {
+            let not_q_frist = not_q_first.query(meta);
             let cur_enable = meta.query_advice(char_enable, Rotation::cur());
             let not_cur_enable = Expression::Constant(F::from(1)) - cur_enable.clone();
             let prev_enable = meta.query_advice(char_enable, Rotation::prev());
@@ -87,8 +359,63 @@ impl<F: PrimeField> RegexCheckConfig<F> {
             ]
         });
 
+        // An enabled row is looked up with the state on the next row, which for the last row is
+        // outside the check. So the last row is never enabled, and the enable flags fall to 0,
+        // firing the accept check, within the checked rows.
+        meta.create_gate(tagged(tag, "The last row must not be enabled"), |meta| {
+            let q_last = q_last.query(meta);
+            let cur_enable = meta.query_advice(char_enable, Rotation::cur());
+            vec![q_last * cur_enable]
+        });
+
+        meta.create_gate(tagged(tag, "The padding flags"), |meta| {
+            let q_frist = q_first.query(meta);
+            let not_q_frist = not_q_first.query(meta);
+            let cur_padding = meta.query_advice(is_padding, Rotation::cur());
+            let not_cur_padding = Expression::Constant(F::from(1)) - cur_padding.clone();
+            let prev_padding = meta.query_advice(is_padding, Rotation::prev());
+            let cur_enable = meta.query_advice(char_enable, Rotation::cur());
+            let q_any = q_frist + not_q_frist.clone();
+            vec![
+                q_any.clone() * cur_padding.clone() * not_cur_padding.clone(),
+                q_any * (cur_padding + cur_enable - Expression::Constant(F::from(1))),
+                // Once padding starts it continues, so the input is a prefix of the rows and no
+                // padding in between can drop characters from captures or lengths.
+                not_q_frist * prev_padding * not_cur_padding,
+            ]
+        });
+
+        // Padding rows hold the character 0, so the input ends at the last non-zero character and
+        // the accept check cannot fire early with trailing characters flagged as padding. A
+        // trailing NUL character is indistinguishable from padding.
+        meta.create_gate(tagged(tag, "The padding characters must be zero"), |meta| {
+            let q_any = q_first.query(meta) + not_q_first.query(meta);
+            let cur_padding = meta.query_advice(is_padding, Rotation::cur());
+            let character = meta.query_advice(characters, Rotation::cur());
+            vec![q_any * cur_padding * character]
+        });
+
+        // Accumulates the state at the enable 1->0 transition, where the accept check fires, so
+        // that the last row holds the accepting state reached. An empty input has no such
+        // transition; its first row, padding from the start, is checked instead.
+        meta.create_gate(tagged(tag, "The accepted state accumulator"), |meta| {
+            let q_frist = q_first.query(meta);
+            let not_q_frist = not_q_first.query(meta);
+            let cur_acc = meta.query_advice(accepted_state, Rotation::cur());
+            let prev_acc = meta.query_advice(accepted_state, Rotation::prev());
+            let cur_state = meta.query_advice(state, Rotation::cur());
+            let cur_enable = meta.query_advice(char_enable, Rotation::cur());
+            let not_cur_enable = Expression::Constant(F::from(1)) - cur_enable.clone();
+            let prev_enable = meta.query_advice(char_enable, Rotation::prev());
+            let enable_change = prev_enable - cur_enable;
+            vec![
+                q_frist * (cur_acc.clone() - not_cur_enable * cur_state.clone()),
+                not_q_frist * (cur_acc - prev_acc - enable_change * cur_state),
+            ]
+        });
+
         // Lookup each transition value individually, not paying attention to bit count
-        meta.lookup("lookup characters and their state", |meta| {
+        meta.lookup(tagged(tag, "lookup characters and their state"), |meta| {
             let enable = meta.query_advice(char_enable, Rotation::cur());
             let cur_state = meta.query_advice(state, Rotation::cur());
             let next_state = meta.query_advice(state, Rotation::next());
@@ -104,7 +431,9 @@ impl<F: PrimeField> RegexCheckConfig<F> {
                 |   | s_next|
             */
 
+            // The enable flag is looked up too, so an enabled row never matches the dummy row.
             vec![
+                (enable.clone(), transition_table.enabled),
                 (
                     enable.clone() * cur_state + one_minus_enable.clone() * zero.clone(),
                     transition_table.prev_state,
@@ -120,43 +449,492 @@ impl<F: PrimeField> RegexCheckConfig<F> {
             ]
         });
 
-        meta.lookup("The final state must be accepted", |meta| {
-            let not_q_frist = meta.query_selector(not_q_first);
+        meta.lookup(tagged(tag, "The final state must be accepted"), |meta| {
+            let q_frist = q_first.query(meta);
+            let not_q_frist = not_q_first.query(meta);
             let cur_state = meta.query_advice(state, Rotation::cur());
             let cur_enable = meta.query_advice(char_enable, Rotation::cur());
             let prev_enable = meta.query_advice(char_enable, Rotation::prev());
-            let enable_change = not_q_frist * (prev_enable.clone() - cur_enable.clone());
+            let not_cur_enable = Expression::Constant(F::from(1)) - cur_enable.clone();
+            // The end of the input, or a first row that is padding for an empty input.
+            let enable_change =
+                not_q_frist * (prev_enable.clone() - cur_enable.clone()) + q_frist * not_cur_enable;
             let not_enable_change = Expression::Constant(F::from(1)) - enable_change.clone();
             let zero = Expression::Constant(F::from(0));
-            vec![(
-                enable_change * cur_state + not_enable_change * zero,
-                accepted_states,
-            )]
+            vec![
+                (enable_change.clone(), accepted_flags),
+                (
+                    enable_change * cur_state + not_enable_change * zero,
+                    accepted_states,
+                ),
+            ]
         });
 
         Self {
             characters,
             state,
             char_enable,
+            is_padding,
+            accepted_state,
             q_first,
             not_q_first,
+            q_last,
             transition_table,
+            accepted_flags,
             accepted_states,
             regex_def,
             max_chars_size,
+            length_range: None,
+            state_delta: None,
+            offset: None,
+            case_fold: None,
+            match_count: None,
+            transition_tags: None,
+            tag: tag.map(String::from),
+            k: None,
+            blinding_factors: meta.blinding_factors(),
             _marker: PhantomData,
         }
     }
 
+    /// Adds a custom gate over each pair of consecutive characters of the input. `constraints`
+    /// receives the previous and the current character, queried at `Rotation::prev()` and
+    /// `Rotation::cur()` of the characters column, and may query further columns through the
+    /// virtual cells; every returned expression must be 0 on the rows it applies to.
+    ///
+    /// The gate only applies to rows 1 to `max_chars_size - 1` whose character is enabled. The
+    /// first row has no previous character, and padding rows (including the extra row after the
+    /// input) are skipped; since enable flags never go back to 1, the previous character of an
+    /// enabled row is enabled too.
+    pub fn create_digraph_gate(
+        &self,
+        meta: &mut ConstraintSystem<F>,
+        name: &'static str,
+        constraints: impl FnOnce(
+            &mut VirtualCells<F>,
+            Expression<F>,
+            Expression<F>,
+        ) -> Vec<Expression<F>>,
+    ) {
+        let (characters, char_enable, not_q_first) =
+            (self.characters, self.char_enable, self.not_q_first);
+        meta.create_gate(name, |meta| {
+            let not_q_frist = not_q_first.query(meta);
+            let cur_enable = meta.query_advice(char_enable, Rotation::cur());
+            let prev_char = meta.query_advice(characters, Rotation::prev());
+            let cur_char = meta.query_advice(characters, Rotation::cur());
+            Constraints::with_selector(
+                not_q_frist * cur_enable,
+                constraints(meta, prev_char, cur_char),
+            )
+        });
+    }
+
+    /// Additionally requires the input to have between `min_length` and `max_length` characters,
+    /// counted over the rows that are not padding, independently of the regex.
+    pub fn configure_length_range(
+        &mut self,
+        meta: &mut ConstraintSystem<F>,
+        min_length: usize,
+        max_length: usize,
+    ) {
+        assert!(min_length <= max_length && max_length <= self.max_chars_size);
+        let count = meta.advice_column();
+        let length_flags = meta.lookup_table_column();
+        let lengths = meta.lookup_table_column();
+        let (q_first, not_q_first, q_last, is_padding) =
+            (self.q_first, self.not_q_first, self.q_last, self.is_padding);
+
+        meta.create_gate(
+            tagged(self.tag.as_deref(), "The count of non-padding rows"),
+            |meta| {
+                let q_frist = q_first.query(meta);
+                let not_q_frist = not_q_first.query(meta);
+                let cur_count = meta.query_advice(count, Rotation::cur());
+                let prev_count = meta.query_advice(count, Rotation::prev());
+                let not_cur_padding = Expression::Constant(F::from(1))
+                    - meta.query_advice(is_padding, Rotation::cur());
+                vec![
+                    q_frist * (cur_count.clone() - not_cur_padding.clone()),
+                    not_q_frist * (cur_count - prev_count - not_cur_padding),
+                ]
+            },
+        );
+
+        meta.lookup(
+            tagged(self.tag.as_deref(), "The length must be in range"),
+            |meta| {
+                let q_last = q_last.query(meta);
+                let cur_count = meta.query_advice(count, Rotation::cur());
+                vec![
+                    (q_last.clone(), length_flags),
+                    (q_last * cur_count, lengths),
+                ]
+            },
+        );
+
+        self.length_range = Some(LengthRangeConfig {
+            min_length,
+            max_length,
+            count,
+            length_flags,
+            lengths,
+        });
+        self.blinding_factors = meta.blinding_factors();
+    }
+
+    /// Additionally assigns the difference of consecutive states, with a gate reconstructing
+    /// each state from the previous state and delta: `states[i + 1] = states[i] + deltas[i]` on
+    /// enabled rows, and `deltas[i] = 0` on padding rows. A self-loop has delta 0, and a
+    /// transition to a smaller state a negated (field-wrapped) delta.
+    ///
+    /// This does not shrink the witness or the table: the lookup still uses absolute states,
+    /// the deltas are not range checked, and delta records `(prev_state, delta, char)` would
+    /// need one table row per transition as well, as `test_regex_state_deltas` checks. The
+    /// deltas are an extra column for gadgets that consume them.
+    pub fn configure_state_deltas(&mut self, meta: &mut ConstraintSystem<F>) {
+        let state_delta = meta.advice_column();
+        meta.enable_equality(state_delta);
+        let (state, char_enable) = (self.state, self.char_enable);
+        let (q_first, not_q_first) = (self.q_first, self.not_q_first);
+        meta.create_gate(tagged(self.tag.as_deref(), "The state deltas"), |meta| {
+            let q_frist = q_first.query(meta);
+            let not_q_frist = not_q_first.query(meta);
+            let cur_enable = meta.query_advice(char_enable, Rotation::cur());
+            let not_cur_enable = Expression::Constant(F::from(1)) - cur_enable;
+            let prev_enable = meta.query_advice(char_enable, Rotation::prev());
+            let cur_state = meta.query_advice(state, Rotation::cur());
+            let prev_state = meta.query_advice(state, Rotation::prev());
+            let cur_delta = meta.query_advice(state_delta, Rotation::cur());
+            let prev_delta = meta.query_advice(state_delta, Rotation::prev());
+            vec![
+                not_q_frist.clone() * prev_enable * (cur_state - prev_state - prev_delta),
+                (q_frist + not_q_frist) * not_cur_enable * cur_delta,
+            ]
+        });
+        self.state_delta = Some(state_delta);
+        self.blinding_factors = meta.blinding_factors();
+    }
+
+    /// Additionally assigns the offset of each row in a larger buffer the input is a slice of,
+    /// `base_offset + i` on row `i`, so that characters can be copy-constrained to the cells of
+    /// an external hashing or commitment region at the same offsets. The first row is fixed to
+    /// `base_offset` and every following row increments by one, padding rows included.
+    pub fn configure_offsets(&mut self, meta: &mut ConstraintSystem<F>, base_offset: u64) {
+        let offset = meta.advice_column();
+        meta.enable_equality(offset);
+        let (q_first, not_q_first) = (self.q_first, self.not_q_first);
+        meta.create_gate(
+            tagged(self.tag.as_deref(), "The character offsets"),
+            |meta| {
+                let q_frist = q_first.query(meta);
+                let not_q_frist = not_q_first.query(meta);
+                let cur_offset = meta.query_advice(offset, Rotation::cur());
+                let prev_offset = meta.query_advice(offset, Rotation::prev());
+                vec![
+                    q_frist * (cur_offset.clone() - Expression::Constant(F::from(base_offset))),
+                    not_q_frist * (cur_offset - prev_offset - Expression::Constant(F::from(1))),
+                ]
+            },
+        );
+        self.offset = Some((offset, base_offset));
+        self.blinding_factors = meta.blinding_factors();
+    }
+
+    /// Additionally counts the characters after which the DFA is in one of `hit_state_vals`:
+    /// each row's hit flag is looked up next to its next state, and a running sum of the flags
+    /// ends in [`AssignedRegexResult::match_count`].
+    ///
+    /// With the table of a [`crate::CompiledRegex::scanner`] made [`RegexDef::accepting_all`] and
+    /// its original accept states as hit states, this counts the occurrences of the scanned
+    /// pattern in any input; whether overlapping occurrences count is chosen by the scanner.
+    pub fn configure_match_count(
+        &mut self,
+        meta: &mut ConstraintSystem<F>,
+        hit_state_vals: Vec<u64>,
+    ) {
+        let hits = meta.advice_column();
+        let count = meta.advice_column();
+        meta.enable_equality(count);
+        let enabled = meta.lookup_table_column();
+        let states = meta.lookup_table_column();
+        let hit_flags = meta.lookup_table_column();
+        let (q_first, not_q_first, state, char_enable) =
+            (self.q_first, self.not_q_first, self.state, self.char_enable);
+
+        // Padding rows must match the dummy row [0, 0, 0], so they are never hits.
+        meta.lookup(
+            tagged(self.tag.as_deref(), "The hit flag of each next state"),
+            |meta| {
+                let enable = meta.query_advice(char_enable, Rotation::cur());
+                let next_state = meta.query_advice(state, Rotation::next());
+                let hit = meta.query_advice(hits, Rotation::cur());
+                vec![
+                    (enable.clone(), enabled),
+                    (enable * next_state, states),
+                    (hit, hit_flags),
+                ]
+            },
+        );
+
+        meta.create_gate(tagged(self.tag.as_deref(), "The match count"), |meta| {
+            let q_frist = q_first.query(meta);
+            let not_q_frist = not_q_first.query(meta);
+            let cur_count = meta.query_advice(count, Rotation::cur());
+            let prev_count = meta.query_advice(count, Rotation::prev());
+            let cur_hit = meta.query_advice(hits, Rotation::cur());
+            vec![
+                q_frist * (cur_count.clone() - cur_hit.clone()),
+                not_q_frist * (cur_count - prev_count - cur_hit),
+            ]
+        });
+
+        self.match_count = Some(MatchCountConfig {
+            hit_state_vals,
+            hits,
+            count,
+            enabled,
+            states,
+            hit_flags,
+        });
+        self.blinding_factors = meta.blinding_factors();
+    }
+
+    /// Additionally labels each character with `num_tags` independent flags, one per tag: `tags`
+    /// maps the character and previous state of a transition to its tag bits, bit `i` setting
+    /// the flag of tag `i`, and transitions that are missing carry no tags. The flags of each
+    /// row are constrained to the bits of its transition, and are 0 on padding rows, so several
+    /// labelings (e.g. "is a digit" and "is in the domain") can be read off one check.
+    ///
+    /// Each tag costs one advice column and one boolean constraint. All tags share a single
+    /// lookup, of one row per transition, so up to 64 tags add one table of four columns.
+    pub fn configure_transition_tags(
+        &mut self,
+        meta: &mut ConstraintSystem<F>,
+        num_tags: usize,
+        tags: HashMap<(C, u64), u64>,
+    ) {
+        assert!((1..=64).contains(&num_tags));
+        assert!(tags.values().all(|bits| (*bits as u128) >> num_tags == 0));
+        let flags: Vec<Column<Advice>> = (0..num_tags).map(|_| meta.advice_column()).collect();
+        for flag in flags.iter() {
+            meta.enable_equality(*flag);
+        }
+        let enabled = meta.lookup_table_column();
+        let characters = meta.lookup_table_column();
+        let prev_states = meta.lookup_table_column();
+        let tag_bits = meta.lookup_table_column();
+        let (q_first, not_q_first, state, char_enable, char_column) = (
+            self.q_first,
+            self.not_q_first,
+            self.state,
+            self.char_enable,
+            self.characters,
+        );
+
+        meta.create_gate(
+            tagged(self.tag.as_deref(), "The tag flags must be boolean"),
+            |meta| {
+                let q_any = q_first.query(meta) + not_q_first.query(meta);
+                flags
+                    .iter()
+                    .map(|flag| {
+                        let flag = meta.query_advice(*flag, Rotation::cur());
+                        q_any.clone() * flag.clone() * (Expression::Constant(F::from(1)) - flag)
+                    })
+                    .collect::<Vec<_>>()
+            },
+        );
+
+        // Padding rows must match the dummy row [0, 0, 0, 0], so their flags are all 0.
+        meta.lookup(
+            tagged(self.tag.as_deref(), "The tags of each transition"),
+            |meta| {
+                let enable = meta.query_advice(char_enable, Rotation::cur());
+                let cur_state = meta.query_advice(state, Rotation::cur());
+                let character = meta.query_advice(char_column, Rotation::cur());
+                let bits = flags.iter().enumerate().fold(
+                    Expression::Constant(F::zero()),
+                    |bits, (idx, flag)| {
+                        bits + meta.query_advice(*flag, Rotation::cur())
+                            * Expression::Constant(F::from(1u64 << idx))
+                    },
+                );
+                vec![
+                    (enable.clone(), enabled),
+                    (enable.clone() * character, characters),
+                    (enable * cur_state, prev_states),
+                    (bits, tag_bits),
+                ]
+            },
+        );
+
+        self.transition_tags = Some(TransitionTagsConfig {
+            tags,
+            flags,
+            enabled,
+            characters,
+            prev_states,
+            tag_bits,
+        });
+        self.blinding_factors = meta.blinding_factors();
+    }
+
+    fn table_name(&self, name: &str) -> String {
+        match self.tag.as_deref() {
+            Some(tag) => format!("{}: {}", tag, name),
+            None => name.to_string(),
+        }
+    }
+
+    pub fn estimate_cost(regex_def: &RegexDef<C>, max_chars_size: usize) -> CostEstimate {
+        // Both tables get a dummy row: [0, 0, 0] for transitions and 0 for accepted states.
+        let transition_rows = regex_def.state_lookup.len() + 1;
+        let accepted_rows = regex_def.accepted_state_vals.len() + 1;
+        CostEstimate {
+            advice_rows: max_chars_size + 1,
+            lookup_rows: max_chars_size,
+            table_rows: transition_rows.max(accepted_rows),
+        }
+    }
+
+    /// The smallest `k` for which the circuit of the plain [`Self::configure`] fits. Halo2 keeps
+    /// the last `blinding_factors + 1` rows of every column for blinding, so `k` is the least
+    /// value with `2^k - (blinding_factors + 1) >= max(advice_rows, table_rows)`, where
+    /// `blinding_factors` is read from a `ConstraintSystem` configured with this config alone.
+    /// Optional columns and other chips may need a larger `k`; check a configured instance with
+    /// [`Self::check_table_rows`].
+    pub fn minimum_k(regex_def: &RegexDef<C>, max_chars_size: usize) -> u32 {
+        let mut meta = ConstraintSystem::<F>::default();
+        Self::configure(&mut meta, regex_def.clone(), max_chars_size);
+        let estimate = Self::estimate_cost(regex_def, max_chars_size);
+        let rows = estimate.advice_rows.max(estimate.table_rows) + meta.blinding_factors() + 1;
+        usize::BITS - (rows - 1).leading_zeros()
+    }
+
+    /// Sets the `k` the circuit is run with, so that [`Self::load`] checks the tables with
+    /// [`Self::check_table_rows`] first and fails with its [`RegexError::TableTooLargeForK`],
+    /// instead of on the first table row that does not fit.
+    pub fn set_k(&mut self, k: u32) {
+        self.k = Some(k);
+    }
+
+    /// Checks that the tables of this config, those of the optional columns included, fit the
+    /// usable rows of a circuit of size `2^k`, see [`Self::minimum_k`]. The blinding rows are
+    /// those of the constraint system as this config left it; columns configured later by other
+    /// chips may need more.
+    pub fn check_table_rows(&self, k: u32) -> Result<(), RegexError> {
+        let capacity = (1usize << k).saturating_sub(self.blinding_factors + 1);
+        let mut rows = Self::estimate_cost(&self.regex_def, self.max_chars_size).table_rows;
+        if let Some(length_range) = self.length_range.as_ref() {
+            rows = rows.max(length_range.max_length - length_range.min_length + 2);
+        }
+        if self.case_fold.is_some() {
+            rows = rows.max(256);
+        }
+        if self.match_count.is_some() {
+            let next_states: HashSet<u64> = self.regex_def.state_lookup.values().copied().collect();
+            rows = rows.max(next_states.len() + 1);
+        }
+        if self.transition_tags.is_some() {
+            rows = rows.max(self.regex_def.state_lookup.len() + 1);
+        }
+        if rows > capacity {
+            return Err(RegexError::TableTooLargeForK { rows, capacity });
+        }
+        Ok(())
+    }
+
+    /// Loads the tables. With the `k` of [`Self::set_k`], tables that do not fit fail with the
+    /// [`RegexError::TableTooLargeForK`] of [`Self::check_table_rows`], converted to an `Error`
+    /// it can be recovered from, see [`Self::load_checked`].
     pub fn load(&self, layouter: &mut impl Layouter<F>) -> Result<(), Error> {
-        self.transition_table
-            .load(layouter, &self.regex_def.state_lookup)?;
-        let mut accepted_state_vals = self.regex_def.accepted_state_vals.to_vec();
-        accepted_state_vals.push(0);
+        self.load_checked(layouter)?
+    }
+
+    /// Like [`Self::load`], but tables that do not fit the `k` of [`Self::set_k`] fail with the
+    /// [`RegexError::TableTooLargeForK`] of [`Self::check_table_rows`] before anything is
+    /// assigned. The inner result is that of loading.
+    pub fn load_checked(
+        &self,
+        layouter: &mut impl Layouter<F>,
+    ) -> Result<Result<(), Error>, RegexError> {
+        if let Some(k) = self.k {
+            self.check_table_rows(k)?;
+        }
+        Ok(self
+            .load_tables(
+                layouter,
+                &self.regex_def.state_lookup,
+                &self.regex_def.accepted_state_vals,
+            )
+            .and_then(|()| self.load_option_tables(layouter)))
+    }
+
+    /// Loads the tables shared by `configs` from [`Self::configure_all`] with the transitions and
+    /// accepted states of all of them, and the tables of each config's own options. Fails with
+    /// `Error::Synthesis` if the configs do not share their tables or their state values overlap,
+    /// so that one regex could reach the states of another.
+    pub fn load_all(layouter: &mut impl Layouter<F>, configs: &[Self]) -> Result<(), Error> {
+        let first = match configs.first() {
+            Some(first) => first,
+            None => return Ok(()),
+        };
+        let mut state_lookup = HashMap::new();
+        let mut accepted_state_vals = vec![];
+        let mut all_states = HashSet::new();
+        for config in configs.iter() {
+            let shares_tables = config.transition_table.enabled == first.transition_table.enabled
+                && config.accepted_flags == first.accepted_flags;
+            if !shares_tables {
+                return Err(Error::Synthesis);
+            }
+            let regex_def = &config.regex_def;
+            let states: HashSet<u64> = regex_def
+                .state_lookup
+                .iter()
+                .flat_map(|((_, prev_state), next_state)| [*prev_state, *next_state])
+                .chain([regex_def.first_state_val])
+                .chain(regex_def.accepted_state_vals.iter().copied())
+                .collect();
+            if !all_states.is_disjoint(&states) {
+                return Err(Error::Synthesis);
+            }
+            all_states.extend(states);
+            state_lookup.extend(regex_def.state_lookup.iter().map(|(key, val)| (*key, *val)));
+            accepted_state_vals.extend(regex_def.accepted_state_vals.iter().copied());
+        }
+        first.load_tables(layouter, &state_lookup, &accepted_state_vals)?;
+        for config in configs.iter() {
+            config.load_option_tables(layouter)?;
+        }
+        Ok(())
+    }
+
+    fn load_tables(
+        &self,
+        layouter: &mut impl Layouter<F>,
+        state_lookup: &HashMap<(C, u64), u64>,
+        accepted_state_vals: &[u64],
+    ) -> Result<(), Error> {
+        self.transition_table.load(layouter, state_lookup)?;
+        // Accepted states are flagged with 1; the dummy row [0, 0] serves the rows without an
+        // accept check, even if 0 is an accepted state.
+        let mut accepted_rows: Vec<(u64, u64)> = accepted_state_vals
+            .iter()
+            .map(|state| (1, *state))
+            .collect();
+        accepted_rows.push((0, 0));
         layouter.assign_table(
-            || "accepted_states",
+            || self.table_name("accepted_states"),
             |mut table| {
-                for (idx, state) in accepted_state_vals.iter().enumerate() {
+                for (idx, (flag, state)) in accepted_rows.iter().enumerate() {
+                    table.assign_cell(
+                        || format!("accepted flag at {}", idx),
+                        self.accepted_flags,
+                        idx,
+                        || Value::known(F::from(*flag)),
+                    )?;
                     table.assign_cell(
                         || format!("accepted state at {}", idx),
                         self.accepted_states,
@@ -166,146 +944,967 @@ impl<F: PrimeField> RegexCheckConfig<F> {
                 }
                 Ok(())
             },
-        )?;
-        Ok(())
+        )
     }
 
-    // Note that the two types of region.assign_advice calls happen together so that it is the same region
-    pub fn assign_values(
-        &self,
-        region: &mut Region<F>,
-        characters: &[u8],
-    ) -> Result<AssignedRegexResult<F>, Error> {
-        let mut assigned_enables = Vec::new();
-        let mut assigned_characters = Vec::new();
-        let mut assigned_states = Vec::new();
-        let states = self.derive_states(characters);
-
-        self.q_first.enable(region, 0)?;
-        for idx in 1..self.max_chars_size {
-            self.not_q_first.enable(region, idx)?;
-        }
-
-        for (idx, (char, state)) in characters
-            .iter()
-            .zip(states[0..characters.len()].iter())
-            .enumerate()
-        {
-            let assigned_enable = region.assign_advice(
-                || format!("char_enable at {}", idx),
-                self.char_enable,
-                idx,
-                || Value::known(F::from(1)),
-            )?;
-            assigned_enables.push(assigned_enable);
-            let assigned_c = region.assign_advice(
-                || format!("character at {}", idx),
-                self.characters,
-                idx,
-                || Value::known(F::from(*char as u64)),
-            )?;
-            assigned_characters.push(assigned_c);
-            let assigned_s = region.assign_advice(
-                || format!("state at {}", idx),
-                self.state,
-                idx,
-                || Value::known(F::from(*state)),
+    // The tables of `configure_length_range`, `configure_case_fold`, `configure_match_count` and
+    // `configure_transition_tags`.
+    fn load_option_tables(&self, layouter: &mut impl Layouter<F>) -> Result<(), Error> {
+        if let Some(length_range) = self.length_range.as_ref() {
+            // Allowed lengths are flagged with 1, next to the dummy row [0, 0].
+            let length_rows = (length_range.min_length..=length_range.max_length)
+                .map(|length| (1, length as u64))
+                .chain([(0, 0)]);
+            layouter.assign_table(
+                || self.table_name("lengths"),
+                |mut table| {
+                    for (idx, (flag, length)) in length_rows.clone().enumerate() {
+                        table.assign_cell(
+                            || format!("length flag at {}", idx),
+                            length_range.length_flags,
+                            idx,
+                            || Value::known(F::from(flag)),
+                        )?;
+                        table.assign_cell(
+                            || format!("length at {}", idx),
+                            length_range.lengths,
+                            idx,
+                            || Value::known(F::from(length)),
+                        )?;
+                    }
+                    Ok(())
+                },
             )?;
-            assigned_states.push(assigned_s);
         }
-        for idx in characters.len()..self.max_chars_size {
-            let assigned_enable = region.assign_advice(
-                || format!("char_enable at {}", idx),
-                self.char_enable,
-                idx,
-                || Value::known(F::from(0)),
+        if let Some(case_fold) = self.case_fold.as_ref() {
+            layouter.assign_table(
+                || self.table_name("case fold"),
+                |mut table| {
+                    for byte in 0..=u8::MAX {
+                        table.assign_cell(
+                            || format!("raw byte {}", byte),
+                            case_fold.raw_bytes,
+                            byte as usize,
+                            || Value::known(F::from(byte as u64)),
+                        )?;
+                        table.assign_cell(
+                            || format!("folded byte {}", byte),
+                            case_fold.folded_bytes,
+                            byte as usize,
+                            || Value::known(F::from(byte.to_ascii_lowercase() as u64)),
+                        )?;
+                    }
+                    Ok(())
+                },
             )?;
-            assigned_enables.push(assigned_enable);
-            let assigned_c = region.assign_advice(
-                || format!("character at {}", idx),
-                self.characters,
-                idx,
-                || Value::known(F::from(0)),
+        }
+        if let Some(match_count) = self.match_count.as_ref() {
+            let mut state_vals: Vec<u64> = self
+                .regex_def
+                .state_lookup
+                .values()
+                .copied()
+                .collect::<HashSet<u64>>()
+                .into_iter()
+                .collect();
+            state_vals.sort_unstable();
+            let hit_rows = state_vals
+                .iter()
+                .map(|state| {
+                    let hit = match_count.hit_state_vals.contains(state);
+                    (1, *state, hit as u64)
+                })
+                .chain([(0, 0, 0)]);
+            layouter.assign_table(
+                || self.table_name("hit states"),
+                |mut table| {
+                    for (idx, (flag, state, hit)) in hit_rows.clone().enumerate() {
+                        table.assign_cell(
+                            || format!("hit enabled at {}", idx),
+                            match_count.enabled,
+                            idx,
+                            || Value::known(F::from(flag)),
+                        )?;
+                        table.assign_cell(
+                            || format!("hit state at {}", idx),
+                            match_count.states,
+                            idx,
+                            || Value::known(F::from(state)),
+                        )?;
+                        table.assign_cell(
+                            || format!("hit flag at {}", idx),
+                            match_count.hit_flags,
+                            idx,
+                            || Value::known(F::from(hit)),
+                        )?;
+                    }
+                    Ok(())
+                },
             )?;
-            assigned_characters.push(assigned_c);
         }
-        for idx in characters.len()..self.max_chars_size + 1 {
-            let state_val = if idx == characters.len() {
-                states[idx]
-            } else {
-                0
-            };
-            let assigned_s = region.assign_advice(
-                || format!("state at {}", idx),
-                self.state,
-                idx,
-                || Value::known(F::from(state_val)),
+        if let Some(transition_tags) = self.transition_tags.as_ref() {
+            let mut tag_rows: Vec<(u64, u64, u64, u64)> = self
+                .regex_def
+                .state_lookup
+                .keys()
+                .map(|(char, prev_state)| {
+                    let bits = transition_tags
+                        .tags
+                        .get(&(*char, *prev_state))
+                        .copied()
+                        .unwrap_or(0);
+                    (1, char.to_u64(), *prev_state, bits)
+                })
+                .collect();
+            tag_rows.sort_unstable();
+            tag_rows.push((0, 0, 0, 0));
+            layouter.assign_table(
+                || self.table_name("transition tags"),
+                |mut table| {
+                    for (idx, (flag, char, prev_state, bits)) in tag_rows.iter().enumerate() {
+                        table.assign_cell(
+                            || format!("tag enabled at {}", idx),
+                            transition_tags.enabled,
+                            idx,
+                            || Value::known(F::from(*flag)),
+                        )?;
+                        table.assign_cell(
+                            || format!("tag character at {}", idx),
+                            transition_tags.characters,
+                            idx,
+                            || Value::known(F::from(*char)),
+                        )?;
+                        table.assign_cell(
+                            || format!("tag prev state at {}", idx),
+                            transition_tags.prev_states,
+                            idx,
+                            || Value::known(F::from(*prev_state)),
+                        )?;
+                        table.assign_cell(
+                            || format!("tag bits at {}", idx),
+                            transition_tags.tag_bits,
+                            idx,
+                            || Value::known(F::from(*bits)),
+                        )?;
+                    }
+                    Ok(())
+                },
             )?;
-            assigned_states.push(assigned_s);
         }
-        debug_assert_eq!(assigned_enables.len(), assigned_characters.len());
-        debug_assert_eq!(assigned_characters.len() + 1, assigned_states.len());
-        Ok(AssignedRegexResult {
-            enable_flags: assigned_enables,
-            characters: assigned_characters,
-            states: assigned_states,
-        })
+        Ok(())
     }
 
-    pub(crate) fn derive_states(&self, characters: &[u8]) -> Vec<u64> {
-        let mut states = vec![self.regex_def.first_state_val];
-        for (idx, char) in characters.into_iter().enumerate() {
-            let state = states[idx];
-            let next_state = self.regex_def.state_lookup.get(&(*char, state));
-            // println!("idx {} char {}, state {} is_none {}",idx,char,state,next_state.is_none());
-            match next_state {
-                Some(s) => states.push(*s),
-                None => states.push(self.regex_def.first_state_val),
-            };
+    /// A CRC-32 of everything that shapes the configured circuit: the table, `max_chars_size` and
+    /// the optional columns with their parameters.
+    pub fn config_fingerprint(&self) -> u32 {
+        let mut bytes = self.regex_def.fingerprint().to_le_bytes().to_vec();
+        bytes.extend((self.max_chars_size as u64).to_le_bytes());
+        if let Some(length_range) = self.length_range.as_ref() {
+            bytes.extend((length_range.min_length as u64).to_le_bytes());
+            bytes.extend((length_range.max_length as u64).to_le_bytes());
         }
-
-        // // Set the states to transition via the character and state that appear in the array, to the third value in each array tuple
-        // for idx in 0..self.characters.len() {
-        //     let character = self.characters[idx];
-        //     // states[i] = next_state;
-        //     let state = states[idx];
-        //     // next_state = START_STATE; // Default to start state if no match found
-        //     let mut is_found = false;
-        //     for j in 0..array.len() {
-        //         if array[j][2] == character as u64 && array[j][0] == state {
-        //             // next_state = array[j][1] as u64;
-        //             // println!(
-        //             //     "char {} cur_state {} next_state {}",
-        //             //     character as char, state, array[j][1]
-        //             // );
-        //             states.push(array[j][1]);
-        //             is_found = true;
-        //             break;
-        //         }
-        //     }
-        //     if !is_found {
-        //         // println!("not found {} {}", character as char, state);
-        //         states.push(Self::Config::STATE_FIRST);
-        //     }
-        // }
-        assert_eq!(states.len(), characters.len() + 1);
-        states
+        if self.state_delta.is_some() {
+            bytes.push(b'd');
+        }
+        if let Some((_, base_offset)) = self.offset {
+            bytes.push(b'o');
+            bytes.extend(base_offset.to_le_bytes());
+        }
+        if self.case_fold.is_some() {
+            bytes.push(b'f');
+        }
+        if let Some(match_count) = self.match_count.as_ref() {
+            bytes.push(b'c');
+            let mut hit_state_vals = match_count.hit_state_vals.clone();
+            hit_state_vals.sort_unstable();
+            for state in hit_state_vals {
+                bytes.extend(state.to_le_bytes());
+            }
+        }
+        if let Some(transition_tags) = self.transition_tags.as_ref() {
+            bytes.push(b't');
+            bytes.extend((transition_tags.flags.len() as u64).to_le_bytes());
+            let mut tags: Vec<(u64, u64, u64)> = transition_tags
+                .tags
+                .iter()
+                .map(|((char, prev_state), bits)| (char.to_u64(), *prev_state, *bits))
+                .collect();
+            tags.sort_unstable();
+            for (char, prev_state, bits) in tags {
+                bytes.extend(char.to_le_bytes());
+                bytes.extend(prev_state.to_le_bytes());
+                bytes.extend(bits.to_le_bytes());
+            }
+        }
+        if self.q_first.is_fixed() {
+            bytes.push(b's');
+        }
+        table_checksum(&bytes)
     }
-}
-
-#[cfg(test)]
-mod tests {
-    use halo2_base::halo2_proofs::{
-        circuit::floor_planner::V1,
-        dev::{CircuitCost, FailureLocation, MockProver, VerifyFailure},
-        halo2curves::bn256::{Fr, G1},
-        plonk::{Any, Circuit},
-    };
+
+    /// Whether `other` has the same circuit shape as this config: the same columns, selectors and
+    /// lookup tables at the same indices, the same optional columns and the same constants in
+    /// the gates (the first state and the base offset), for the same `max_chars_size`. Two
+    /// circuits that differ only in such configs have identical constraint systems and the same
+    /// copy constraints, so their verifying keys differ only in the commitments of the fixed
+    /// columns holding the tables; a backend that can swap those may reuse the rest of the key.
+    ///
+    /// The table contents are not compared: the regex, the range of
+    /// [`Self::configure_length_range`], the hit states of [`Self::configure_match_count`] and the
+    /// tags of [`Self::configure_transition_tags`] are all table values, and the tag only names
+    /// the gates. Both tables must still fit the same `k`, see [`Self::minimum_k`].
+    pub fn is_shape_compatible(&self, other: &Self) -> bool {
+        let length_columns = |config: &Self| {
+            config.length_range.as_ref().map(|length_range| {
+                (
+                    length_range.count,
+                    length_range.length_flags,
+                    length_range.lengths,
+                )
+            })
+        };
+        let case_fold_columns = |config: &Self| {
+            config.case_fold.as_ref().map(|case_fold| {
+                (
+                    case_fold.raw_characters,
+                    case_fold.raw_bytes,
+                    case_fold.folded_bytes,
+                )
+            })
+        };
+        let match_count_columns = |config: &Self| {
+            config.match_count.as_ref().map(|match_count| {
+                (
+                    match_count.hits,
+                    match_count.count,
+                    match_count.enabled,
+                    match_count.states,
+                    match_count.hit_flags,
+                )
+            })
+        };
+        let tag_columns = |config: &Self| {
+            config.transition_tags.as_ref().map(|transition_tags| {
+                (
+                    transition_tags.flags.clone(),
+                    transition_tags.enabled,
+                    transition_tags.characters,
+                    transition_tags.prev_states,
+                    transition_tags.tag_bits,
+                )
+            })
+        };
+        let table = |config: &Self| {
+            (
+                config.transition_table.enabled,
+                config.transition_table.prev_state,
+                config.transition_table.next_state,
+                config.transition_table.character,
+                config.accepted_flags,
+                config.accepted_states,
+            )
+        };
+        [
+            self.characters,
+            self.state,
+            self.char_enable,
+            self.is_padding,
+            self.accepted_state,
+        ] == [
+            other.characters,
+            other.state,
+            other.char_enable,
+            other.is_padding,
+            other.accepted_state,
+        ] && (self.q_first, self.not_q_first, self.q_last)
+            == (other.q_first, other.not_q_first, other.q_last)
+            && table(self) == table(other)
+            && self.regex_def.first_state_val == other.regex_def.first_state_val
+            && self.max_chars_size == other.max_chars_size
+            && length_columns(self) == length_columns(other)
+            && self.state_delta == other.state_delta
+            && self.offset == other.offset
+            && case_fold_columns(self) == case_fold_columns(other)
+            && match_count_columns(self) == match_count_columns(other)
+            && tag_columns(self) == tag_columns(other)
+    }
+
+    // Note that the two types of region.assign_advice calls happen together so that it is the same region
+    pub fn assign_values(
+        &self,
+        region: &mut Region<F>,
+        characters: &[C],
+    ) -> Result<AssignedRegexResult<F>, Error> {
+        self.assign_values_at(region, 0, characters)
+    }
+
+    /// Checks each of `inputs` in its own block of `max_chars_size + 1` rows of `region`, all
+    /// against the shared tables. Every input must be accepted, like with [`Self::assign_values`];
+    /// the accepting state of each is in its result, e.g. for [`Self::expose_accepted_states`].
+    pub fn assign_values_batch(
+        &self,
+        region: &mut Region<F>,
+        inputs: &[&[C]],
+    ) -> Result<Vec<AssignedRegexResult<F>>, Error> {
+        inputs
+            .iter()
+            .enumerate()
+            .map(|(idx, characters)| {
+                self.assign_values_at(region, idx * (self.max_chars_size + 1), characters)
+            })
+            .collect()
+    }
+
+    /// Exposes the accepting state of each result at the rows of `instance` in order.
+    pub fn expose_accepted_states(
+        &self,
+        layouter: &mut impl Layouter<F>,
+        results: &[AssignedRegexResult<F>],
+        instance: Column<Instance>,
+    ) -> Result<(), Error> {
+        for (row, result) in results.iter().enumerate() {
+            layouter.constrain_instance(result.accepted_state.cell(), instance, row)?;
+        }
+        Ok(())
+    }
+
+    /// Like [`Self::assign_values`], but streams the input instead of taking a slice: `rows`
+    /// yields each of the `len` characters with the state before it, e.g. from
+    /// [`RegexDef::gen_states`], and is assigned row by row. The state after the last character
+    /// is looked up in the table. Fails with `Error::Synthesis` if `rows` does not yield exactly
+    /// `len` items or `len` exceeds `max_chars_size`.
+    pub fn assign_values_iter(
+        &self,
+        region: &mut Region<F>,
+        rows: impl IntoIterator<Item = (C, u64)>,
+        len: usize,
+    ) -> Result<AssignedRegexResult<F>, Error> {
+        let rows = rows.into_iter().map(|(char, state)| (char, F::from(state)));
+        self.assign_char_rows_at(region, 0, rows, len, None)
+    }
+
+    /// Like [`Self::assign_values_iter`], but takes the input in consecutive chunks, e.g. as it
+    /// arrives from a decoder, and derives the states: the chunks are one check of `len`
+    /// characters, where each chunk continues from the state the previous one exited in, and the
+    /// accept check applies once, to the whole input after the last chunk. The state an
+    /// intermediate chunk exits in is only constrained by the transitions, so it may be any
+    /// state, accepting or not; a chunk boundary is no match boundary. Fails with
+    /// `Error::Synthesis` if the chunks do not hold exactly `len` characters.
+    pub fn assign_chunks<'a>(
+        &self,
+        region: &mut Region<F>,
+        chunks: impl IntoIterator<Item = &'a [C]>,
+        len: usize,
+    ) -> Result<AssignedRegexResult<F>, Error>
+    where
+        C: 'a,
+    {
+        let first_state_val = self.regex_def.first_state_val;
+        // After a character without a transition the states restart, like in `derive_states`,
+        // so that the lookup fails at that row.
+        let mut state = Some(first_state_val);
+        let rows = chunks.into_iter().flatten().map(|raw_char| {
+            let char = if self.case_fold.is_some() {
+                raw_char.fold_ascii_case()
+            } else {
+                *raw_char
+            };
+            let prev_state = state.unwrap_or(first_state_val);
+            state = state.and_then(|state| self.regex_def.next_state(state, char));
+            (*raw_char, prev_state)
+        });
+        self.assign_values_iter(region, rows, len)
+    }
+
+    /// Like [`Self::assign_values`], but with the `characters.len() + 1` witness states given as
+    /// `u128`, e.g. for packed encodings whose values exceed `u64`, assigned with
+    /// `F::from_u128`. Every `u128` is below the field modulus, so no value wraps around; the
+    /// states are range checked by the transition lookup, which only holds states of the table,
+    /// so wider states require a table loaded with them. Fails with `Error::Synthesis` if the
+    /// number of states does not fit.
+    pub fn assign_values_with_states(
+        &self,
+        region: &mut Region<F>,
+        characters: &[C],
+        states: &[u128],
+    ) -> Result<AssignedRegexResult<F>, Error> {
+        if states.len() != characters.len() + 1 {
+            return Err(Error::Synthesis);
+        }
+        let states: Vec<F> = states.iter().map(|state| F::from_u128(*state)).collect();
+        self.assign_char_rows_at(
+            region,
+            0,
+            characters.iter().copied().zip(states.iter().copied()),
+            characters.len(),
+            Some(states[characters.len()]),
+        )
+    }
+
+    /// [`Self::assign_values_with_states`] for `u64` states, e.g. from [`RegexDef::gen_states`].
+    pub fn assign_values_with_states_u64(
+        &self,
+        region: &mut Region<F>,
+        characters: &[C],
+        states: &[u64],
+    ) -> Result<AssignedRegexResult<F>, Error> {
+        let states: Vec<u128> = states.iter().map(|state| *state as u128).collect();
+        self.assign_values_with_states(region, characters, &states)
+    }
+
+    /// Like [`Self::assign_values_with_states`], but with the characters and the
+    /// `characters.len() + 1` states already converted into field elements, e.g. once for an
+    /// input that is proven repeatedly, so that they are assigned as they are. Fails with
+    /// `Error::Synthesis` if the number of states does not fit, or if the config derives further
+    /// columns from the characters, i.e. with [`Self::configure_case_fold`],
+    /// [`Self::configure_match_count`] or [`Self::configure_transition_tags`].
+    pub fn assign_values_fe(
+        &self,
+        region: &mut Region<F>,
+        characters: &[Value<F>],
+        states: &[Value<F>],
+    ) -> Result<AssignedRegexResult<F>, Error> {
+        if states.len() != characters.len() + 1
+            || self.case_fold.is_some()
+            || self.match_count.is_some()
+            || self.transition_tags.is_some()
+        {
+            return Err(Error::Synthesis);
+        }
+        let rows = characters
+            .iter()
+            .zip(states.windows(2))
+            .map(|(char, states)| RowWitness {
+                raw_char: *char,
+                char: *char,
+                state: states[0],
+                next_state: states[1],
+                tag_bits: 0,
+            });
+        self.assign_rows_at(region, 0, rows, characters.len())
+    }
+
+    /// Like [`Self::assign_values`], but starts at row `offset` of `region`, e.g. the
+    /// [`AssignedRegexResult::next_offset`] of a previous check, so that several checks of this
+    /// config can follow each other in one region.
+    pub fn assign_values_at(
+        &self,
+        region: &mut Region<F>,
+        offset: usize,
+        characters: &[C],
+    ) -> Result<AssignedRegexResult<F>, Error> {
+        let folded;
+        let folded_characters = if self.case_fold.is_some() {
+            folded = characters
+                .iter()
+                .map(|char| char.fold_ascii_case())
+                .collect::<Vec<C>>();
+            &folded[..]
+        } else {
+            characters
+        };
+        let states: Vec<F> = self
+            .derive_states(folded_characters)
+            .into_iter()
+            .map(F::from)
+            .collect();
+        self.assign_char_rows_at(
+            region,
+            offset,
+            characters.iter().copied().zip(states.iter().copied()),
+            characters.len(),
+            Some(states[characters.len()]),
+        )
+    }
+
+    /// Matches the regex against `buffer` from byte `start` on, so the match is anchored at that
+    /// position: the first `start` bytes go to rows `0..start` without any selector, so they are
+    /// not constrained, and `buffer[start..]` is checked from row `start` like with
+    /// [`Self::assign_values_at`], with the accept check at its end. Since the selectors are
+    /// fixed in the verifying key, so is `start`. Returns the cells of the skipped bytes, in the
+    /// column holding the input as given (the raw characters with
+    /// [`Self::configure_case_fold`]), e.g. to bind the whole buffer to a hash.
+    pub fn assign_values_from(
+        &self,
+        region: &mut Region<F>,
+        buffer: &[C],
+        start: usize,
+    ) -> Result<(Vec<AssignedCell<F, F>>, AssignedRegexResult<F>), Error> {
+        if start > buffer.len() {
+            return Err(Error::Synthesis);
+        }
+        let column = match self.case_fold.as_ref() {
+            Some(case_fold) => case_fold.raw_characters,
+            None => self.characters,
+        };
+        let skipped = buffer[..start]
+            .iter()
+            .enumerate()
+            .map(|(idx, char)| {
+                region.assign_advice(
+                    || format!("skipped character at {}", idx),
+                    column,
+                    idx,
+                    || Value::known(F::from(char.to_u64())),
+                )
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        let result = self.assign_values_at(region, start, &buffer[start..])?;
+        Ok((skipped, result))
+    }
+
+    // Assigns the rows `offset..=offset + max_chars_size` in one pass over the `(raw character,
+    // state)` of each of the `len` characters. `final_state` is the state after the last
+    // character; if it is `None`, it is looked up in the table, falling back to the first state
+    // like `RegexDef::witness_states` does.
+    fn assign_char_rows_at(
+        &self,
+        region: &mut Region<F>,
+        offset: usize,
+        rows: impl Iterator<Item = (C, F)>,
+        len: usize,
+        final_state: Option<F>,
+    ) -> Result<AssignedRegexResult<F>, Error> {
+        let mut rows = rows.peekable();
+        let mut idx = 0;
+        let rows = std::iter::from_fn(|| {
+            let (raw_char, state) = rows.next()?;
+            let char = if self.case_fold.is_some() {
+                raw_char.fold_ascii_case()
+            } else {
+                raw_char
+            };
+            let next_state = match rows.peek() {
+                Some((_, next_state)) if idx + 1 < len => *next_state,
+                _ => final_state.unwrap_or_else(|| {
+                    let next_state = u64::try_from(fe_to_biguint(&state))
+                        .ok()
+                        .and_then(|state| self.regex_def.next_state(state, char));
+                    F::from(next_state.unwrap_or(self.regex_def.first_state_val))
+                }),
+            };
+            idx += 1;
+            let tag_bits = self
+                .transition_tags
+                .as_ref()
+                .and_then(|config| {
+                    let state = u64::try_from(fe_to_biguint(&state)).ok()?;
+                    config.tags.get(&(char, state)).copied()
+                })
+                .unwrap_or(0);
+            Some(RowWitness {
+                raw_char: Value::known(F::from(raw_char.to_u64())),
+                char: Value::known(F::from(char.to_u64())),
+                state: Value::known(state),
+                next_state: Value::known(next_state),
+                tag_bits,
+            })
+        });
+        self.assign_rows_at(region, offset, rows, len)
+    }
+
+    // Assigns the rows `offset..=offset + max_chars_size` in one pass over the witnesses of the
+    // `len` characters, followed by padding. Every entry point assigns its rows here, so that the
+    // selectors and the derived columns follow the gates in one place.
+    fn assign_rows_at(
+        &self,
+        region: &mut Region<F>,
+        offset: usize,
+        mut rows: impl Iterator<Item = RowWitness<F>>,
+        len: usize,
+    ) -> Result<AssignedRegexResult<F>, Error> {
+        if len > self.max_chars_size {
+            return Err(Error::Synthesis);
+        }
+        let mut assigned_enables = Vec::new();
+        let mut assigned_paddings = Vec::new();
+        let mut assigned_characters = Vec::new();
+        let mut assigned_states = Vec::new();
+        let mut assigned_deltas = Vec::new();
+        let mut assigned_offsets = Vec::new();
+        let mut assigned_raw_characters = Vec::new();
+        let mut assigned_accepted_state = None;
+        let mut assigned_match_count = None;
+        let mut match_count = Value::known(F::zero());
+        let mut assigned_tags = vec![
+            vec![];
+            self.transition_tags
+                .as_ref()
+                .map_or(0, |config| config.flags.len())
+        ];
+        let zero = Value::known(F::zero());
+        // The state after the input, which the row after the last character holds.
+        let mut end_state = Value::known(F::from(self.regex_def.first_state_val));
+
+        self.q_first.enable(region, offset)?;
+        self.q_last.enable(region, offset + self.max_chars_size)?;
+        // The row after the last character is also checked, so that the accept check fires even
+        // when the input fills all rows.
+        for idx in 0..=self.max_chars_size {
+            if idx > 0 {
+                self.not_q_first.enable(region, offset + idx)?;
+            }
+            let enabled = idx < len;
+            let row = if enabled {
+                let row = rows.next().ok_or(Error::Synthesis)?;
+                end_state = row.next_state;
+                row
+            } else {
+                RowWitness {
+                    raw_char: zero,
+                    char: zero,
+                    state: if idx == len { end_state } else { zero },
+                    next_state: zero,
+                    tag_bits: 0,
+                }
+            };
+
+            let assigned_enable = region.assign_advice(
+                || format!("char_enable at {}", idx),
+                self.char_enable,
+                offset + idx,
+                || Value::known(F::from(enabled as u64)),
+            )?;
+            let assigned_c = region.assign_advice(
+                || format!("character at {}", idx),
+                self.characters,
+                offset + idx,
+                || row.char,
+            )?;
+            let assigned_s = region.assign_advice(
+                || format!("state at {}", idx),
+                self.state,
+                offset + idx,
+                || row.state,
+            )?;
+            assigned_states.push(assigned_s);
+            let assigned_padding = region.assign_advice(
+                || format!("is_padding at {}", idx),
+                self.is_padding,
+                offset + idx,
+                || Value::known(F::from(!enabled as u64)),
+            )?;
+            if let Some(length_range) = self.length_range.as_ref() {
+                region.assign_advice(
+                    || format!("count at {}", idx),
+                    length_range.count,
+                    offset + idx,
+                    || Value::known(F::from((idx + 1).min(len) as u64)),
+                )?;
+            }
+            if let Some(state_delta) = self.state_delta {
+                if idx < self.max_chars_size {
+                    let delta = if enabled {
+                        row.next_state - row.state
+                    } else {
+                        zero
+                    };
+                    assigned_deltas.push(region.assign_advice(
+                        || format!("state_delta at {}", idx),
+                        state_delta,
+                        offset + idx,
+                        || delta,
+                    )?);
+                }
+            }
+            let assigned_offset = match self.offset {
+                Some((offset_column, base_offset)) => Some(region.assign_advice(
+                    || format!("offset at {}", idx),
+                    offset_column,
+                    offset + idx,
+                    || Value::known(F::from(base_offset + idx as u64)),
+                )?),
+                None => None,
+            };
+            let assigned_raw = match self.case_fold.as_ref() {
+                Some(case_fold) => Some(region.assign_advice(
+                    || format!("raw character at {}", idx),
+                    case_fold.raw_characters,
+                    offset + idx,
+                    || row.raw_char,
+                )?),
+                None => None,
+            };
+            if let Some(config) = self.match_count.as_ref() {
+                let hit = if enabled {
+                    row.next_state.map(|next_state| {
+                        let hit = config
+                            .hit_state_vals
+                            .iter()
+                            .any(|state| F::from(*state) == next_state);
+                        F::from(hit as u64)
+                    })
+                } else {
+                    zero
+                };
+                match_count = match_count + hit;
+                region.assign_advice(
+                    || format!("hit at {}", idx),
+                    config.hits,
+                    offset + idx,
+                    || hit,
+                )?;
+                assigned_match_count = Some(region.assign_advice(
+                    || format!("match count at {}", idx),
+                    config.count,
+                    offset + idx,
+                    || match_count,
+                )?);
+            }
+            if let Some(config) = self.transition_tags.as_ref() {
+                for (tag, flag) in config.flags.iter().enumerate() {
+                    let assigned_flag = region.assign_advice(
+                        || format!("tag {} at {}", tag, idx),
+                        *flag,
+                        offset + idx,
+                        || Value::known(F::from((row.tag_bits >> tag) & 1)),
+                    )?;
+                    if idx < self.max_chars_size {
+                        assigned_tags[tag].push(assigned_flag);
+                    }
+                }
+            }
+            let acc = if idx >= len { end_state } else { zero };
+            assigned_accepted_state = Some(region.assign_advice(
+                || format!("accepted_state at {}", idx),
+                self.accepted_state,
+                offset + idx,
+                || acc,
+            )?);
+            if idx < self.max_chars_size {
+                assigned_enables.push(assigned_enable);
+                assigned_characters.push(assigned_c);
+                assigned_paddings.push(assigned_padding);
+                assigned_offsets.extend(assigned_offset);
+                assigned_raw_characters.extend(assigned_raw);
+            }
+        }
+        if rows.next().is_some() {
+            return Err(Error::Synthesis);
+        }
+        debug_assert_eq!(assigned_enables.len(), assigned_characters.len());
+        debug_assert_eq!(assigned_characters.len() + 1, assigned_states.len());
+        Ok(AssignedRegexResult {
+            enable_flags: assigned_enables,
+            is_padding: assigned_paddings,
+            state_deltas: assigned_deltas,
+            offsets: assigned_offsets,
+            raw_characters: assigned_raw_characters,
+            characters: assigned_characters,
+            states: assigned_states,
+            accepted_state: assigned_accepted_state.unwrap(),
+            match_count: assigned_match_count,
+            transition_tags: assigned_tags,
+            next_offset: offset + self.max_chars_size + 1,
+        })
+    }
+
+    /// Exposes the state the input was entered in and the accepting state it exited in at rows
+    /// `offset` and `offset + 1` of `instance`, for checking the independent stages of a
+    /// [`crate::RegexPipeline`] with [`crate::RegexPipeline::check_links`]. An empty input exits
+    /// in the start state, if it is accepting.
+    pub fn expose_states(
+        &self,
+        layouter: &mut impl Layouter<F>,
+        result: &AssignedRegexResult<F>,
+        instance: Column<Instance>,
+        offset: usize,
+    ) -> Result<(), Error> {
+        layouter.constrain_instance(result.states[0].cell(), instance, offset)?;
+        layouter.constrain_instance(result.accepted_state.cell(), instance, offset + 1)
+    }
+
+    /// Constrains the inputs of `a` and `b` to be equal with the permutation argument: their
+    /// characters (the raw characters, if both were assigned with
+    /// [`Self::configure_case_fold`]) and padding flags are tied row by row. Padding characters
+    /// are 0, so tying the padding rows only requires both inputs to end at the same row. With
+    /// `with_states`, the states are tied too. Fails with `Error::Synthesis` if the results have
+    /// different numbers of rows.
+    pub fn constrain_results_equal(
+        &self,
+        region: &mut Region<F>,
+        a: &AssignedRegexResult<F>,
+        b: &AssignedRegexResult<F>,
+        with_states: bool,
+    ) -> Result<(), Error> {
+        if a.characters.len() != b.characters.len() {
+            return Err(Error::Synthesis);
+        }
+        let (a_chars, b_chars) = if a.raw_characters.is_empty() || b.raw_characters.is_empty() {
+            (&a.characters, &b.characters)
+        } else {
+            (&a.raw_characters, &b.raw_characters)
+        };
+        let mut pairs = a_chars
+            .iter()
+            .zip(b_chars.iter())
+            .chain(a.is_padding.iter().zip(b.is_padding.iter()))
+            .collect::<Vec<_>>();
+        if with_states {
+            pairs.extend(a.states.iter().zip(b.states.iter()));
+        }
+        for (a_cell, b_cell) in pairs {
+            region.constrain_equal(a_cell.cell(), b_cell.cell())?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn derive_states(&self, characters: &[C]) -> Vec<u64> {
+        self.regex_def.witness_states(characters)
+    }
+}
+
+impl<F: PrimeField> RegexCheckConfig<F> {
+    /// Matches the input case-insensitively without growing the DFA: the input goes into a raw
+    /// characters column, and the characters column the transitions are looked up on holds its
+    /// ASCII lowercase, tied to the raw byte by a 256-row fold table. The regex must be written in
+    /// lowercase, since transitions on uppercase letters are never taken. The fold table needs
+    /// `k >= 9`.
+    pub fn configure_case_fold(&mut self, meta: &mut ConstraintSystem<F>) {
+        let raw_characters = meta.advice_column();
+        meta.enable_equality(raw_characters);
+        let raw_bytes = meta.lookup_table_column();
+        let folded_bytes = meta.lookup_table_column();
+        let (characters, q_first, not_q_first) = (self.characters, self.q_first, self.not_q_first);
+        meta.lookup(
+            tagged(
+                self.tag.as_deref(),
+                "The folded character of each raw character",
+            ),
+            |meta| {
+                let q_any = q_first.query(meta) + not_q_first.query(meta);
+                let raw_char = meta.query_advice(raw_characters, Rotation::cur());
+                let folded_char = meta.query_advice(characters, Rotation::cur());
+                vec![
+                    (q_any.clone() * raw_char, raw_bytes),
+                    (q_any * folded_char, folded_bytes),
+                ]
+            },
+        );
+        self.case_fold = Some(CaseFoldConfig {
+            raw_characters,
+            raw_bytes,
+            folded_bytes,
+        });
+        self.blinding_factors = meta.blinding_factors();
+    }
+
+    /// Like [`Self::load`], but first checks that `bytes` (a table in the text format) have the
+    /// CRC-32 `expected_checksum` and encode the configured table.
+    pub fn load_from_bytes(
+        &self,
+        layouter: &mut impl Layouter<F>,
+        bytes: &[u8],
+        expected_checksum: u32,
+    ) -> Result<(), Error> {
+        let regex_def =
+            RegexDef::from_text_bytes(bytes, expected_checksum).map_err(|_| Error::Synthesis)?;
+        if regex_def.fingerprint() != self.regex_def.fingerprint() {
+            return Err(Error::Synthesis);
+        }
+        self.load(layouter)
+    }
+
+    /// Decodes `encoded` with `decoder` in the same region and checks the decoded bytes. Every
+    /// character (the raw one, if configured with [`Self::configure_case_fold`]) and enable flag
+    /// is constrained to equal the decoded byte and flag at its position, padding included, so
+    /// the input is exactly the decoding. Returns the decoding with the result, e.g. to bind
+    /// [`DecodedInput::encoded`] to the encoded input. Fails with `Error::Synthesis` unless
+    /// the decoder outputs `max_chars_size` positions.
+    pub fn assign_values_decoded(
+        &self,
+        region: &mut Region<F>,
+        encoded: &[u8],
+        decoder: &impl InputDecoder<F>,
+    ) -> Result<(DecodedInput<F>, AssignedRegexResult<F>), Error> {
+        let decoded = decoder.decode(region, encoded)?;
+        if decoded.cells.len() != self.max_chars_size
+            || decoded.enable_flags.len() != self.max_chars_size
+        {
+            return Err(Error::Synthesis);
+        }
+        let result = self.assign_values(region, &decoded.bytes)?;
+        let characters = if self.case_fold.is_some() {
+            &result.raw_characters
+        } else {
+            &result.characters
+        };
+        let pairs = characters
+            .iter()
+            .zip(decoded.cells.iter())
+            .chain(result.enable_flags.iter().zip(decoded.enable_flags.iter()));
+        for (cell, decoded_cell) in pairs {
+            region.constrain_equal(cell.cell(), decoded_cell.cell())?;
+        }
+        Ok((decoded, result))
+    }
+
+    /// Checks characters that are already assigned in `chars`, e.g. by another gadget, instead
+    /// of witnessing them again: the assigned characters are constrained to equal `chars` (the
+    /// raw characters, if configured with [`Self::configure_case_fold`]). `states` are the
+    /// `chars.len() + 1` states the input visits, e.g. from [`RegexDef::gen_states`]. Fails with
+    /// `Error::Synthesis` if a known character is not a byte or the lengths do not fit.
+    pub fn assign_from_cells(
+        &self,
+        region: &mut Region<F>,
+        chars: &[AssignedCell<F, F>],
+        states: &[u64],
+    ) -> Result<AssignedRegexResult<F>, Error> {
+        if states.len() != chars.len() + 1 {
+            return Err(Error::Synthesis);
+        }
+        // The values are unknown during key generation, where any placeholder will do.
+        let characters = chars
+            .iter()
+            .map(|cell| match value_to_option(cell.value().copied()) {
+                Some(v) => u8::try_from(fe_to_biguint(&v)).map_err(|_| Error::Synthesis),
+                None => Ok(0),
+            })
+            .collect::<Result<Vec<u8>, Error>>()?;
+        let result = self.assign_char_rows_at(
+            region,
+            0,
+            characters
+                .iter()
+                .copied()
+                .zip(states.iter().map(|state| F::from(*state))),
+            chars.len(),
+            Some(F::from(states[chars.len()])),
+        )?;
+        let assigned = if self.case_fold.is_some() {
+            &result.raw_characters
+        } else {
+            &result.characters
+        };
+        for (char, cell) in assigned.iter().zip(chars.iter()) {
+            region.constrain_equal(char.cell(), cell.cell())?;
+        }
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use halo2_base::halo2_proofs::{
+        arithmetic::{Field, FieldExt},
+        circuit::floor_planner::V1,
+        dev::{CircuitCost, FailureLocation, MockProver, VerifyFailure},
+        halo2curves::bn256::{Fr, G1},
+        plonk::{Any, Circuit},
+    };
+
+    #[cfg(feature = "real-prover")]
+    use halo2_base::halo2_proofs::{halo2curves::bn256::Bn256, poly::kzg::commitment::ParamsKZG};
 
     use super::*;
+    use crate::table::{pad_chars, pad_states};
+    use crate::{compile_regex, CompiledRegex};
+    use std::rc::Rc;
 
     // Checks a regex of string len
     const MAX_STRING_LEN: usize = 128;
+    // The pattern encoded by ./test_regexes/regex_test_lookup.txt.
+    const TEST_REGEX: &str = r"email was meant for @(a|b|c|d|e|f|g|h|i|j|k|l|m|n|o|p|q|r|s|t|u|v|w|x|y|z|A|B|C|D|E|F|G|H|I|J|K|L|M|N|O|P|Q|R|S|T|U|V|W|X|Y|Z|0|1|2|3|4|5|6|7|8|9|_)+( and (a|b|c|d|e|f|g|h|i|j|k|l|m|n|o|p|q|r|s|t|u|v|w|x|y|z)+)*\.";
 
     #[derive(Default, Clone, Debug)]
     struct TestRegexCheckCircuit<F: PrimeField> {
@@ -338,7 +1937,7 @@ mod tests {
             config: Self::Config,
             mut layouter: impl Layouter<F>,
         ) -> Result<(), Error> {
-            // test regex: "email was meant for @(a|b|c|d|e|f|g|h|i|j|k|l|m|n|o|p|q|r|s|t|u|v|w|x|y|z|A|B|C|D|E|F|G|H|I|J|K|L|M|N|O|P|Q|R|S|T|U|V|W|X|Y|Z|0|1|2|3|4|5|6|7|8|9|_)+( and (a|b|c|d|e|f|g|h|i|j|k|l|m|n|o|p|q|r|s|t|u|v|w|x|y|z)+)*."
+            // test regex: "email was meant for @(a|b|c|d|e|f|g|h|i|j|k|l|m|n|o|p|q|r|s|t|u|v|w|x|y|z|A|B|C|D|E|F|G|H|I|J|K|L|M|N|O|P|Q|R|S|T|U|V|W|X|Y|Z|0|1|2|3|4|5|6|7|8|9|_)+( and (a|b|c|d|e|f|g|h|i|j|k|l|m|n|o|p|q|r|s|t|u|v|w|x|y|z)+)*\\."
             // accepted state: 23
             // let lookup_filepath = "./test_regexes/regex_test_lookup.txt";
             // let array: Vec<Vec<u64>> = read_2d_array::<u64>(lookup_filepath);
@@ -414,6 +2013,25 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_regex_sample_states() {
+        // The circuit derives the states from the table. They are not numbered along the
+        // input: the sample table starts in 0, and its chain for "email was meant for @" skips
+        // the states of the other branches.
+        let regex_def = RegexDef::read_from_text("./test_regexes/regex_test_lookup.txt");
+        let characters = b"email was meant for @y.";
+        let states = regex_def.gen_states(characters).unwrap();
+        assert_eq!(
+            states,
+            vec![
+                0, 2, 3, 6, 7, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23, 24, 25, 26, 27, 28, 29,
+                1, 5
+            ]
+        );
+        assert_eq!(regex_def.check_witness(characters, &states), Ok(()));
+        assert_eq!(regex_def.accepted_state_vals, vec![5]);
+    }
+
     #[test]
     fn test_regex_pass2() {
         let k = 8; // 8, 128, etc
@@ -477,12 +2095,9 @@ mod tests {
         // Convert query string to u128s
         let characters: Vec<u8> = "email was meant for @y".chars().map(|c| c as u8).collect();
 
-        // assert_eq!(characters.len(), STRING_LEN);
-
         // Out-of-range `value = 8`
         let circuit = TestRegexCheckCircuit::<Fr> {
             characters: characters,
-            // states: states,
             _marker: PhantomData,
         };
         let prover = MockProver::run(k, &circuit, vec![]).unwrap();
@@ -504,12 +2119,9 @@ mod tests {
             .map(|c| c as u8)
             .collect();
 
-        // assert_eq!(characters.len(), STRING_LEN);
-
         // Out-of-range `value = 8`
         let circuit = TestRegexCheckCircuit::<Fr> {
             characters: characters,
-            // states: states,
             _marker: PhantomData,
         };
         let prover = MockProver::run(k, &circuit, vec![]).unwrap();
@@ -531,12 +2143,9 @@ mod tests {
             .map(|c| c as u8)
             .collect();
 
-        // assert_eq!(characters.len(), STRING_LEN);
-
         // Out-of-range `value = 8`
         let circuit = TestRegexCheckCircuit::<Fr> {
             characters: characters,
-            // states: states,
             _marker: PhantomData,
         };
         let prover = MockProver::run(k, &circuit, vec![]).unwrap();
@@ -548,6 +2157,2113 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_regex_zero_state_cannot_forge_match() {
+        // 0 is the first state of the sample table, so NUL characters staying in state 0 would
+        // satisfy the dummy rows of both tables if they were not flagged as disabled.
+        let circuit = TestRegexCheckCircuit::<Fr> {
+            characters: vec![0, 0, 0],
+            _marker: PhantomData,
+        };
+        let prover = MockProver::run(8, &circuit, vec![]).unwrap();
+        assert!(prover.verify().is_err());
+    }
+
+    /// Configures the circuit of a [`TestCircuit`] and loads its tables.
+    trait TestConfig<F: PrimeField> {
+        type Config: Clone;
+
+        fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config;
+
+        fn load(config: &Self::Config, layouter: &mut impl Layouter<F>) -> Result<(), Error>;
+    }
+
+    type AssignFn<F, T> =
+        Rc<dyn Fn(&<T as TestConfig<F>>::Config, &mut Region<'_, F>) -> Result<(), Error>>;
+
+    /// A test circuit configured by `T`, whose rows are assigned in a single region by `assign`.
+    struct TestCircuit<F: PrimeField, T: TestConfig<F>> {
+        assign: AssignFn<F, T>,
+    }
+
+    impl<F: PrimeField, T: TestConfig<F>> TestCircuit<F, T> {
+        fn new(
+            assign: impl Fn(&T::Config, &mut Region<'_, F>) -> Result<(), Error> + 'static,
+        ) -> Self {
+            Self {
+                assign: Rc::new(assign),
+            }
+        }
+    }
+
+    impl<F: PrimeField, T: TestConfig<F>> Circuit<F> for TestCircuit<F, T> {
+        type Config = T::Config;
+        type FloorPlanner = SimpleFloorPlanner;
+
+        fn without_witnesses(&self) -> Self {
+            Self {
+                assign: self.assign.clone(),
+            }
+        }
+
+        fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+            T::configure(meta)
+        }
+
+        fn synthesize(
+            &self,
+            config: Self::Config,
+            mut layouter: impl Layouter<F>,
+        ) -> Result<(), Error> {
+            T::load(&config, &mut layouter)?;
+            layouter.assign_region(|| "regex", |mut region| (self.assign)(&config, &mut region))
+        }
+    }
+
+    /// The regex of ./test_regexes/regex_test_lookup.txt over `MAX_STRING_LEN` characters.
+    struct SampleRegex;
+
+    impl<F: PrimeField> TestConfig<F> for SampleRegex {
+        type Config = RegexCheckConfig<F>;
+
+        fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+            let regex_def = RegexDef::read_from_text("./test_regexes/regex_test_lookup.txt");
+            RegexCheckConfig::configure(meta, regex_def, MAX_STRING_LEN)
+        }
+
+        fn load(config: &Self::Config, layouter: &mut impl Layouter<F>) -> Result<(), Error> {
+            config.load(layouter)
+        }
+    }
+
+    #[test]
+    fn test_regex_padding_flags() {
+        let characters = b"email was meant for @y.";
+        // Flags the given rows inside the input as padding, with their characters zeroed.
+        let run = |padding_rows: Vec<usize>| {
+            let circuit = TestCircuit::<Fr, SampleRegex>::new(move |config, region| {
+                let result = config.assign_values(region, characters)?;
+                assert_eq!(result.is_padding.len(), MAX_STRING_LEN);
+                for (idx, padding) in result.is_padding.iter().enumerate() {
+                    let expected = (idx >= characters.len()) as u64;
+                    padding.value().map(|v| assert_eq!(*v, Fr::from(expected)));
+                }
+                for row in padding_rows.iter().copied() {
+                    region.assign_advice(
+                        || "character",
+                        config.characters,
+                        row,
+                        || Value::known(Fr::from(0)),
+                    )?;
+                    region.assign_advice(
+                        || "char_enable",
+                        config.char_enable,
+                        row,
+                        || Value::known(Fr::from(0)),
+                    )?;
+                    region.assign_advice(
+                        || "is_padding",
+                        config.is_padding,
+                        row,
+                        || Value::known(Fr::from(1)),
+                    )?;
+                }
+                Ok(())
+            });
+            MockProver::run(8, &circuit, vec![]).unwrap().verify()
+        };
+        assert_eq!(run(vec![]), Ok(()));
+
+        // Padding must be a suffix: a run of padding rows inside the input, which would drop
+        // characters from the match, is rejected.
+        for padding_rows in [vec![5], vec![5, 6, 7], vec![0, 1]] {
+            let failures = format!("{:?}", run(padding_rows).unwrap_err());
+            assert!(failures.contains("The padding flags"));
+        }
+    }
+
+    #[test]
+    fn test_regex_assign_values_iter() {
+        // Passes `len` along with the rows of `characters`.
+        let run = |characters: &[u8], len: usize| {
+            let characters = characters.to_vec();
+            let circuit = TestCircuit::<Fr, SampleRegex>::new(move |config, region| {
+                let states = config.derive_states(&characters);
+                let rows = characters.iter().copied().zip(states);
+                let result = config.assign_values_iter(region, rows, len)?;
+                assert_eq!(result.characters.len(), MAX_STRING_LEN);
+                assert_eq!(result.states.len(), MAX_STRING_LEN + 1);
+                Ok(())
+            });
+            MockProver::run(8, &circuit, vec![])
+        };
+        let input = b"email was meant for @y.";
+        run(input, input.len()).unwrap().assert_satisfied();
+        let input = b"email was meant for @y and z.";
+        run(input, input.len()).unwrap().assert_satisfied();
+        assert!(run(b"email was meant for @y", 22)
+            .unwrap()
+            .verify()
+            .is_err());
+        // The length must match the number of rows.
+        assert!(run(input, input.len() - 1).is_err());
+        assert!(run(input, input.len() + 1).is_err());
+        assert!(run(&[b'a'; MAX_STRING_LEN + 1], MAX_STRING_LEN + 1).is_err());
+    }
+
+    /// `[a-z]+@[a-z]+\.com` over 16 characters.
+    struct EmailRegex;
+
+    impl<F: PrimeField> TestConfig<F> for EmailRegex {
+        type Config = RegexCheckConfig<F>;
+
+        fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+            let regex_def = compile_regex(r"[a-z]+@[a-z]+\.com").unwrap().regex_def();
+            RegexCheckConfig::configure(meta, regex_def, 16)
+        }
+
+        fn load(config: &Self::Config, layouter: &mut impl Layouter<F>) -> Result<(), Error> {
+            config.load(layouter)
+        }
+    }
+
+    #[test]
+    fn test_regex_assign_chunks() {
+        let compiled = compile_regex(r"[a-z]+@[a-z]+\.com").unwrap();
+        let run = |chunks: &[&str], len: usize| {
+            let chunks: Vec<Vec<u8>> = chunks
+                .iter()
+                .map(|chunk| chunk.as_bytes().to_vec())
+                .collect();
+            let circuit = TestCircuit::<Fr, EmailRegex>::new(move |config, region| {
+                let chunks = chunks.iter().map(|chunk| &chunk[..]);
+                config.assign_chunks(region, chunks, len)?;
+                Ok(())
+            });
+            MockProver::run(8, &circuit, vec![])
+        };
+        // The first chunk exits in a state that is not accepting; only the whole input is.
+        assert!(!compiled.is_match(b"alice@ex"));
+        run(&["alice@ex", "ample.com"], 17)
+            .unwrap()
+            .assert_satisfied();
+        run(&["bob@", "", "mail.com"], 12)
+            .unwrap()
+            .assert_satisfied();
+        // A first chunk that is accepted on its own does not end the match.
+        assert!(compiled.is_match(b"bob@mail.com"));
+        assert!(run(&["bob@mail.com", "x"], 13).unwrap().verify().is_err());
+        assert!(run(&["bob@mail.c", "o"], 11).unwrap().verify().is_err());
+        // The chunks must hold exactly `len` characters.
+        assert!(run(&["alice@ex", "ample.com"], 16).is_err());
+        assert!(run(&["alice@ex", "ample.com"], 18).is_err());
+    }
+
+    /// `[a-z]+` over 8 characters.
+    struct LowercaseRegex;
+
+    impl<F: PrimeField> TestConfig<F> for LowercaseRegex {
+        type Config = RegexCheckConfig<F>;
+
+        fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+            let regex_def = compile_regex("[a-z]+").unwrap().regex_def();
+            RegexCheckConfig::configure(meta, regex_def, 8)
+        }
+
+        fn load(config: &Self::Config, layouter: &mut impl Layouter<F>) -> Result<(), Error> {
+            config.load(layouter)
+        }
+    }
+
+    #[test]
+    fn test_regex_assign_values_with_states() {
+        let regex_def = compile_regex("[a-z]+").unwrap().regex_def();
+        let run = |characters: &'static [u8], states: Vec<u128>| {
+            let circuit = TestCircuit::<Fr, LowercaseRegex>::new(move |config, region| {
+                let result = config.assign_values_with_states(region, characters, &states)?;
+                // The states are assigned without truncation to 64 bits.
+                for (cell, state) in result.states.iter().zip(states.iter()) {
+                    cell.value().map(|v| assert_eq!(*v, Fr::from_u128(*state)));
+                }
+                Ok(())
+            });
+            MockProver::run(6, &circuit, vec![])
+        };
+        let states = regex_def.gen_states(b"abc").unwrap();
+        let wide: Vec<u128> = states.iter().map(|state| *state as u128).collect();
+        run(b"abc", wide.clone()).unwrap().assert_satisfied();
+
+        // A state beyond `u64` is assigned as is, and rejected since the table does not hold it.
+        let mut forged = wide.clone();
+        forged[1] += 1 << 64;
+        assert!(forged[1] > u64::MAX as u128);
+        let failures = format!("{:?}", run(b"abc", forged).unwrap().verify().unwrap_err());
+        assert!(failures.contains("lookup characters and their state"));
+        // The number of states must fit the input.
+        assert!(run(b"abc", wide[..3].to_vec()).is_err());
+    }
+
+    crate::regex_circuit!(
+        PrefixCircuit,
+        compile_regex("abc")
+            .unwrap()
+            .regex_def()
+            .with_prefix_match(),
+        16
+    );
+
+    #[test]
+    fn test_regex_prefix_match() {
+        for (input, ok) in [
+            ("abc-whatever", true),
+            ("abc", true),
+            ("abcabc", true),
+            ("ab-c", false),
+            ("xabc", false),
+        ] {
+            let circuit = PrefixCircuit::<Fr>::new(input.as_bytes().to_vec());
+            let prover = MockProver::run(10, &circuit, vec![]).unwrap();
+            assert_eq!(prover.verify().is_ok(), ok, "{}", input);
+        }
+    }
+
+    /// [`SampleRegex`] with an external column the characters are first assigned to.
+    struct ExternalCharsRegex;
+
+    impl<F: PrimeField> TestConfig<F> for ExternalCharsRegex {
+        type Config = (RegexCheckConfig<F>, Column<Advice>);
+
+        fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+            let external = meta.advice_column();
+            meta.enable_equality(external);
+            (SampleRegex::configure(meta), external)
+        }
+
+        fn load((config, _): &Self::Config, layouter: &mut impl Layouter<F>) -> Result<(), Error> {
+            config.load(layouter)
+        }
+    }
+
+    #[test]
+    fn test_regex_assign_from_cells() {
+        let characters = b"email was meant for @y.";
+        // Writes `forged_char` over the regex characters column after linking.
+        let run = |forged_char: Option<(usize, u8)>| {
+            let circuit =
+                TestCircuit::<Fr, ExternalCharsRegex>::new(move |(config, external), region| {
+                    let cells = characters
+                        .iter()
+                        .enumerate()
+                        .map(|(idx, char)| {
+                            region.assign_advice(
+                                || "external character",
+                                *external,
+                                idx,
+                                || Value::known(Fr::from(*char as u64)),
+                            )
+                        })
+                        .collect::<Result<Vec<_>, Error>>()?;
+                    let states = config.derive_states(characters);
+                    config.assign_from_cells(region, &cells, &states)?;
+                    if let Some((row, char)) = forged_char {
+                        region.assign_advice(
+                            || "forged character",
+                            config.characters,
+                            row,
+                            || Value::known(Fr::from(char as u64)),
+                        )?;
+                    }
+                    Ok(())
+                });
+            MockProver::run(8, &circuit, vec![]).unwrap().verify()
+        };
+        assert_eq!(run(None), Ok(()));
+        // "@z." is accepted too, but the checked characters are no longer the external ones.
+        let failures = format!("{:?}", run(Some((21, b'z'))).unwrap_err());
+        assert!(failures.contains("Permutation"));
+        assert!(!failures.contains("Lookup"));
+    }
+
+    /// A local part and a domain regex, each with its own tag.
+    struct TaggedRegexes;
+
+    impl<F: PrimeField> TestConfig<F> for TaggedRegexes {
+        type Config = (RegexCheckConfig<F>, RegexCheckConfig<F>);
+
+        fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+            let local_part = compile_regex("[a-z.]+").unwrap().regex_def();
+            let domain = compile_regex(r"[a-z]+\.com").unwrap().regex_def();
+            (
+                RegexCheckConfig::configure_tagged(meta, local_part, 16, "local part"),
+                RegexCheckConfig::configure_tagged(meta, domain, 16, "domain"),
+            )
+        }
+
+        fn load(
+            (local_part, domain): &Self::Config,
+            layouter: &mut impl Layouter<F>,
+        ) -> Result<(), Error> {
+            local_part.load(layouter)?;
+            domain.load(layouter)
+        }
+    }
+
+    #[test]
+    fn test_regex_tagged_configs() {
+        let run = |local_part: &'static [u8], domain: &'static [u8]| {
+            let circuit = TestCircuit::<Fr, TaggedRegexes>::new(move |configs, region| {
+                configs.0.assign_values(region, local_part)?;
+                configs.1.assign_values(region, domain)?;
+                Ok(())
+            });
+            MockProver::run(7, &circuit, vec![]).unwrap().verify()
+        };
+        assert_eq!(run(b"alice.smith", b"mail.com"), Ok(()));
+
+        let failures = format!("{:?}", run(b"alice.smith", b"mail.org").unwrap_err());
+        assert!(failures.contains("domain: lookup characters and their state"));
+        assert!(!failures.contains("local part:"));
+        let failures = format!("{:?}", run(b"alice@", b"mail.com").unwrap_err());
+        assert!(failures.contains("local part: lookup characters and their state"));
+        assert!(!failures.contains("domain:"));
+
+        // Configuring again reuses the leaked names instead of leaking new ones.
+        let name = tagged(Some("domain"), "lookup characters and their state");
+        assert!(std::ptr::eq(
+            name,
+            tagged(Some("domain"), "lookup characters and their state")
+        ));
+    }
+
+    #[derive(Default, Clone, Debug)]
+    struct TestLoadAllCircuit<F: PrimeField> {
+        // The index of the config each input is checked by, and the input.
+        inputs: Vec<(usize, Vec<u8>)>,
+        // Gives the second config the states of the first before loading.
+        overlap: bool,
+        _marker: PhantomData<F>,
+    }
+
+    impl<F: PrimeField> Circuit<F> for TestLoadAllCircuit<F> {
+        type Config = Vec<RegexCheckConfig<F>>;
+        type FloorPlanner = SimpleFloorPlanner;
+
+        fn without_witnesses(&self) -> Self {
+            Self::default()
+        }
+
+        fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+            let regex_defs = ["[a-z]+@[a-z]+", "[0-9]+", "(?:ab)+"]
+                .iter()
+                .map(|pattern| compile_regex(pattern).unwrap().regex_def())
+                .collect();
+            RegexCheckConfig::configure_all(meta, regex_defs, 16)
+        }
+
+        fn synthesize(
+            &self,
+            mut configs: Self::Config,
+            mut layouter: impl Layouter<F>,
+        ) -> Result<(), Error> {
+            if self.overlap {
+                configs[1].regex_def = configs[0].regex_def.clone();
+            }
+            RegexCheckConfig::load_all(&mut layouter, &configs)?;
+            for (idx, characters) in self.inputs.iter() {
+                layouter.assign_region(
+                    || format!("regex {}", idx),
+                    |mut region| configs[*idx].assign_values(&mut region, characters),
+                )?;
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_regex_load_all() {
+        let circuit = |inputs: &[(usize, &str)], overlap| TestLoadAllCircuit::<Fr> {
+            inputs: inputs
+                .iter()
+                .map(|(idx, input)| (*idx, input.as_bytes().to_vec()))
+                .collect(),
+            overlap,
+            _marker: PhantomData,
+        };
+        let run = |inputs: &[(usize, &str)]| {
+            MockProver::run(8, &circuit(inputs, false), vec![])
+                .unwrap()
+                .verify()
+        };
+        assert_eq!(run(&[(0, "alice@mail"), (1, "2023"), (2, "abab")]), Ok(()));
+        // Each input is only matched against its own regex in the shared table.
+        assert!(run(&[(0, "2023")]).is_err());
+        assert!(run(&[(1, "abab")]).is_err());
+        assert!(run(&[(2, "alice@mail")]).is_err());
+
+        // Configs with overlapping states cannot share the tables.
+        assert!(MockProver::run(8, &circuit(&[(0, "alice@mail")], true), vec![]).is_err());
+    }
+
+    #[derive(Default, Clone, Debug)]
+    struct TestMatchCountCircuit<F: PrimeField, const OVERLAPPING: bool> {
+        characters: Vec<u8>,
+        // Clears the hit after the first occurrence and lowers the counts to match.
+        forge_hit: bool,
+        _marker: PhantomData<F>,
+    }
+
+    impl<F: PrimeField, const OVERLAPPING: bool> Circuit<F> for TestMatchCountCircuit<F, OVERLAPPING> {
+        type Config = (RegexCheckConfig<F>, Column<Instance>);
+        type FloorPlanner = SimpleFloorPlanner;
+
+        fn without_witnesses(&self) -> Self {
+            Self::default()
+        }
+
+        fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+            let scanner = CompiledRegex::scanner("ab", OVERLAPPING).unwrap();
+            let regex_def = scanner.regex_def();
+            let hit_state_vals = regex_def.accepted_state_vals.clone();
+            let mut config = RegexCheckConfig::configure(meta, regex_def.accepting_all(), 8);
+            config.configure_match_count(meta, hit_state_vals);
+            let instance = meta.instance_column();
+            meta.enable_equality(instance);
+            (config, instance)
+        }
+
+        fn synthesize(
+            &self,
+            (config, instance): Self::Config,
+            mut layouter: impl Layouter<F>,
+        ) -> Result<(), Error> {
+            config.load(&mut layouter)?;
+            let result = layouter.assign_region(
+                || "regex",
+                |mut region| {
+                    let result = config.assign_values(&mut region, &self.characters)?;
+                    if self.forge_hit {
+                        let match_count = config.match_count.as_ref().unwrap();
+                        let scanner = CompiledRegex::scanner("ab", OVERLAPPING).unwrap();
+                        let first_hit = (0..self.characters.len())
+                            .find(|idx| scanner.count_matches(&self.characters[..=*idx]) == 1)
+                            .unwrap();
+                        region.assign_advice(
+                            || "forged hit",
+                            match_count.hits,
+                            first_hit,
+                            || Value::known(F::zero()),
+                        )?;
+                        for idx in first_hit..=config.max_chars_size {
+                            let len = (idx + 1).min(self.characters.len());
+                            let count = scanner.count_matches(&self.characters[..len]) - 1;
+                            region.assign_advice(
+                                || "forged count",
+                                match_count.count,
+                                idx,
+                                || Value::known(F::from(count as u64)),
+                            )?;
+                        }
+                    }
+                    Ok(result)
+                },
+            )?;
+            layouter.constrain_instance(result.match_count.unwrap().cell(), instance, 0)
+        }
+    }
+
+    #[test]
+    fn test_regex_match_count() {
+        fn run<const OVERLAPPING: bool>(
+            characters: &str,
+            forge_hit: bool,
+            count: u64,
+        ) -> Result<(), Vec<VerifyFailure>> {
+            let circuit = TestMatchCountCircuit::<Fr, OVERLAPPING> {
+                characters: characters.as_bytes().to_vec(),
+                forge_hit,
+                _marker: PhantomData,
+            };
+            MockProver::run(10, &circuit, vec![vec![Fr::from(count)]])
+                .unwrap()
+                .verify()
+        }
+        assert_eq!(run::<true>("ababab", false, 3), Ok(()));
+        assert_eq!(run::<true>("xaby", false, 1), Ok(()));
+        assert_eq!(run::<true>("ba", false, 0), Ok(()));
+        assert!(run::<true>("ababab", false, 2).is_err());
+        // A hit cannot be dropped, even with the counts adjusted.
+        let failures = format!("{:?}", run::<true>("ababab", true, 2).unwrap_err());
+        assert!(failures.contains("The hit flag of each next state"));
+
+        assert_eq!(run::<false>("ababab", false, 3), Ok(()));
+        assert_eq!(run::<false>("abba", false, 1), Ok(()));
+    }
+
+    // Tags the transitions of `[a-z0-9]+@[a-z0-9]+` on digits with bit 0 and those after the
+    // `@` with bit 1.
+    fn email_tags(regex_def: &RegexDef) -> HashMap<(u8, u64), u64> {
+        let mut domain_states: HashSet<u64> = regex_def
+            .state_lookup
+            .iter()
+            .filter(|((char, _), _)| *char == b'@')
+            .map(|(_, next_state)| *next_state)
+            .collect();
+        for _ in 0..regex_def.largest_state_val {
+            for ((_, prev_state), next_state) in regex_def.state_lookup.iter() {
+                if domain_states.contains(prev_state) {
+                    domain_states.insert(*next_state);
+                }
+            }
+        }
+        regex_def
+            .state_lookup
+            .keys()
+            .map(|(char, prev_state)| {
+                let is_digit = char.is_ascii_digit() as u64;
+                let in_domain = domain_states.contains(prev_state) as u64;
+                ((*char, *prev_state), is_digit | in_domain << 1)
+            })
+            .collect()
+    }
+
+    #[derive(Default, Clone, Debug)]
+    struct TestTransitionTagsCircuit<F: PrimeField> {
+        characters: Vec<u8>,
+        // Sets the digit flag of the first character.
+        forge_flag: bool,
+        _marker: PhantomData<F>,
+    }
+
+    impl<F: PrimeField> Circuit<F> for TestTransitionTagsCircuit<F> {
+        type Config = (RegexCheckConfig<F>, [Column<Instance>; 2]);
+        type FloorPlanner = SimpleFloorPlanner;
+
+        fn without_witnesses(&self) -> Self {
+            Self::default()
+        }
+
+        fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+            let regex_def = compile_regex("[a-z0-9]+@[a-z0-9]+").unwrap().regex_def();
+            let tags = email_tags(&regex_def);
+            let mut config = RegexCheckConfig::configure(meta, regex_def, 10);
+            config.configure_transition_tags(meta, 2, tags);
+            let instances = [meta.instance_column(), meta.instance_column()];
+            for instance in instances {
+                meta.enable_equality(instance);
+            }
+            (config, instances)
+        }
+
+        fn synthesize(
+            &self,
+            (config, instances): Self::Config,
+            mut layouter: impl Layouter<F>,
+        ) -> Result<(), Error> {
+            config.load(&mut layouter)?;
+            let result = layouter.assign_region(
+                || "regex",
+                |mut region| {
+                    let mut result = config.assign_values(&mut region, &self.characters)?;
+                    if self.forge_flag {
+                        let flags = &config.transition_tags.as_ref().unwrap().flags;
+                        result.transition_tags[0][0] = region.assign_advice(
+                            || "forged flag",
+                            flags[0],
+                            0,
+                            || Value::known(F::from(1)),
+                        )?;
+                    }
+                    Ok(result)
+                },
+            )?;
+            for (flags, instance) in result.transition_tags.iter().zip(instances) {
+                for (idx, flag) in flags.iter().enumerate() {
+                    layouter.constrain_instance(flag.cell(), instance, idx)?;
+                }
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_regex_transition_tags() {
+        fn run(characters: &str, forge_flag: bool) -> Result<(), Vec<VerifyFailure>> {
+            let flags = |tag: usize| {
+                let mut flags: Vec<Fr> = characters
+                    .bytes()
+                    .enumerate()
+                    .map(|(idx, char)| match tag {
+                        0 => char.is_ascii_digit() || (forge_flag && idx == 0),
+                        _ => characters[..idx].contains('@'),
+                    })
+                    .map(|flag| Fr::from(flag as u64))
+                    .collect();
+                flags.resize(10, Fr::from(0));
+                flags
+            };
+            let circuit = TestTransitionTagsCircuit::<Fr> {
+                characters: characters.as_bytes().to_vec(),
+                forge_flag,
+                _marker: PhantomData,
+            };
+            MockProver::run(8, &circuit, vec![flags(0), flags(1)])
+                .unwrap()
+                .verify()
+        }
+        // The two tags label the characters independently.
+        assert_eq!(run("ab12@x9y", false), Ok(()));
+        assert_eq!(run("7@b", false), Ok(()));
+        // A flag cannot be set on a transition without the tag.
+        let failures = format!("{:?}", run("ab12@x9y", true).unwrap_err());
+        assert!(failures.contains("The tags of each transition"));
+    }
+
+    #[test]
+    fn test_regex_input_fully_consumed() {
+        // Writes `trailing` over the padding rows after the witnessed input.
+        let run = |characters: &'static [u8], trailing: &'static [u8]| {
+            let circuit = TestCircuit::<Fr, LowercaseRegex>::new(move |config, region| {
+                config.assign_values(region, characters)?;
+                for (idx, char) in trailing.iter().enumerate() {
+                    region.assign_advice(
+                        || "trailing character",
+                        config.characters,
+                        characters.len() + idx,
+                        || Value::known(Fr::from(*char as u64)),
+                    )?;
+                }
+                Ok(())
+            });
+            MockProver::run(6, &circuit, vec![]).unwrap().verify()
+        };
+        assert_eq!(run(b"abc", b""), Ok(()));
+        assert!(run(b"abc123", b"").is_err());
+        // "abc" is accepted, but the trailing "123" cannot be passed off as padding.
+        let failures = format!("{:?}", run(b"abc", b"123").unwrap_err());
+        assert!(failures.contains("The padding characters must be zero"));
+        assert!(!failures.contains("Lookup"));
+    }
+
+    #[derive(Default, Clone, Debug)]
+    struct TestEmptyInputCircuit<F: PrimeField, const PLUS: bool> {
+        characters: Vec<u8>,
+        _marker: PhantomData<F>,
+    }
+
+    impl<F: PrimeField, const PLUS: bool> Circuit<F> for TestEmptyInputCircuit<F, PLUS> {
+        type Config = (RegexCheckConfig<F>, Column<Instance>);
+        type FloorPlanner = SimpleFloorPlanner;
+
+        fn without_witnesses(&self) -> Self {
+            Self::default()
+        }
+
+        fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+            let pattern = if PLUS { "a+" } else { "a*" };
+            let regex_def = compile_regex(pattern).unwrap().regex_def();
+            let instance = meta.instance_column();
+            meta.enable_equality(instance);
+            (RegexCheckConfig::configure(meta, regex_def, 8), instance)
+        }
+
+        fn synthesize(
+            &self,
+            (config, instance): Self::Config,
+            mut layouter: impl Layouter<F>,
+        ) -> Result<(), Error> {
+            config.load(&mut layouter)?;
+            let result = layouter.assign_region(
+                || "regex",
+                |mut region| config.assign_values(&mut region, &self.characters),
+            )?;
+            layouter.constrain_instance(result.accepted_state.cell(), instance, 0)
+        }
+    }
+
+    #[test]
+    fn test_regex_empty_input() {
+        fn run<const PLUS: bool>(characters: &[u8]) -> Result<(), Vec<VerifyFailure>> {
+            let pattern = if PLUS { "a+" } else { "a*" };
+            let regex_def = compile_regex(pattern).unwrap().regex_def();
+            let end_state = *regex_def.gen_states(characters).unwrap().last().unwrap();
+            let circuit = TestEmptyInputCircuit::<Fr, PLUS> {
+                characters: characters.to_vec(),
+                _marker: PhantomData,
+            };
+            MockProver::run(6, &circuit, vec![vec![Fr::from(end_state)]])
+                .unwrap()
+                .verify()
+        }
+        // An empty input is accepted iff the start state is, and exits in it.
+        assert_eq!(run::<false>(b""), Ok(()));
+        let failures = format!("{:?}", run::<true>(b"").unwrap_err());
+        assert!(failures.contains("The final state must be accepted"));
+        assert_eq!(run::<false>(b"aa"), Ok(()));
+        assert_eq!(run::<true>(b"aa"), Ok(()));
+    }
+
+    const SELF_LOOP_PATTERNS: [&str; 3] = ["a*", "a+a*", "(ab)*"];
+
+    #[derive(Default, Clone, Debug)]
+    struct TestSelfLoopCircuit<F: PrimeField, const PATTERN: usize> {
+        characters: Vec<u8>,
+        _marker: PhantomData<F>,
+    }
+
+    impl<F: PrimeField, const PATTERN: usize> Circuit<F> for TestSelfLoopCircuit<F, PATTERN> {
+        type Config = (RegexCheckConfig<F>, Column<Instance>);
+        type FloorPlanner = SimpleFloorPlanner;
+
+        fn without_witnesses(&self) -> Self {
+            Self::default()
+        }
+
+        fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+            let regex_def = compile_regex(SELF_LOOP_PATTERNS[PATTERN])
+                .unwrap()
+                .regex_def();
+            let instance = meta.instance_column();
+            meta.enable_equality(instance);
+            (RegexCheckConfig::configure(meta, regex_def, 8), instance)
+        }
+
+        fn synthesize(
+            &self,
+            (config, instance): Self::Config,
+            mut layouter: impl Layouter<F>,
+        ) -> Result<(), Error> {
+            config.load(&mut layouter)?;
+            let result = layouter.assign_region(
+                || "regex",
+                |mut region| config.assign_values(&mut region, &self.characters),
+            )?;
+            layouter.constrain_instance(result.accepted_state.cell(), instance, 0)
+        }
+    }
+
+    #[test]
+    fn test_regex_self_loops() {
+        fn check<const PATTERN: usize>() {
+            let compiled = compile_regex(SELF_LOOP_PATTERNS[PATTERN]).unwrap();
+            let regex_def = compiled.regex_def();
+            // Every input of up to 8 bytes over `ab`: the loops are left on any row, including
+            // the last one, after which the input runs into padding or fills all rows.
+            let mut inputs = vec![vec![]];
+            for len in 1..=8 {
+                inputs.extend((0..1 << len).map(|bits: usize| {
+                    (0..len)
+                        .map(|idx| if (bits >> idx) & 1 == 0 { b'a' } else { b'b' })
+                        .collect()
+                }));
+            }
+            for input in inputs {
+                let accepted = compiled.is_match(&input);
+                let end_state = match regex_def.gen_states(&input) {
+                    Ok(states) => *states.last().unwrap(),
+                    Err(_) => 0,
+                };
+                let circuit = TestSelfLoopCircuit::<Fr, PATTERN> {
+                    characters: input.clone(),
+                    _marker: PhantomData,
+                };
+                let prover = MockProver::run(6, &circuit, vec![vec![Fr::from(end_state)]]).unwrap();
+                assert_eq!(
+                    prover.verify().is_ok(),
+                    accepted,
+                    "{} {:?}",
+                    SELF_LOOP_PATTERNS[PATTERN],
+                    String::from_utf8_lossy(&input)
+                );
+            }
+        }
+        // `a*` and `(ab)*` loop back to their accepting start state, `a+a*` loops in a later one.
+        check::<0>();
+        check::<1>();
+        check::<2>();
+    }
+
+    /// `[a-z]+` over 8 characters, of 3 to 5 characters.
+    struct LengthRangeRegex;
+
+    impl<F: PrimeField> TestConfig<F> for LengthRangeRegex {
+        type Config = RegexCheckConfig<F>;
+
+        fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+            let mut config = LowercaseRegex::configure(meta);
+            config.configure_length_range(meta, 3, 5);
+            config
+        }
+
+        fn load(config: &Self::Config, layouter: &mut impl Layouter<F>) -> Result<(), Error> {
+            config.load(layouter)
+        }
+    }
+
+    #[test]
+    fn test_regex_length_range() {
+        for (input, in_range) in [
+            ("abc", true),
+            ("abcde", true),
+            ("ab", false),
+            ("abcdef", false),
+            ("abcdefgh", false),
+        ] {
+            let circuit = TestCircuit::<Fr, LengthRangeRegex>::new(move |config, region| {
+                config.assign_values(region, input.as_bytes())?;
+                Ok(())
+            });
+            let prover = MockProver::run(6, &circuit, vec![]).unwrap();
+            assert_eq!(prover.verify().is_ok(), in_range, "{}", input);
+        }
+    }
+
+    const DIGRAPH_STRING_LEN: usize = 8;
+
+    /// `[a-z.]+` with a digraph gate rejecting doubled dots, and the column of its inverses.
+    struct NoDoubleDotRegex;
+
+    impl<F: PrimeField> TestConfig<F> for NoDoubleDotRegex {
+        type Config = (RegexCheckConfig<F>, Column<Advice>);
+
+        fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+            let regex_def = compile_regex("[a-z.]+").unwrap().regex_def();
+            let config = RegexCheckConfig::configure(meta, regex_def, DIGRAPH_STRING_LEN);
+            // (prev - '.')^2 + (cur - '.')^2 is non-zero unless both are dots, so it has an
+            // inverse exactly when the pair is allowed.
+            let inv = meta.advice_column();
+            config.create_digraph_gate(meta, "No doubled dots", |meta, prev_char, cur_char| {
+                let dot = Expression::Constant(F::from(b'.' as u64));
+                let prev_diff = prev_char - dot.clone();
+                let cur_diff = cur_char - dot;
+                let sum = prev_diff.clone() * prev_diff + cur_diff.clone() * cur_diff;
+                let inv = meta.query_advice(inv, Rotation::cur());
+                vec![sum * inv - Expression::Constant(F::from(1))]
+            });
+            (config, inv)
+        }
+
+        fn load((config, _): &Self::Config, layouter: &mut impl Layouter<F>) -> Result<(), Error> {
+            config.load(layouter)
+        }
+    }
+
+    #[test]
+    fn test_regex_digraph_gate() {
+        for (input, allowed) in [
+            ("a.b.c", true),
+            (".a.", true),
+            ("abc", true),
+            ("a..b", false),
+            ("ab..", false),
+        ] {
+            let characters = input.as_bytes();
+            let circuit = TestCircuit::<Fr, NoDoubleDotRegex>::new(move |(config, inv), region| {
+                config.assign_values(region, characters)?;
+                // The gate queries `inv` on every row but the first, padding included.
+                for idx in 1..=DIGRAPH_STRING_LEN {
+                    let diff = |idx: usize| {
+                        let char = characters.get(idx).copied().unwrap_or(0);
+                        Fr::from(char as u64) - Fr::from(b'.' as u64)
+                    };
+                    let sum = diff(idx - 1).square() + diff(idx).square();
+                    region.assign_advice(
+                        || format!("inv at {}", idx),
+                        *inv,
+                        idx,
+                        || Value::known(sum.invert().unwrap_or(Fr::zero())),
+                    )?;
+                }
+                Ok(())
+            });
+            let prover = MockProver::run(6, &circuit, vec![]).unwrap();
+            assert_eq!(prover.verify().is_ok(), allowed, "{}", input);
+        }
+    }
+
+    /// [`SampleRegex`] with a state-delta column.
+    struct StateDeltaRegex;
+
+    impl<F: PrimeField> TestConfig<F> for StateDeltaRegex {
+        type Config = RegexCheckConfig<F>;
+
+        fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+            let mut config = SampleRegex::configure(meta);
+            config.configure_state_deltas(meta);
+            config
+        }
+
+        fn load(config: &Self::Config, layouter: &mut impl Layouter<F>) -> Result<(), Error> {
+            config.load(layouter)
+        }
+    }
+
+    #[test]
+    fn test_regex_state_deltas() {
+        // Assigns `forged_delta` at a row instead of the honest delta.
+        let run = |characters: &'static [u8], forged_delta: Option<(usize, u64)>| {
+            let circuit = TestCircuit::<Fr, StateDeltaRegex>::new(move |config, region| {
+                let result = config.assign_values(region, characters)?;
+                assert_eq!(result.state_deltas.len(), MAX_STRING_LEN);
+                if let Some((row, delta)) = forged_delta {
+                    region.assign_advice(
+                        || "state_delta",
+                        config.state_delta.unwrap(),
+                        row,
+                        || Value::known(Fr::from(delta)),
+                    )?;
+                }
+                Ok(())
+            });
+            MockProver::run(9, &circuit, vec![]).unwrap().verify()
+        };
+        let regex_def = RegexDef::read_from_text("./test_regexes/regex_test_lookup.txt");
+        // Same verdicts as the absolute mode of `TestRegexCheckCircuit`.
+        for (input, accepted) in [
+            ("email was meant for @y.", true),
+            ("email was meant for @ykjt.", true),
+            ("email was meant for @y", false),
+            ("email was sent", false),
+        ] {
+            let absolute = TestRegexCheckCircuit::<Fr> {
+                characters: input.as_bytes().to_vec(),
+                _marker: PhantomData,
+            };
+            let absolute_ok = MockProver::run(9, &absolute, vec![])
+                .unwrap()
+                .verify()
+                .is_ok();
+            assert_eq!(absolute_ok, accepted, "{}", input);
+            assert_eq!(run(input.as_bytes(), None).is_ok(), accepted, "{}", input);
+        }
+
+        // "ykjt" stays in the same state after its first letter: the self-loop has delta 0.
+        let states = regex_def.gen_states(b"email was meant for @ykjt.").unwrap();
+        assert_eq!(states[22], states[23]);
+
+        // A delta that does not lead to the assigned next state fails, and so does a nonzero
+        // delta on a padding row.
+        let input = b"email was meant for @y.";
+        for forged_delta in [(0, 7), (input.len(), 7)] {
+            let failures = run(input, Some(forged_delta)).unwrap_err();
+            assert!(format!("{:?}", failures).contains("The state deltas"));
+        }
+
+        // Deltas do not shrink the transition table: one record per transition either way.
+        let delta_records: std::collections::HashSet<(u64, u64, u8)> = regex_def
+            .state_lookup
+            .iter()
+            .map(|((char, prev_state), next_state)| {
+                (*prev_state, next_state.wrapping_sub(*prev_state), *char)
+            })
+            .collect();
+        let estimate = RegexCheckConfig::<Fr>::estimate_cost(&regex_def, MAX_STRING_LEN);
+        assert_eq!(delta_records.len() + 1, estimate.table_rows);
+    }
+
+    #[derive(Default, Clone, Debug)]
+    struct TestBatchCircuit<F: PrimeField> {
+        inputs: Vec<Vec<u8>>,
+        _marker: PhantomData<F>,
+    }
+
+    impl<F: PrimeField> Circuit<F> for TestBatchCircuit<F> {
+        type Config = (RegexCheckConfig<F>, Column<Instance>);
+        type FloorPlanner = SimpleFloorPlanner;
+
+        fn without_witnesses(&self) -> Self {
+            Self {
+                inputs: vec![vec![]; self.inputs.len()],
+                _marker: PhantomData,
+            }
+        }
+
+        fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+            let regex_def = RegexDef::read_from_text("./test_regexes/regex_test_lookup.txt");
+            let instance = meta.instance_column();
+            meta.enable_equality(instance);
+            (
+                RegexCheckConfig::configure(meta, regex_def, MAX_STRING_LEN),
+                instance,
+            )
+        }
+
+        fn synthesize(
+            &self,
+            (config, instance): Self::Config,
+            mut layouter: impl Layouter<F>,
+        ) -> Result<(), Error> {
+            config.load(&mut layouter)?;
+            let inputs: Vec<&[u8]> = self.inputs.iter().map(|input| &input[..]).collect();
+            let results = layouter.assign_region(
+                || "regex batch",
+                |mut region| config.assign_values_batch(&mut region, &inputs),
+            )?;
+            config.expose_accepted_states(&mut layouter, &results, instance)
+        }
+    }
+
+    #[test]
+    fn test_regex_batch() {
+        let regex_def = RegexDef::read_from_text("./test_regexes/regex_test_lookup.txt");
+        let inputs: Vec<Vec<u8>> = [
+            "email was meant for @y.",
+            "email was meant for @ykjt and zk.",
+            "email was meant for @alice_01.",
+        ]
+        .iter()
+        .map(|input| input.as_bytes().to_vec())
+        .collect();
+        let accepted_states: Vec<Fr> = inputs
+            .iter()
+            .map(|input| Fr::from(*regex_def.gen_states(input).unwrap().last().unwrap()))
+            .collect();
+        // The tables are shared, so the batch takes one block of rows per input in a single
+        // circuit instead of one circuit per input, each paying for the tables.
+        let circuit = TestBatchCircuit::<Fr> {
+            inputs: inputs.clone(),
+            _marker: PhantomData,
+        };
+        let prover = MockProver::run(9, &circuit, vec![accepted_states.clone()]).unwrap();
+        prover.assert_satisfied();
+
+        // A rejected input fails the whole batch.
+        let mut rejected = inputs;
+        rejected[1] = b"email was meant for @ykjt and".to_vec();
+        let circuit = TestBatchCircuit::<Fr> {
+            inputs: rejected,
+            _marker: PhantomData,
+        };
+        let prover = MockProver::run(9, &circuit, vec![accepted_states]).unwrap();
+        assert!(prover.verify().is_err());
+    }
+
+    #[test]
+    fn test_regex_chained_offsets() {
+        let run = |first: &'static str, second: &'static str| {
+            let circuit = TestCircuit::<Fr, LowercaseRegex>::new(move |config, region| {
+                let first = config.assign_values(region, first.as_bytes())?;
+                assert_eq!(first.next_offset, 9);
+                let second =
+                    config.assign_values_at(region, first.next_offset, second.as_bytes())?;
+                assert_eq!(second.next_offset, 18);
+                Ok(())
+            });
+            MockProver::run(6, &circuit, vec![]).unwrap().verify()
+        };
+        assert_eq!(run("alice", "bob"), Ok(()));
+        assert_eq!(run("abcdefgh", "x"), Ok(()));
+        // Each check is verified on its own rows.
+        assert!(run("alice", "b0b").is_err());
+        assert!(run("al1ce", "bob").is_err());
+    }
+
+    #[test]
+    fn test_regex_results_equal() {
+        let run = |first: &'static str, second: &'static str| {
+            let circuit = TestCircuit::<Fr, LowercaseRegex>::new(move |config, region| {
+                let inputs = [first.as_bytes(), second.as_bytes()];
+                let results = config.assign_values_batch(region, &inputs)?;
+                config.constrain_results_equal(region, &results[0], &results[1], true)
+            });
+            MockProver::run(6, &circuit, vec![]).unwrap().verify()
+        };
+        assert_eq!(run("alice", "alice"), Ok(()));
+        assert_eq!(run("abcdefgh", "abcdefgh"), Ok(()));
+        // Both inputs are accepted, so only the copy constraints fail.
+        for (first, second) in [("alice", "alicf"), ("alice", "alic"), ("bob", "bobby")] {
+            let failures = format!("{:?}", run(first, second).unwrap_err());
+            assert!(failures.contains("Permutation"), "{} {}", first, second);
+            assert!(!failures.contains("Lookup"), "{} {}", first, second);
+        }
+    }
+
+    /// `a+b` over 8 characters.
+    struct AnchoredRegex;
+
+    impl<F: PrimeField> TestConfig<F> for AnchoredRegex {
+        type Config = RegexCheckConfig<F>;
+
+        fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+            let regex_def = compile_regex("a+b").unwrap().regex_def();
+            RegexCheckConfig::configure(meta, regex_def, 8)
+        }
+
+        fn load(config: &Self::Config, layouter: &mut impl Layouter<F>) -> Result<(), Error> {
+            config.load(layouter)
+        }
+    }
+
+    #[test]
+    fn test_regex_fixed_position() {
+        let run = |buffer: &'static str, start: usize| {
+            let circuit = TestCircuit::<Fr, AnchoredRegex>::new(move |config, region| {
+                let (skipped, result) =
+                    config.assign_values_from(region, buffer.as_bytes(), start)?;
+                assert_eq!(skipped.len(), start);
+                assert_eq!(result.next_offset, start + 9);
+                Ok(())
+            });
+            MockProver::run(6, &circuit, vec![]).unwrap().verify()
+        };
+        // The match starts at offset 4, whatever comes before.
+        assert_eq!(run("xyz!aab", 4), Ok(()));
+        assert_eq!(run("\0\0ba aab", 6), Ok(()));
+        // Only the segment from the offset on is matched, up to its end.
+        assert!(run("xyz!aab", 3).is_err());
+        assert!(run("xyz!aab", 6).is_err());
+        assert!(run("xyz!aabc", 4).is_err());
+    }
+
+    #[derive(Default, Clone, Debug)]
+    struct TestAssignFeCircuit<F: PrimeField, const MAX: usize> {
+        characters: Vec<u8>,
+        // The characters and states as field elements, assigned with `assign_values_fe` instead
+        // of the bytes.
+        precomputed: Option<(Vec<Value<F>>, Vec<Value<F>>)>,
+    }
+
+    impl<F: PrimeField, const MAX: usize> Circuit<F> for TestAssignFeCircuit<F, MAX> {
+        type Config = (RegexCheckConfig<F>, Column<Instance>);
+        type FloorPlanner = SimpleFloorPlanner;
+
+        fn without_witnesses(&self) -> Self {
+            Self::default()
+        }
+
+        fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+            let regex_def = compile_regex("[a-z]+").unwrap().regex_def();
+            let mut config = RegexCheckConfig::configure(meta, regex_def, MAX);
+            config.configure_state_deltas(meta);
+            let instance = meta.instance_column();
+            meta.enable_equality(instance);
+            (config, instance)
+        }
+
+        fn synthesize(
+            &self,
+            (config, instance): Self::Config,
+            mut layouter: impl Layouter<F>,
+        ) -> Result<(), Error> {
+            config.load(&mut layouter)?;
+            let result = layouter.assign_region(
+                || "regex",
+                |mut region| match self.precomputed.as_ref() {
+                    Some((characters, states)) => {
+                        config.assign_values_fe(&mut region, characters, states)
+                    }
+                    None => config.assign_values(&mut region, &self.characters),
+                },
+            )?;
+            // The padded characters, then the padded states.
+            for (row, cell) in result
+                .characters
+                .iter()
+                .chain(result.states.iter())
+                .enumerate()
+            {
+                layouter.constrain_instance(cell.cell(), instance, row)?;
+            }
+            Ok(())
+        }
+    }
+
+    fn precompute<F: PrimeField>(
+        characters: &[u8],
+        states: &[u64],
+    ) -> (Vec<Value<F>>, Vec<Value<F>>) {
+        (
+            characters
+                .iter()
+                .map(|char| Value::known(F::from(*char as u64)))
+                .collect(),
+            states
+                .iter()
+                .map(|state| Value::known(F::from(*state)))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn test_regex_assign_values_fe() {
+        const MAX: usize = 16;
+        let regex_def = compile_regex("[a-z]+").unwrap().regex_def();
+        let characters = b"streaming".to_vec();
+        let states = regex_def.gen_states(&characters).unwrap();
+        let instance: Vec<Fr> = pad_chars(&characters, MAX, 0)
+            .into_iter()
+            .map(|char| Fr::from(char as u64))
+            .chain(pad_states(&states, MAX, 0).into_iter().map(Fr::from))
+            .collect();
+        let run = |precomputed| {
+            let circuit = TestAssignFeCircuit::<Fr, MAX> {
+                characters: characters.clone(),
+                precomputed,
+            };
+            MockProver::run(7, &circuit, vec![instance.clone()])
+        };
+        // Both paths assign the same cells.
+        assert_eq!(run(None).unwrap().verify(), Ok(()));
+        let precomputed = precompute(&characters, &states);
+        assert_eq!(run(Some(precomputed.clone())).unwrap().verify(), Ok(()));
+
+        // The field elements are constrained like the bytes.
+        let (chars, mut forged_states) = precomputed.clone();
+        forged_states[3] = Value::known(Fr::from(states[3] + 1));
+        assert!(run(Some((chars, forged_states))).unwrap().verify().is_err());
+        let (chars, states) = precomputed;
+        assert!(run(Some((chars, states[..5].to_vec()))).is_err());
+    }
+
+    // $ cargo test --release test_regex_assign_values_fe_long -- --ignored
+    #[test]
+    #[ignore]
+    fn test_regex_assign_values_fe_long() {
+        const MAX: usize = 4096;
+        let regex_def = compile_regex("[a-z]+").unwrap().regex_def();
+        let characters: Vec<u8> = (0..MAX).map(|idx| b'a' + (idx % 26) as u8).collect();
+        let states = regex_def.gen_states(&characters).unwrap();
+        let instance: Vec<Fr> = characters
+            .iter()
+            .map(|char| Fr::from(*char as u64))
+            .chain(states.iter().map(|state| Fr::from(*state)))
+            .collect();
+        let precomputed = precompute(&characters, &states);
+        for precomputed in [None, Some(precomputed)] {
+            let circuit = TestAssignFeCircuit::<Fr, MAX> {
+                characters: characters.clone(),
+                precomputed,
+            };
+            MockProver::run(14, &circuit, vec![instance.clone()])
+                .unwrap()
+                .assert_satisfied();
+        }
+    }
+
+    /// `[a-z]+` over 8 characters, with offsets starting at 100.
+    struct OffsetRegex;
+
+    impl<F: PrimeField> TestConfig<F> for OffsetRegex {
+        type Config = RegexCheckConfig<F>;
+
+        fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+            let mut config = LowercaseRegex::configure(meta);
+            config.configure_offsets(meta, 100);
+            config
+        }
+
+        fn load(config: &Self::Config, layouter: &mut impl Layouter<F>) -> Result<(), Error> {
+            config.load(layouter)
+        }
+    }
+
+    // Assigns `forged_offset` at row 3 instead of the honest offset.
+    fn offset_circuit(
+        characters: &'static [u8],
+        forged_offset: Option<u64>,
+    ) -> TestCircuit<Fr, OffsetRegex> {
+        TestCircuit::new(move |config, region| {
+            let result = config.assign_values(region, characters)?;
+            assert_eq!(result.offsets.len(), 8);
+            for (idx, offset) in result.offsets.iter().enumerate() {
+                offset
+                    .value()
+                    .map(|v| assert_eq!(*v, Fr::from(100 + idx as u64)));
+            }
+            if let Some(forged) = forged_offset {
+                region.assign_advice(
+                    || "offset",
+                    config.offset.unwrap().0,
+                    3,
+                    || Value::known(Fr::from(forged)),
+                )?;
+            }
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_regex_offsets() {
+        let run = |forged_offset| {
+            MockProver::run(6, &offset_circuit(b"hello", forged_offset), vec![])
+                .unwrap()
+                .verify()
+        };
+        assert_eq!(run(None), Ok(()));
+
+        // Skipping an offset breaks the increments.
+        assert!(run(Some(104)).is_err());
+    }
+
+    // Generates params and keys, then proves and verifies `circuit` with KZG (SHPLONK). A witness
+    // whose lookup inputs are missing from the tables already fails in `create_proof`.
+    #[cfg(feature = "real-prover")]
+    fn prove_and_verify<C: Circuit<Fr>>(k: u32, circuit: C) -> bool {
+        use rand::rngs::OsRng;
+
+        prove_and_verify_with(&ParamsKZG::<Bn256>::setup(k, OsRng), circuit)
+    }
+
+    // Like `prove_and_verify`, with the keys generated for `params`.
+    #[cfg(feature = "real-prover")]
+    fn prove_and_verify_with<C: Circuit<Fr>>(params: &ParamsKZG<Bn256>, circuit: C) -> bool {
+        use halo2_base::halo2_proofs::{
+            halo2curves::bn256::G1Affine,
+            plonk::{create_proof, keygen_pk, keygen_vk, verify_proof},
+            poly::{
+                commitment::ParamsProver,
+                kzg::{
+                    commitment::KZGCommitmentScheme,
+                    multiopen::{ProverSHPLONK, VerifierSHPLONK},
+                    strategy::SingleStrategy,
+                },
+            },
+            transcript::{
+                Blake2bRead, Blake2bWrite, Challenge255, TranscriptReadBuffer,
+                TranscriptWriterBuffer,
+            },
+        };
+        use rand::rngs::OsRng;
+
+        let vk = keygen_vk(params, &circuit.without_witnesses()).unwrap();
+        let pk = keygen_pk(params, vk, &circuit.without_witnesses()).unwrap();
+        let mut transcript = Blake2bWrite::<_, G1Affine, Challenge255<_>>::init(vec![]);
+        let proved = create_proof::<KZGCommitmentScheme<Bn256>, ProverSHPLONK<'_, Bn256>, _, _, _, _>(
+            params,
+            &pk,
+            &[circuit],
+            &[&[]],
+            OsRng,
+            &mut transcript,
+        );
+        if proved.is_err() {
+            return false;
+        }
+        let proof = transcript.finalize();
+        let mut transcript = Blake2bRead::<_, G1Affine, Challenge255<_>>::init(&proof[..]);
+        verify_proof::<KZGCommitmentScheme<Bn256>, VerifierSHPLONK<'_, Bn256>, _, _, _>(
+            params.verifier_params(),
+            pk.get_vk(),
+            SingleStrategy::new(params),
+            &[&[]],
+            &mut transcript,
+        )
+        .is_ok()
+    }
+
+    #[cfg(feature = "real-prover")]
+    #[test]
+    fn test_regex_real_prover() {
+        assert!(prove_and_verify(6, offset_circuit(b"hello", None)));
+        // A character outside of `[a-z]` has no transition.
+        assert!(!prove_and_verify(6, offset_circuit(b"hello!", None)));
+        // A violated gate still yields a proof, which does not verify.
+        assert!(!prove_and_verify(6, offset_circuit(b"hello", Some(104))));
+    }
+
+    #[cfg(feature = "real-prover")]
+    #[test]
+    fn test_regex_real_prover_minimum_k() {
+        use crate::test_utils::params_for_k;
+        use rand::rngs::OsRng;
+
+        let regex_def = RegexDef::read_from_text("./test_regexes/regex_test_lookup.txt");
+        let k = RegexCheckConfig::<Fr>::minimum_k(&regex_def, MAX_STRING_LEN);
+        let circuit = TestRegexCheckCircuit::<Fr> {
+            characters: b"email was meant for @y.".to_vec(),
+            _marker: PhantomData,
+        };
+        MockProver::run(k, &circuit, vec![])
+            .unwrap()
+            .assert_satisfied();
+        // The `k` that passes `MockProver` also proves, with a larger setup downsized to it.
+        let srs = ParamsKZG::<Bn256>::setup(k + 1, OsRng);
+        assert!(prove_and_verify_with(
+            &params_for_k(&srs, k).unwrap(),
+            circuit
+        ));
+    }
+
+    // "αβ+" over UTF-16 code units, whose values do not fit into a byte.
+    fn alpha_beta_regex_def() -> RegexDef<u16> {
+        RegexDef {
+            state_lookup: HashMap::from([((0x3b1, 1), 2), ((0x3b2, 2), 3), ((0x3b2, 3), 3)]),
+            first_state_val: 1,
+            accepted_state_vals: vec![3],
+            largest_state_val: 3,
+        }
+    }
+
+    /// "αβ+" over 8 UTF-16 code units.
+    struct AlphaBetaRegex;
+
+    impl<F: PrimeField> TestConfig<F> for AlphaBetaRegex {
+        type Config = RegexCheckConfig<F, u16>;
+
+        fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+            RegexCheckConfig::configure(meta, alpha_beta_regex_def(), 8)
+        }
+
+        fn load(config: &Self::Config, layouter: &mut impl Layouter<F>) -> Result<(), Error> {
+            config.load(layouter)
+        }
+    }
+
+    #[test]
+    fn test_regex_wide_chars() {
+        let run = |characters: Vec<u16>| {
+            let circuit = TestCircuit::<Fr, AlphaBetaRegex>::new(move |config, region| {
+                let result = config.assign_values(region, &characters)?;
+                result.characters[0]
+                    .value()
+                    .map(|v| assert_eq!(*v, Fr::from(characters[0] as u64)));
+                Ok(())
+            });
+            MockProver::run(6, &circuit, vec![]).unwrap().verify()
+        };
+        for input in ["αβ", "αβββ"] {
+            assert_eq!(run(input.encode_utf16().collect()), Ok(()));
+        }
+        for input in ["α", "αα", "βα"] {
+            assert!(run(input.encode_utf16().collect()).is_err());
+        }
+        // The characters are not truncated to bytes: 0xb1 is the low byte of α.
+        assert!(run(vec![0xb1, 0xb2]).is_err());
+    }
+
+    /// `hello` over 8 characters, matched case-insensitively.
+    struct CaseFoldRegex;
+
+    impl<F: PrimeField> TestConfig<F> for CaseFoldRegex {
+        type Config = RegexCheckConfig<F>;
+
+        fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+            let regex_def = compile_regex("hello").unwrap().regex_def();
+            let mut config = RegexCheckConfig::configure(meta, regex_def, 8);
+            config.configure_case_fold(meta);
+            config
+        }
+
+        fn load(config: &Self::Config, layouter: &mut impl Layouter<F>) -> Result<(), Error> {
+            config.load(layouter)
+        }
+    }
+
+    #[test]
+    fn test_regex_case_fold() {
+        // Assigns `forged_folded` at row 0 instead of the honest folded character.
+        let run = |characters: &'static [u8], forged_folded: Option<u8>| {
+            let circuit = TestCircuit::<Fr, CaseFoldRegex>::new(move |config, region| {
+                let result = config.assign_values(region, characters)?;
+                assert_eq!(result.raw_characters.len(), 8);
+                if let Some(forged) = forged_folded {
+                    region.assign_advice(
+                        || "character",
+                        config.characters,
+                        0,
+                        || Value::known(Fr::from(forged as u64)),
+                    )?;
+                }
+                Ok(())
+            });
+            MockProver::run(9, &circuit, vec![]).unwrap().verify()
+        };
+        for input in [&b"HELLO"[..], b"hello", b"HeLlO"] {
+            assert_eq!(run(input, None), Ok(()));
+        }
+        assert!(run(b"HELLP", None).is_err());
+
+        // The folded character must be the lowercase of the raw one.
+        assert!(run(b"JELLO", Some(b'h')).is_err());
+    }
+
+    /// `[a-z]+@[a-z]+` with a length range, using fixed columns as selectors if `FIXED`.
+    struct SelectorRegex<const FIXED: bool>;
+
+    impl<F: PrimeField, const FIXED: bool> TestConfig<F> for SelectorRegex<FIXED> {
+        type Config = RegexCheckConfig<F>;
+
+        fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+            let regex_def = compile_regex("[a-z]+@[a-z]+").unwrap().regex_def();
+            let mut config = if FIXED {
+                RegexCheckConfig::configure_with_fixed_selectors(meta, regex_def, 16)
+            } else {
+                RegexCheckConfig::configure(meta, regex_def, 16)
+            };
+            config.configure_length_range(meta, 3, 12);
+            config
+        }
+
+        fn load(config: &Self::Config, layouter: &mut impl Layouter<F>) -> Result<(), Error> {
+            config.load(layouter)
+        }
+    }
+
+    #[test]
+    fn test_regex_fixed_selectors() {
+        fn verifies<const FIXED: bool>(characters: &[u8]) -> bool {
+            let characters = characters.to_vec();
+            let circuit = TestCircuit::<Fr, SelectorRegex<FIXED>>::new(move |config, region| {
+                config.assign_values(region, &characters)?;
+                Ok(())
+            });
+            MockProver::run(7, &circuit, vec![])
+                .unwrap()
+                .verify()
+                .is_ok()
+        }
+        for (input, accepted) in [
+            (&b"a@b"[..], true),
+            (b"alice@mail", true),
+            (b"a@", false),
+            (b"@mail", false),
+            (b"alice@mailserver", false),
+            (b"", false),
+        ] {
+            assert_eq!(verifies::<false>(input), accepted);
+            assert_eq!(verifies::<true>(input), accepted);
+        }
+
+        // The same constraints with one fixed column per complex selector.
+        let mut complex = ConstraintSystem::<Fr>::default();
+        let config = <SelectorRegex<false> as TestConfig<Fr>>::configure(&mut complex);
+        let mut fixed = ConstraintSystem::<Fr>::default();
+        let fixed_config = <SelectorRegex<true> as TestConfig<Fr>>::configure(&mut fixed);
+        assert_eq!(complex.degree(), fixed.degree());
+        assert_eq!(complex.minimum_rows(), fixed.minimum_rows());
+        assert_eq!(
+            (complex.num_selectors(), complex.num_fixed_columns()),
+            (3, 0)
+        );
+        assert_eq!((fixed.num_selectors(), fixed.num_fixed_columns()), (0, 3));
+        assert_ne!(
+            config.config_fingerprint(),
+            fixed_config.config_fingerprint()
+        );
+    }
+
+    #[test]
+    fn test_regex_full_length_input() {
+        let prefix = "email was meant for @";
+        let accepted = format!(
+            "{}{}.",
+            prefix,
+            "a".repeat(MAX_STRING_LEN - prefix.len() - 1)
+        );
+        assert_eq!(accepted.len(), MAX_STRING_LEN);
+        let circuit = TestRegexCheckCircuit::<Fr> {
+            characters: accepted.into_bytes(),
+            _marker: PhantomData,
+        };
+        let prover = MockProver::run(8, &circuit, vec![]).unwrap();
+        prover.assert_satisfied();
+
+        // Every transition is valid, but the input ends outside an accepted state.
+        let rejected = format!("{}{}", prefix, "a".repeat(MAX_STRING_LEN - prefix.len()));
+        let circuit = TestRegexCheckCircuit::<Fr> {
+            characters: rejected.into_bytes(),
+            _marker: PhantomData,
+        };
+        let prover = MockProver::run(8, &circuit, vec![]).unwrap();
+        assert!(prover.verify().is_err());
+    }
+
+    #[test]
+    fn test_regex_last_row_not_enabled() {
+        // Enables the row after a full-length input if `forge`.
+        let run = |characters: &'static [u8], forge: bool| {
+            let circuit = TestCircuit::<Fr, AnchoredRegex>::new(move |config, region| {
+                config.assign_values(region, characters)?;
+                if forge {
+                    // A self-loop from the last row into the row after the check, which no
+                    // selector covers, so that the enable flags never fall to 0.
+                    let state = config.derive_states(characters)[8];
+                    for (column, row, value) in [
+                        (config.char_enable, 8, 1),
+                        (config.is_padding, 8, 0),
+                        (config.characters, 8, b'a' as u64),
+                        (config.accepted_state, 8, 0),
+                        (config.state, 9, state),
+                    ] {
+                        region.assign_advice(
+                            || "forged",
+                            column,
+                            row,
+                            || Value::known(Fr::from(value)),
+                        )?;
+                    }
+                }
+                Ok(())
+            });
+            MockProver::run(6, &circuit, vec![]).unwrap().verify()
+        };
+        assert_eq!(run(b"aaaaaaab", false), Ok(()));
+        assert!(run(b"aaaaaaaa", false).is_err());
+        // Enabling the last row would skip the accept check of the rejected full-length input.
+        let failures = run(b"aaaaaaaa", true).unwrap_err();
+        assert_eq!(failures.len(), 1);
+        assert!(format!("{:?}", failures[0]).contains("The last row must not be enabled"));
+    }
+
+    #[derive(Default, Clone, Debug)]
+    struct TestLoadFromBytesCircuit<F: PrimeField> {
+        table_bytes: Vec<u8>,
+        checksum: u32,
+        _marker: PhantomData<F>,
+    }
+
+    impl<F: PrimeField> Circuit<F> for TestLoadFromBytesCircuit<F> {
+        type Config = RegexCheckConfig<F>;
+        type FloorPlanner = SimpleFloorPlanner;
+
+        fn without_witnesses(&self) -> Self {
+            self.clone()
+        }
+
+        fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+            <TestRegexCheckCircuit<F> as Circuit<F>>::configure(meta)
+        }
+
+        fn synthesize(
+            &self,
+            config: Self::Config,
+            mut layouter: impl Layouter<F>,
+        ) -> Result<(), Error> {
+            config.load_from_bytes(&mut layouter, &self.table_bytes, self.checksum)?;
+            layouter.assign_region(
+                || "regex",
+                |mut region| {
+                    config.assign_values(&mut region, b"email was meant for @y.")?;
+                    Ok(())
+                },
+            )?;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_regex_load_from_bytes() {
+        let table_bytes = std::fs::read("./test_regexes/regex_test_lookup.txt").unwrap();
+        let checksum = table_checksum(&table_bytes);
+        let circuit = TestLoadFromBytesCircuit::<Fr> {
+            table_bytes: table_bytes.clone(),
+            checksum,
+            _marker: PhantomData,
+        };
+        let prover = MockProver::run(8, &circuit, vec![]).unwrap();
+        prover.assert_satisfied();
+
+        // A damaged buffer fails the checksum.
+        let mut tampered = table_bytes.clone();
+        let len = tampered.len();
+        tampered[len - 2] = b'1';
+        let circuit = TestLoadFromBytesCircuit::<Fr> {
+            table_bytes: tampered.clone(),
+            checksum,
+            _marker: PhantomData,
+        };
+        assert!(MockProver::run(8, &circuit, vec![]).is_err());
+
+        // A different table with a valid checksum does not match the configuration.
+        let circuit = TestLoadFromBytesCircuit::<Fr> {
+            checksum: table_checksum(&tampered),
+            table_bytes: tampered,
+            _marker: PhantomData,
+        };
+        assert!(MockProver::run(8, &circuit, vec![]).is_err());
+        let other_config = {
+            let mut meta = ConstraintSystem::<Fr>::default();
+            RegexCheckConfig::configure(&mut meta, RegexDef::<u8>::default(), MAX_STRING_LEN)
+        };
+        let mut meta = ConstraintSystem::<Fr>::default();
+        let config = <TestRegexCheckCircuit<Fr> as Circuit<Fr>>::configure(&mut meta);
+        assert_ne!(
+            config.config_fingerprint(),
+            other_config.config_fingerprint()
+        );
+    }
+
+    /// `[a-z]+@[a-z]+` over 8 characters for a circuit of `2^K` rows, loaded with
+    /// [`RegexCheckConfig::load_checked`] if `CHECKED`.
+    struct TableRowsRegex<const K: u32, const CHECKED: bool>;
+
+    impl<F: PrimeField, const K: u32, const CHECKED: bool> TestConfig<F>
+        for TableRowsRegex<K, CHECKED>
+    {
+        type Config = RegexCheckConfig<F>;
+
+        fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+            let regex_def = compile_regex("[a-z]+@[a-z]+").unwrap().regex_def();
+            let mut config = RegexCheckConfig::configure(meta, regex_def, 8);
+            config.set_k(K);
+            config
+        }
+
+        fn load(config: &Self::Config, layouter: &mut impl Layouter<F>) -> Result<(), Error> {
+            if !CHECKED {
+                return config.load(layouter);
+            }
+            match config.load_checked(layouter) {
+                Ok(result) => result,
+                Err(err) => {
+                    assert!(matches!(err, RegexError::TableTooLargeForK { .. }));
+                    Err(Error::Synthesis)
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_regex_table_too_large_for_k() {
+        fn table_rows_circuit<const K: u32, const CHECKED: bool>(
+        ) -> TestCircuit<Fr, TableRowsRegex<K, CHECKED>> {
+            TestCircuit::new(|config, region| {
+                config.assign_values(region, b"me@x")?;
+                Ok(())
+            })
+        }
+        let mut meta = ConstraintSystem::<Fr>::default();
+        let config = <TableRowsRegex<6, false> as TestConfig<Fr>>::configure(&mut meta);
+        let table_rows = RegexCheckConfig::<Fr>::estimate_cost(&config.regex_def, 8).table_rows;
+        // 2^6 rows, minus the blinding rows.
+        let capacity = 64 - meta.blinding_factors() - 1;
+        assert!(table_rows > capacity);
+        assert_eq!(
+            config.check_table_rows(6),
+            Err(RegexError::TableTooLargeForK {
+                rows: table_rows,
+                capacity
+            })
+        );
+        assert_eq!(config.check_table_rows(7), Ok(()));
+        // The blinding rows are those of the constraint system with the optional columns.
+        let mut config = config;
+        config.configure_state_deltas(&mut meta);
+        config.configure_case_fold(&mut meta);
+        assert_eq!(config.blinding_factors, meta.blinding_factors());
+
+        let circuit = table_rows_circuit::<6, false>();
+        match MockProver::run(6, &circuit, vec![]) {
+            Err(Error::Transcript(err)) => assert_eq!(
+                err.get_ref()
+                    .and_then(|err| err.downcast_ref::<RegexError>()),
+                Some(&RegexError::TableTooLargeForK {
+                    rows: table_rows,
+                    capacity
+                })
+            ),
+            _ => panic!("the table size error is not passed through"),
+        }
+        // `load_checked` fails with the `RegexError` instead, which the circuit asserts.
+        let circuit = table_rows_circuit::<6, true>();
+        assert!(matches!(
+            MockProver::run(6, &circuit, vec![]),
+            Err(Error::Synthesis)
+        ));
+        let circuit = table_rows_circuit::<7, false>();
+        MockProver::run(7, &circuit, vec![])
+            .unwrap()
+            .assert_satisfied();
+        let circuit = table_rows_circuit::<7, true>();
+        MockProver::run(7, &circuit, vec![])
+            .unwrap()
+            .assert_satisfied();
+    }
+
+    #[test]
+    fn test_regex_shape_compatible() {
+        let configure = |pattern: &str, max_chars_size: usize, deltas: bool| {
+            let mut meta = ConstraintSystem::<Fr>::default();
+            let regex_def = compile_regex(pattern).unwrap().regex_def();
+            let mut config = RegexCheckConfig::configure(&mut meta, regex_def, max_chars_size);
+            config.configure_length_range(&mut meta, 1, max_chars_size);
+            if deltas {
+                config.configure_state_deltas(&mut meta);
+            }
+            (config, meta)
+        };
+        let (config, meta) = configure("[a-z]+@mail", 16, false);
+        let (other_config, other_meta) = configure("(cat|dog)s?", 16, false);
+        assert!(config.is_shape_compatible(&other_config));
+        assert_ne!(
+            config.config_fingerprint(),
+            other_config.config_fingerprint()
+        );
+        // The constraint systems are identical; only the loaded tables differ.
+        assert_eq!(
+            format!("{:?}", meta.pinned()),
+            format!("{:?}", other_meta.pinned())
+        );
+
+        assert!(!config.is_shape_compatible(&configure("[a-z]+@mail", 32, false).0));
+        assert!(!config.is_shape_compatible(&configure("[a-z]+@mail", 16, true).0));
+    }
+
+    // "cat" and "dog" end in different accepting states: 4 and 7.
+    fn cat_dog_regex_def() -> RegexDef {
+        let state_lookup = HashMap::from([
+            ((b'c', 1), 2),
+            ((b'a', 2), 3),
+            ((b't', 3), 4),
+            ((b'd', 1), 5),
+            ((b'o', 5), 6),
+            ((b'g', 6), 7),
+        ]);
+        RegexDef {
+            state_lookup,
+            first_state_val: 1,
+            accepted_state_vals: vec![4, 7],
+            largest_state_val: 7,
+        }
+    }
+
+    /// "cat|dog" over 8 characters, accepted in two different states.
+    struct CatDogRegex;
+
+    impl<F: PrimeField> TestConfig<F> for CatDogRegex {
+        type Config = RegexCheckConfig<F>;
+
+        fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+            RegexCheckConfig::configure(meta, cat_dog_regex_def(), 8)
+        }
+
+        fn load(config: &Self::Config, layouter: &mut impl Layouter<F>) -> Result<(), Error> {
+            config.load(layouter)
+        }
+    }
+
+    #[test]
+    fn test_regex_accepted_state() {
+        let run = |characters: &'static [u8], accepted_state: u64| {
+            let circuit = TestCircuit::<Fr, CatDogRegex>::new(move |config, region| {
+                let result = config.assign_values(region, characters)?;
+                result
+                    .accepted_state
+                    .value()
+                    .map(|v| assert_eq!(*v, Fr::from(accepted_state)));
+                Ok(())
+            });
+            MockProver::run(6, &circuit, vec![]).unwrap().verify()
+        };
+        assert_eq!(run(b"cat", 4), Ok(()));
+        assert_eq!(run(b"dog", 7), Ok(()));
+        // An empty input exits in the start state, which is not accepting.
+        assert!(run(b"", 1).is_err());
+    }
+
+    const PIPELINE_STRING_LEN: usize = 32;
+
+    fn header_pipeline() -> crate::RegexPipeline {
+        crate::split_regex(r"from:[a-z]+@[a-z]+\.com\r\nto:[a-z]+@[a-z]+\.com\r\n", 20).unwrap()
+    }
+
+    #[derive(Default, Clone, Debug)]
+    struct TestPipelineStageCircuit<F: PrimeField, const STAGE: usize> {
+        characters: Vec<u8>,
+        _marker: PhantomData<F>,
+    }
+
+    impl<F: PrimeField, const STAGE: usize> Circuit<F> for TestPipelineStageCircuit<F, STAGE> {
+        type Config = (RegexCheckConfig<F>, Column<Instance>);
+        type FloorPlanner = SimpleFloorPlanner;
+
+        fn without_witnesses(&self) -> Self {
+            Self {
+                characters: vec![],
+                _marker: PhantomData,
+            }
+        }
+
+        fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+            let regex_def = header_pipeline().regex_defs().swap_remove(STAGE);
+            let instance = meta.instance_column();
+            meta.enable_equality(instance);
+            (
+                RegexCheckConfig::configure(meta, regex_def, PIPELINE_STRING_LEN),
+                instance,
+            )
+        }
+
+        fn synthesize(
+            &self,
+            (config, instance): Self::Config,
+            mut layouter: impl Layouter<F>,
+        ) -> Result<(), Error> {
+            config.load(&mut layouter)?;
+            let result = layouter.assign_region(
+                || "regex",
+                |mut region| config.assign_values(&mut region, &self.characters),
+            )?;
+            config.expose_states(&mut layouter, &result, instance, 0)
+        }
+    }
+
+    #[test]
+    fn test_regex_pipeline() {
+        let pipeline = header_pipeline();
+        assert_eq!(pipeline.stages.len(), 2);
+        let input = b"from:alice@mail.com\r\nto:bob@mail.com\r\n";
+        let lengths = pipeline.split_input(input).unwrap();
+        let (first, second) = input.split_at(lengths[0]);
+        let states = pipeline.link_states(input, &lengths);
+        assert!(pipeline.check_links(&states));
+        let instances: Vec<Vec<Fr>> = states
+            .iter()
+            .map(|stage_states| stage_states.map(Fr::from).to_vec())
+            .collect();
+
+        let circuit = TestPipelineStageCircuit::<Fr, 0> {
+            characters: first.to_vec(),
+            _marker: PhantomData,
+        };
+        let prover = MockProver::run(8, &circuit, vec![instances[0].clone()]).unwrap();
+        prover.assert_satisfied();
+        let circuit = TestPipelineStageCircuit::<Fr, 1> {
+            characters: second.to_vec(),
+            _marker: PhantomData,
+        };
+        let prover = MockProver::run(8, &circuit, vec![instances[1].clone()]).unwrap();
+        prover.assert_satisfied();
+
+        // Handing over an exit state the second stage did not reach fails.
+        let tampered = vec![instances[1][0], instances[1][1] + Fr::from(1)];
+        let prover = MockProver::run(8, &circuit, vec![tampered]).unwrap();
+        assert!(prover.verify().is_err());
+    }
+
+    #[test]
+    fn test_regex_estimate_cost() {
+        let regex_def = RegexDef::read_from_text("./test_regexes/regex_test_lookup.txt");
+        let estimate = RegexCheckConfig::<Fr>::estimate_cost(&regex_def, MAX_STRING_LEN);
+        assert_eq!(estimate.advice_rows, MAX_STRING_LEN + 1);
+        assert_eq!(estimate.lookup_rows, MAX_STRING_LEN);
+        // 207 transitions plus the dummy row.
+        assert_eq!(estimate.table_rows, 208);
+
+        // The smallest power of two covering the estimate fits; the next smaller one does not.
+        let rows = estimate.advice_rows.max(estimate.table_rows);
+        let k = usize::BITS - (rows - 1).leading_zeros();
+        let characters: Vec<u8> = "email was meant for @y.".chars().map(|c| c as u8).collect();
+        let circuit = TestRegexCheckCircuit::<Fr> {
+            characters,
+            _marker: PhantomData,
+        };
+        let prover = MockProver::run(k, &circuit, vec![]).unwrap();
+        prover.assert_satisfied();
+        assert!(MockProver::run(k - 1, &circuit, vec![]).is_err());
+    }
+
+    // 252 characters need 253 advice rows: 2^8 rows would suffice without the blinding rows.
+    const LONG_STRING_LEN: usize = 252;
+
+    crate::regex_circuit!(
+        LongRegexCheckCircuit,
+        RegexDef::read_from_text("./test_regexes/regex_test_lookup.txt"),
+        LONG_STRING_LEN
+    );
+
+    #[test]
+    fn test_regex_minimum_k() {
+        let regex_def = RegexDef::read_from_text("./test_regexes/regex_test_lookup.txt");
+        let estimate = RegexCheckConfig::<Fr>::estimate_cost(&regex_def, LONG_STRING_LEN);
+        let rows = estimate.advice_rows.max(estimate.table_rows);
+        let naive_k = usize::BITS - (rows - 1).leading_zeros();
+        let k = RegexCheckConfig::<Fr>::minimum_k(&regex_def, LONG_STRING_LEN);
+        assert_eq!(naive_k, 8);
+        assert_eq!(k, 9);
+
+        let circuit = LongRegexCheckCircuit::<Fr>::new(b"email was meant for @y.".to_vec());
+        assert!(MockProver::run(naive_k, &circuit, vec![]).is_err());
+        let prover = MockProver::run(k, &circuit, vec![]).unwrap();
+        prover.assert_satisfied();
+    }
+
+    // Whether the two tables are the same DFA up to a renaming of states.
+    fn is_isomorphic(a: &RegexDef, b: &RegexDef) -> bool {
+        let outgoing = |regex_def: &RegexDef| {
+            let mut edges = HashMap::<u64, Vec<(u8, u64)>>::new();
+            for ((char, prev_state), next_state) in regex_def.state_lookup.iter() {
+                edges
+                    .entry(*prev_state)
+                    .or_default()
+                    .push((*char, *next_state));
+            }
+            for list in edges.values_mut() {
+                list.sort();
+            }
+            edges
+        };
+        let (edges_a, edges_b) = (outgoing(a), outgoing(b));
+        let mut a_to_b = HashMap::from([(a.first_state_val, b.first_state_val)]);
+        let mut b_to_a = HashMap::from([(b.first_state_val, a.first_state_val)]);
+        let mut stack = vec![(a.first_state_val, b.first_state_val)];
+        while let Some((state_a, state_b)) = stack.pop() {
+            if a.accepted_state_vals.contains(&state_a) != b.accepted_state_vals.contains(&state_b)
+            {
+                return false;
+            }
+            let list_a = edges_a.get(&state_a).cloned().unwrap_or_default();
+            let list_b = edges_b.get(&state_b).cloned().unwrap_or_default();
+            if list_a.len() != list_b.len() {
+                return false;
+            }
+            for ((char_a, next_a), (char_b, next_b)) in list_a.into_iter().zip(list_b) {
+                if char_a != char_b {
+                    return false;
+                }
+                match (a_to_b.get(&next_a), b_to_a.get(&next_b)) {
+                    (Some(mapped_b), Some(mapped_a))
+                        if *mapped_b == next_b && *mapped_a == next_a => {}
+                    (None, None) => {
+                        a_to_b.insert(next_a, next_b);
+                        b_to_a.insert(next_b, next_a);
+                        stack.push((next_a, next_b));
+                    }
+                    _ => return false,
+                }
+            }
+        }
+        a.state_lookup.len() == b.state_lookup.len()
+    }
+
+    #[test]
+    fn test_regex_lookup_file_matches_pattern() {
+        let regex_def = RegexDef::read_from_text("./test_regexes/regex_test_lookup.txt");
+        let compiled = compile_regex(TEST_REGEX).unwrap().regex_def();
+        assert!(is_isomorphic(&regex_def, &compiled));
+        // A different pattern must not be considered equivalent.
+        let other = compile_regex(&TEST_REGEX.replace(" and ", " or "))
+            .unwrap()
+            .regex_def();
+        assert!(!is_isomorphic(&regex_def, &other));
+    }
+
+    #[cfg(feature = "regex-automata")]
+    crate::regex_circuit!(
+        DenseDfaCircuit,
+        {
+            use regex_automata::{dfa::dense, SyntaxConfig};
+            let dfa = dense::Builder::new()
+                .configure(dense::Config::new().anchored(true))
+                .syntax(SyntaxConfig::new().unicode(false).utf8(false))
+                .build(r"[a-z]+@[a-z]+\.com")
+                .unwrap();
+            crate::CompiledRegex::from_dense_dfa(&dfa).regex_def()
+        },
+        MAX_STRING_LEN
+    );
+
+    #[cfg(feature = "regex-automata")]
+    #[test]
+    fn test_regex_from_dense_dfa() {
+        let circuit = DenseDfaCircuit::<Fr>::new(b"alice@mail.com".to_vec());
+        let prover = MockProver::run(8, &circuit, vec![]).unwrap();
+        prover.assert_satisfied();
+
+        let circuit = DenseDfaCircuit::<Fr>::new(b"alice@mail.org".to_vec());
+        let prover = MockProver::run(8, &circuit, vec![]).unwrap();
+        assert!(prover.verify().is_err());
+    }
+
     // $ cargo test --release --all-features print_range_check_1
     #[cfg(feature = "dev-graph")]
     #[test]
@@ -1,5 +1,5 @@
 use halo2_base::halo2_proofs::{
-    circuit::{AssignedCell, Layouter, Region, SimpleFloorPlanner, Value},
+    circuit::{AssignedCell, Cell, Layouter, Region, SimpleFloorPlanner, Value},
     plonk::{
         Advice, Assigned, Circuit, Column, ConstraintSystem, Constraints, Error, Expression,
         Instance, Selector, TableColumn,
@@ -19,13 +19,17 @@ use std::{
 };
 
 use crate::table::TransitionTableConfig;
-use crate::{AssignedRegexResult, RegexCheckConfig, RegexDef};
+use crate::{AssignedRegexResult, RegexCheckConfig, RegexDef, RegexError};
 
 #[derive(Debug, Clone, Default)]
 pub struct SubstrDef {
+    /// The maximum number of captured characters. A longer capture fails at witness generation
+    /// and does not satisfy the circuit.
     pub max_length: usize,
     pub min_position: u64,
     pub max_position: u64,
+    /// The name of the capture group, used by host-side tooling only.
+    pub name: Option<String>,
     valid_state_transitions: HashSet<(u64, u64)>,
 }
 
@@ -40,10 +44,159 @@ impl SubstrDef {
             max_length,
             min_position,
             max_position,
+            name: None,
             valid_state_transitions,
         }
     }
 
+    pub fn with_name(mut self, name: &str) -> Self {
+        self.name = Some(name.to_string());
+        self
+    }
+
+    /// The `(prev_state, next_state)` transitions whose characters are extracted.
+    pub fn valid_state_transitions(&self) -> &HashSet<(u64, u64)> {
+        &self.valid_state_transitions
+    }
+
+    /// Checks that no two of `substr_defs` share a transition, which would extract the characters
+    /// it consumes into both substrings; see [`crate::CompiledRegex::ambiguous_captures`].
+    pub fn check_disjoint(substr_defs: &[SubstrDef]) -> Result<(), RegexError> {
+        for first in 0..substr_defs.len() {
+            for second in first + 1..substr_defs.len() {
+                if !substr_defs[first]
+                    .valid_state_transitions
+                    .is_disjoint(&substr_defs[second].valid_state_transitions)
+                {
+                    return Err(RegexError::AmbiguousCaptures { first, second });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks the definition against `table`: every transition it names must be in the table,
+    /// on some character, and together they must form one connected region of states, since
+    /// only a single run of the capture is extracted. An empty definition is valid.
+    pub fn validate(&self, table: &RegexDef) -> Result<(), RegexError> {
+        let table_transitions: HashSet<(u64, u64)> = table
+            .state_lookup
+            .iter()
+            .map(|((_, prev_state), next_state)| (*prev_state, *next_state))
+            .collect();
+        let mut transitions: Vec<(u64, u64)> =
+            self.valid_state_transitions.iter().copied().collect();
+        transitions.sort_unstable();
+        if let Some((prev_state, next_state)) = transitions
+            .iter()
+            .find(|transition| !table_transitions.contains(transition))
+        {
+            return Err(RegexError::MissingTransition {
+                prev_state: *prev_state,
+                next_state: *next_state,
+            });
+        }
+        // Grows the region of the lowest transition by the transitions sharing a state with it,
+        // regardless of their direction.
+        let mut region: HashSet<u64> = transitions
+            .first()
+            .map(|(prev_state, next_state)| [*prev_state, *next_state])
+            .into_iter()
+            .flatten()
+            .collect();
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (prev_state, next_state) in transitions.iter() {
+                if region.contains(prev_state) != region.contains(next_state) {
+                    region.insert(*prev_state);
+                    region.insert(*next_state);
+                    changed = true;
+                }
+            }
+        }
+        match transitions
+            .iter()
+            .find(|(prev_state, _)| !region.contains(prev_state))
+        {
+            Some((prev_state, next_state)) => Err(RegexError::DisconnectedCapture {
+                prev_state: *prev_state,
+                next_state: *next_state,
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// Checks that the substrings of `substr_defs`, concatenated in this order like with
+    /// [`SubstrMatchConfig::concat_substrs`], can be split apart again at `delimiter`: each
+    /// capture but the last is never empty, ends with the delimiter and contains it nowhere
+    /// else, so the captures form a prefix-free code. Otherwise, e.g. for `(a+)(a+)`, the
+    /// concatenation may not tell where one capture ends. Errors name the capture by its index
+    /// in `substr_defs`.
+    pub fn check_concat(
+        substr_defs: &[&SubstrDef],
+        table: &RegexDef,
+        delimiter: u8,
+    ) -> Result<(), RegexError> {
+        let num_delimited = substr_defs.len().saturating_sub(1);
+        for (group, substr_def) in substr_defs.iter().enumerate().take(num_delimited) {
+            let captured = &substr_def.valid_state_transitions;
+            let has_transition = |state: u64, in_capture: bool| {
+                table
+                    .state_lookup
+                    .iter()
+                    .any(|((_, prev_state), next_state)| {
+                        *prev_state == state
+                            && captured.contains(&(*prev_state, *next_state)) == in_capture
+                    })
+            };
+            // An accepted state reachable without a capture transition accepts an input that
+            // captures nothing.
+            let mut reachable = HashSet::from([table.first_state_val]);
+            let mut stack = vec![table.first_state_val];
+            while let Some(state) = stack.pop() {
+                if table.accepted_state_vals.contains(&state) {
+                    return Err(RegexError::OptionalCapture { group });
+                }
+                for ((_, prev_state), next_state) in table.state_lookup.iter() {
+                    if *prev_state == state
+                        && !captured.contains(&(*prev_state, *next_state))
+                        && reachable.insert(*next_state)
+                    {
+                        stack.push(*next_state);
+                    }
+                }
+            }
+            // The capture must end right after the delimiter and continue after any other
+            // character.
+            let mut transitions: Vec<(u64, u64, u8)> = table
+                .state_lookup
+                .iter()
+                .filter(|((_, prev_state), next_state)| {
+                    captured.contains(&(*prev_state, **next_state))
+                })
+                .map(|((char, prev_state), next_state)| (*prev_state, *next_state, *char))
+                .collect();
+            transitions.sort_unstable();
+            for (prev_state, next_state, char) in transitions {
+                let delimited = if char == delimiter {
+                    !has_transition(next_state, true)
+                } else {
+                    !table.accepted_state_vals.contains(&next_state)
+                        && !has_transition(next_state, false)
+                };
+                if !delimited {
+                    return Err(RegexError::UndelimitedCapture {
+                        group,
+                        prev_state,
+                        next_state,
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+
     pub fn read_from_text(file_path: &str) -> Self {
         let file = File::open(file_path).unwrap();
         let reader = BufReader::new(file);
@@ -72,12 +225,12 @@ impl SubstrDef {
                 valid_state_transitions.insert((elements[0], elements[1]));
             };
         }
-        Self {
+        Self::new(
             max_length,
             min_position,
             max_position,
             valid_state_transitions,
-        }
+        )
     }
 }
 
@@ -91,6 +244,20 @@ pub struct AssignedSubstrsResult<'a, F: PrimeField> {
     pub substrs_length: Vec<AssignedValue<'a, F>>,
 }
 
+/// The order in which [`SubstrMatchConfig::pack_substrs`] packs the bytes of a substring into a
+/// field element.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ByteOrder {
+    /// The first byte is the least significant, `sum(bytes[i] * 256^i)`, so the value does not
+    /// depend on the maximum length of the substring.
+    #[default]
+    LittleEndian,
+    /// The first byte is the most significant, `sum(bytes[i] * 256^(max_length - 1 - i))` over
+    /// the bytes padded with zeros to the maximum length, like a left-aligned `bytesN` of that
+    /// length in Solidity.
+    BigEndian,
+}
+
 #[derive(Debug, Clone)]
 pub struct SubstrMatchConfig<F: PrimeField> {
     regex_config: RegexCheckConfig<F>,
@@ -101,9 +268,13 @@ pub struct SubstrMatchConfig<F: PrimeField> {
     substr_states: Vec<Column<Advice>>,
     is_valid: Vec<Column<Advice>>,
     selectors: Vec<Selector>,
+    instances: Vec<Column<Instance>>,
+    byte_order: ByteOrder,
 }
 
 impl<F: PrimeField> SubstrMatchConfig<F> {
+    /// Configures the extraction of `substr_defs`. Captures sharing a transition are not
+    /// rejected here, see [`Self::configure_checked`].
     pub fn configure(
         meta: &mut ConstraintSystem<F>,
         regex_def: RegexDef,
@@ -174,6 +345,47 @@ impl<F: PrimeField> SubstrMatchConfig<F> {
             substr_states,
             is_valid,
             selectors,
+            instances: vec![],
+            byte_order: ByteOrder::default(),
+        }
+    }
+
+    /// Like [`Self::configure`], but fails with [`RegexError::AmbiguousCaptures`] if two of
+    /// `substr_defs` share a transition, see [`SubstrDef::check_disjoint`].
+    pub fn configure_checked(
+        meta: &mut ConstraintSystem<F>,
+        regex_def: RegexDef,
+        max_chars_size: usize,
+        range_gate: RangeConfig<F>,
+        substr_defs: Vec<SubstrDef>,
+    ) -> Result<Self, RegexError> {
+        SubstrDef::check_disjoint(&substr_defs)?;
+        Ok(Self::configure(
+            meta,
+            regex_def,
+            max_chars_size,
+            range_gate,
+            substr_defs,
+        ))
+    }
+
+    /// Sets the byte order of [`Self::pack_substrs`], little-endian by default, e.g. to
+    /// [`ByteOrder::BigEndian`] for a verifier that reads the substrings as `bytesN`.
+    pub fn set_byte_order(&mut self, byte_order: ByteOrder) {
+        self.byte_order = byte_order;
+    }
+
+    /// Adds one instance column per substring definition for [`Self::expose_substrs`].
+    pub fn configure_instances(&mut self, meta: &mut ConstraintSystem<F>) {
+        for substr_def in self.substr_defs.iter() {
+            assert!(
+                substr_def.max_length * 8 <= F::CAPACITY as usize,
+                "a substring of {} bytes does not fit into one field element",
+                substr_def.max_length
+            );
+            let column = meta.instance_column();
+            meta.enable_equality(column);
+            self.instances.push(column);
         }
     }
 
@@ -181,6 +393,17 @@ impl<F: PrimeField> SubstrMatchConfig<F> {
         &self,
         ctx: &mut Context<'v, F>,
         characters: &[u8],
+    ) -> Result<AssignedSubstrsResult<'a, F>, Error> {
+        self.assign_substrs(ctx, characters, true)
+    }
+
+    // With `check_max_length`, a capture longer than its `max_length` fails at witness
+    // generation rather than only in the circuit; tests turn it off to reach the circuit.
+    fn assign_substrs<'v: 'a, 'a>(
+        &self,
+        ctx: &mut Context<'v, F>,
+        characters: &[u8],
+        check_max_length: bool,
     ) -> Result<AssignedSubstrsResult<'a, F>, Error> {
         let regex_result = self
             .regex_config
@@ -232,11 +455,21 @@ impl<F: PrimeField> SubstrMatchConfig<F> {
                             continue;
                         } else {
                             substr_end = position;
+                            in_matching = false;
                             break;
                         }
                     }
                 }
             }
+            if in_matching {
+                // The capture runs until the end of the input or of the allowed positions.
+                substr_end = (substr_def.max_position + 1).min(characters.len() as u64);
+            }
+            if let Some(start) = substr_start {
+                if check_max_length && substr_end - start > substr_def.max_length as u64 {
+                    return Err(Error::Synthesis);
+                }
+            }
             let substr_start = match substr_start {
                 Some(x) => x,
                 None => all_max_len as u64,
@@ -262,6 +495,7 @@ impl<F: PrimeField> SubstrMatchConfig<F> {
             //     substr_max_len - substr_positions.len()
             // ]);
             let mut offset = 0;
+            let mut assigned_count = gate.load_zero(ctx);
             for position in (substr_def.min_position as usize)..=(substr_def.max_position as usize)
             {
                 let assigned_c = &assigned_characters[position];
@@ -420,6 +654,11 @@ impl<F: PrimeField> SubstrMatchConfig<F> {
                     QuantumCell::Existing(&assigned_c),
                 );
                 assigned_chars.push(substr_char);
+                assigned_count = gate.add(
+                    ctx,
+                    QuantumCell::Existing(&assigned_count),
+                    QuantumCell::Existing(&is_valid_flag),
+                );
                 // assigned_len = gate.add(
                 //     ctx,
                 //     QuantumCell::Existing(&assigned_len),
@@ -427,6 +666,16 @@ impl<F: PrimeField> SubstrMatchConfig<F> {
                 // );
                 offset += 1;
             }
+            {
+                // The number of captured characters is at most max_length.
+                let is_within = self.range().is_less_than(
+                    ctx,
+                    QuantumCell::Existing(&assigned_count),
+                    QuantumCell::Constant(F::from(substr_def.max_length as u64 + 1)),
+                    64,
+                );
+                gate.assert_is_const(ctx, &is_within, F::from(1));
+            }
             let assigned_chars = self.shift_variable(ctx, &assigned_chars, &assigned_start);
             substrs_bytes.push(assigned_chars[0..substr_def.max_length].to_vec());
             let assigned_len = gate.sub(
@@ -447,6 +696,172 @@ impl<F: PrimeField> SubstrMatchConfig<F> {
         Ok(result)
     }
 
+    /// Packs the bytes of each substring into one field element in the configured
+    /// [`ByteOrder`], by default `sum(bytes[i] * 256^i)`. An empty substring packs to 0.
+    pub fn pack_substrs<'v: 'a, 'a>(
+        &self,
+        ctx: &mut Context<'v, F>,
+        result: &AssignedSubstrsResult<'a, F>,
+    ) -> Vec<AssignedValue<'a, F>> {
+        let gate = self.gate();
+        let mut packed_substrs = Vec::new();
+        for substr_bytes in result.substrs_bytes.iter() {
+            let mut packed = gate.load_zero(ctx);
+            // Horner's rule, from the most significant byte.
+            let bytes: Vec<&AssignedValue<'a, F>> = match self.byte_order {
+                ByteOrder::LittleEndian => substr_bytes.iter().rev().collect(),
+                ByteOrder::BigEndian => substr_bytes.iter().collect(),
+            };
+            for byte in bytes {
+                packed = gate.mul_add(
+                    ctx,
+                    QuantumCell::Existing(&packed),
+                    QuantumCell::Constant(F::from(256)),
+                    QuantumCell::Existing(byte),
+                );
+            }
+            packed_substrs.push(packed);
+        }
+        packed_substrs
+    }
+
+    /// Concatenates the substrings of `group_ids` in order, e.g. a value captured in fragments
+    /// around a delimiter. The captured bytes of each substring directly follow those of the
+    /// previous one, and the result is padded with zeros to the sum of their maximum lengths.
+    pub fn concat_substrs<'v: 'a, 'a>(
+        &self,
+        ctx: &mut Context<'v, F>,
+        result: &AssignedSubstrsResult<'a, F>,
+        group_ids: &[usize],
+    ) -> Result<Vec<AssignedValue<'a, F>>, Error> {
+        if group_ids
+            .iter()
+            .any(|group_id| *group_id >= result.substrs_bytes.len())
+        {
+            return Err(Error::Synthesis);
+        }
+        let gate = self.gate();
+        let mut concatenated = vec![];
+        let mut length = gate.load_zero(ctx);
+        for group_id in group_ids.iter() {
+            let substr_bytes = &result.substrs_bytes[*group_id];
+            let total_len = concatenated.len() + substr_bytes.len();
+            // Rotating by `total_len - length` moves the substring right by `length`; the bytes
+            // that wrap around are zero padding, since `length` is at most the previous maximum.
+            let mut padded = substr_bytes.clone();
+            padded.extend((0..concatenated.len()).map(|_| gate.load_zero(ctx)));
+            let shift = gate.sub(
+                ctx,
+                QuantumCell::Constant(F::from(total_len as u64)),
+                QuantumCell::Existing(&length),
+            );
+            let shifted = self.shift_variable(ctx, &padded, &shift);
+            concatenated.extend((0..substr_bytes.len()).map(|_| gate.load_zero(ctx)));
+            concatenated = concatenated
+                .iter()
+                .zip(shifted.iter())
+                .map(|(byte, shifted_byte)| {
+                    gate.add(
+                        ctx,
+                        QuantumCell::Existing(byte),
+                        QuantumCell::Existing(shifted_byte),
+                    )
+                })
+                .collect();
+            length = gate.add(
+                ctx,
+                QuantumCell::Existing(&length),
+                QuantumCell::Existing(&result.substrs_length[*group_id]),
+            );
+        }
+        Ok(concatenated)
+    }
+
+    /// The decimal value of the captured digits `substr_bytes[..length]`, e.g. a substring of
+    /// [`Self::match_substrs`] and its length. Every captured byte is constrained to be an ASCII
+    /// digit. Leading zeros are allowed and an empty capture is 0. Fails if `substr_bytes` can
+    /// hold more digits than fit into a field element without overflowing.
+    pub fn decimal_value<'v: 'a, 'a>(
+        &self,
+        ctx: &mut Context<'v, F>,
+        substr_bytes: &[AssignedValue<'a, F>],
+        length: &AssignedValue<'a, F>,
+    ) -> Result<AssignedValue<'a, F>, Error> {
+        // 10^n < 2^CAPACITY as long as n <= CAPACITY * log10(2).
+        if substr_bytes.len() > F::CAPACITY as usize * 30103 / 100000 {
+            return Err(Error::Synthesis);
+        }
+        let gate = self.gate();
+        let range = self.range();
+        let zero = gate.load_zero(ctx);
+        let mut value = gate.load_zero(ctx);
+        for (idx, byte) in substr_bytes.iter().enumerate() {
+            let is_captured = range.is_less_than(
+                ctx,
+                QuantumCell::Constant(F::from(idx as u64)),
+                QuantumCell::Existing(length),
+                64,
+            );
+            let digit = gate.sub(
+                ctx,
+                QuantumCell::Existing(byte),
+                QuantumCell::Constant(F::from(b'0' as u64)),
+            );
+            let digit = gate.select(
+                ctx,
+                QuantumCell::Existing(&digit),
+                QuantumCell::Existing(&zero),
+                QuantumCell::Existing(&is_captured),
+            );
+            // A byte below b'0' wraps around and fails the range check as well.
+            range.check_less_than_safe(ctx, &digit, 10);
+            let shifted = gate.mul_add(
+                ctx,
+                QuantumCell::Existing(&value),
+                QuantumCell::Constant(F::from(10)),
+                QuantumCell::Existing(&digit),
+            );
+            value = gate.select(
+                ctx,
+                QuantumCell::Existing(&shifted),
+                QuantumCell::Existing(&value),
+                QuantumCell::Existing(&is_captured),
+            );
+        }
+        Ok(value)
+    }
+
+    /// Constrains the packed substrings (cells of [`Self::pack_substrs`]) to the first row of
+    /// their instance columns, which must have been added by [`Self::configure_instances`].
+    pub fn expose_substrs(
+        &self,
+        layouter: &mut impl Layouter<F>,
+        packed_substrs: &[Cell],
+    ) -> Result<(), Error> {
+        let group_ids: Vec<usize> = (0..self.instances.len()).collect();
+        self.expose_substrs_selective(layouter, packed_substrs, &group_ids)
+    }
+
+    /// Like [`Self::expose_substrs`], but only the groups in `group_ids` are made public. The
+    /// other groups are still constrained by the substring masks; their instance columns stay empty.
+    pub fn expose_substrs_selective(
+        &self,
+        layouter: &mut impl Layouter<F>,
+        packed_substrs: &[Cell],
+        group_ids: &[usize],
+    ) -> Result<(), Error> {
+        if packed_substrs.len() != self.instances.len() {
+            return Err(Error::Synthesis);
+        }
+        for group_id in group_ids.iter() {
+            if *group_id >= self.instances.len() {
+                return Err(Error::Synthesis);
+            }
+            layouter.constrain_instance(packed_substrs[*group_id], self.instances[*group_id], 0)?;
+        }
+        Ok(())
+    }
+
     pub fn load(&self, layouter: &mut impl Layouter<F>) -> Result<(), Error> {
         self.regex_config.load(layouter)?;
         layouter.assign_table(
@@ -715,6 +1130,7 @@ mod test {
         plonk::{Any, Circuit},
     };
     use halo2_base::{gates::range::RangeStrategy::Vertical, ContextParams, SKIP_FIRST_PASS};
+    use rand::{rngs::StdRng, Rng, SeedableRng};
 
     use super::*;
     use crate::table::RegexDef;
@@ -780,7 +1196,7 @@ mod test {
             config: Self::Config,
             mut layouter: impl Layouter<F>,
         ) -> Result<(), Error> {
-            // test regex: "email was meant for @(a|b|c|d|e|f|g|h|i|j|k|l|m|n|o|p|q|r|s|t|u|v|w|x|y|z|A|B|C|D|E|F|G|H|I|J|K|L|M|N|O|P|Q|R|S|T|U|V|W|X|Y|Z|0|1|2|3|4|5|6|7|8|9|_)+( and (a|b|c|d|e|f|g|h|i|j|k|l|m|n|o|p|q|r|s|t|u|v|w|x|y|z)+)*."
+            // test regex: "email was meant for @(a|b|c|d|e|f|g|h|i|j|k|l|m|n|o|p|q|r|s|t|u|v|w|x|y|z|A|B|C|D|E|F|G|H|I|J|K|L|M|N|O|P|Q|R|S|T|U|V|W|X|Y|Z|0|1|2|3|4|5|6|7|8|9|_)+( and (a|b|c|d|e|f|g|h|i|j|k|l|m|n|o|p|q|r|s|t|u|v|w|x|y|z)+)*\\."
             config.load(&mut layouter)?;
             config.range_gate.load_lookup_table(&mut layouter)?;
 
@@ -831,13 +1247,335 @@ mod test {
         }
     }
 
+    // A `SubstrMatchConfig` with the range chip parameters of `TestSubstrMatchCircuit`.
+    fn configure_substrs<F: PrimeField>(
+        meta: &mut ConstraintSystem<F>,
+        regex_def: RegexDef,
+        substr_defs: Vec<SubstrDef>,
+    ) -> SubstrMatchConfig<F> {
+        let range_config = RangeConfig::configure(
+            meta,
+            Vertical,
+            &[TestSubstrMatchCircuit::<F>::NUM_ADVICE],
+            &[TestSubstrMatchCircuit::<F>::NUM_LOOKUP_ADVICE],
+            TestSubstrMatchCircuit::<F>::NUM_FIXED,
+            TestSubstrMatchCircuit::<F>::LOOKUP_BITS,
+            0,
+            K,
+        );
+        SubstrMatchConfig::configure(meta, regex_def, MAX_STRING_LEN, range_config, substr_defs)
+    }
+
+    // Loads the tables of `config` and runs `assign` in a context over one region, skipping the
+    // first pass of the floor planner.
+    fn synthesize_in_context<F: PrimeField>(
+        config: &SubstrMatchConfig<F>,
+        layouter: &mut impl Layouter<F>,
+        mut assign: impl FnMut(&mut Context<'_, F>) -> Result<(), Error>,
+    ) -> Result<(), Error> {
+        config.load(layouter)?;
+        config.range_gate.load_lookup_table(layouter)?;
+
+        let mut first_pass = SKIP_FIRST_PASS;
+        let gate = config.gate().clone();
+        layouter.assign_region(
+            || "regex",
+            |region| {
+                if first_pass {
+                    first_pass = false;
+                    return Ok(());
+                }
+                let mut aux = Context::new(
+                    region,
+                    ContextParams {
+                        max_rows: gate.max_rows,
+                        num_context_ids: 1,
+                        fixed_columns: gate.constants.clone(),
+                    },
+                );
+                let ctx = &mut aux;
+                assign(ctx)?;
+                config.range().finalize(ctx);
+                Ok(())
+            },
+        )
+    }
+
+    // Matches `characters` and exposes the packed substrings of `group_ids`.
+    fn synthesize_exposed<F: PrimeField>(
+        config: &SubstrMatchConfig<F>,
+        layouter: &mut impl Layouter<F>,
+        characters: &[u8],
+        group_ids: &[usize],
+    ) -> Result<(), Error> {
+        let mut packed_cells = vec![];
+        synthesize_in_context(config, layouter, |ctx| {
+            let result = config.match_substrs(ctx, characters)?;
+            packed_cells = config
+                .pack_substrs(ctx, &result)
+                .iter()
+                .map(|packed| packed.cell())
+                .collect();
+            Ok(())
+        })?;
+        config.expose_substrs_selective(layouter, &packed_cells, group_ids)
+    }
+
+    #[derive(Default, Clone, Debug)]
+    struct TestExposeSubstrsCircuit<F: PrimeField> {
+        characters: Vec<u8>,
+        _marker: PhantomData<F>,
+    }
+
+    impl<F: PrimeField> Circuit<F> for TestExposeSubstrsCircuit<F> {
+        type Config = SubstrMatchConfig<F>;
+        type FloorPlanner = SimpleFloorPlanner;
+
+        fn without_witnesses(&self) -> Self {
+            Self {
+                characters: vec![],
+                _marker: PhantomData,
+            }
+        }
+
+        fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+            let mut config = <TestSubstrMatchCircuit<F> as Circuit<F>>::configure(meta);
+            config.configure_instances(meta);
+            config
+        }
+
+        fn synthesize(
+            &self,
+            config: Self::Config,
+            mut layouter: impl Layouter<F>,
+        ) -> Result<(), Error> {
+            synthesize_exposed(&config, &mut layouter, &self.characters, &[0, 1])
+        }
+    }
+
+    // Reveals the domain of an email address while keeping the local-part private, packed in
+    // big-endian order with `BIG_ENDIAN`.
+    #[derive(Default, Clone, Debug)]
+    struct TestRevealDomainCircuit<F: PrimeField, const BIG_ENDIAN: bool = false> {
+        characters: Vec<u8>,
+        _marker: PhantomData<F>,
+    }
+
+    impl<F: PrimeField, const BIG_ENDIAN: bool> Circuit<F> for TestRevealDomainCircuit<F, BIG_ENDIAN> {
+        type Config = SubstrMatchConfig<F>;
+        type FloorPlanner = SimpleFloorPlanner;
+
+        fn without_witnesses(&self) -> Self {
+            Self {
+                characters: vec![],
+                _marker: PhantomData,
+            }
+        }
+
+        fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+            let compiled = crate::compile_regex(r"([a-z]+)@([a-z]+\.com)").unwrap();
+            let mut config = configure_substrs(
+                meta,
+                compiled.regex_def(),
+                vec![
+                    compiled.substr_def(0, 16, 0, 15),
+                    compiled.substr_def(1, 12, 1, 127),
+                ],
+            );
+            config.configure_instances(meta);
+            if BIG_ENDIAN {
+                config.set_byte_order(ByteOrder::BigEndian);
+            }
+            config
+        }
+
+        fn synthesize(
+            &self,
+            config: Self::Config,
+            mut layouter: impl Layouter<F>,
+        ) -> Result<(), Error> {
+            synthesize_exposed(&config, &mut layouter, &self.characters, &[1])
+        }
+    }
+
+    // Parses a captured amount of at most 4 characters and checks that it is below `bound`.
+    // Hex letters are captured too, so that the digit constraints can be exercised.
+    #[derive(Default, Clone, Debug)]
+    struct TestDecimalCircuit<F: PrimeField> {
+        characters: Vec<u8>,
+        value: u64,
+        bound: u64,
+        _marker: PhantomData<F>,
+    }
+
+    impl<F: PrimeField> Circuit<F> for TestDecimalCircuit<F> {
+        type Config = SubstrMatchConfig<F>;
+        type FloorPlanner = SimpleFloorPlanner;
+
+        fn without_witnesses(&self) -> Self {
+            Self {
+                characters: vec![],
+                value: self.value,
+                bound: self.bound,
+                _marker: PhantomData,
+            }
+        }
+
+        fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+            let compiled = crate::compile_regex(r"amount: ([0-9a-f]+);").unwrap();
+            configure_substrs(
+                meta,
+                compiled.regex_def(),
+                vec![compiled.substr_def(0, 4, 0, 127)],
+            )
+        }
+
+        fn synthesize(
+            &self,
+            config: Self::Config,
+            mut layouter: impl Layouter<F>,
+        ) -> Result<(), Error> {
+            synthesize_in_context(&config, &mut layouter, |ctx| {
+                let result = config.match_substrs(ctx, &self.characters)?;
+                let value = config.decimal_value(
+                    ctx,
+                    &result.substrs_bytes[0],
+                    &result.substrs_length[0],
+                )?;
+                config
+                    .gate()
+                    .assert_is_const(ctx, &value, F::from(self.value));
+                config.range().check_less_than_safe(ctx, &value, self.bound);
+                Ok(())
+            })
+        }
+    }
+
+    // Concatenates the two fragments of a value split by a delimiter and compares the result
+    // with `expected`, padded with zeros.
+    #[derive(Default, Clone, Debug)]
+    struct TestConcatCircuit<F: PrimeField> {
+        characters: Vec<u8>,
+        expected: Vec<u8>,
+        _marker: PhantomData<F>,
+    }
+
+    impl<F: PrimeField> Circuit<F> for TestConcatCircuit<F> {
+        type Config = SubstrMatchConfig<F>;
+        type FloorPlanner = SimpleFloorPlanner;
+
+        fn without_witnesses(&self) -> Self {
+            Self {
+                characters: vec![],
+                expected: self.expected.clone(),
+                _marker: PhantomData,
+            }
+        }
+
+        fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+            let compiled = crate::compile_regex(r"([a-z]+)-([a-z]+);").unwrap();
+            configure_substrs(
+                meta,
+                compiled.regex_def(),
+                vec![
+                    compiled.substr_def(0, 8, 0, 127),
+                    compiled.substr_def(1, 8, 0, 127),
+                ],
+            )
+        }
+
+        fn synthesize(
+            &self,
+            config: Self::Config,
+            mut layouter: impl Layouter<F>,
+        ) -> Result<(), Error> {
+            synthesize_in_context(&config, &mut layouter, |ctx| {
+                let result = config.match_substrs(ctx, &self.characters)?;
+                let concatenated = config.concat_substrs(ctx, &result, &[0, 1])?;
+                assert_eq!(concatenated.len(), 16);
+                for (idx, byte) in concatenated.iter().enumerate() {
+                    let expected = self.expected.get(idx).copied().unwrap_or(0);
+                    config
+                        .gate()
+                        .assert_is_const(ctx, byte, F::from(expected as u64));
+                }
+                Ok(())
+            })
+        }
+    }
+
+    // Names longer than 8 bytes and " and ..." lists longer than 24 bytes are not extracted.
+    const REFERENCE_REGEX: &str = r"email was meant for @([a-zA-Z0-9_]+)((?: and [a-z]+)*)\.";
+    // Both captures of an input that may be empty.
+    const OPTIONAL_REGEX: &str = r"(a*)(b*)";
+
+    fn reference_substr_defs(compiled: &crate::CompiledRegex) -> Vec<SubstrDef> {
+        vec![
+            compiled.substr_def(0, 8, 0, 127),
+            compiled.substr_def(1, 24, 0, 127),
+        ]
+    }
+
+    #[derive(Default, Clone, Debug)]
+    struct TestReferenceCircuit<F: PrimeField, const OPTIONAL: bool = false> {
+        characters: Vec<u8>,
+        // The substrings of `test_utils::reference_substrs`.
+        expected: Vec<Vec<u8>>,
+        _marker: PhantomData<F>,
+    }
+
+    impl<F: PrimeField, const OPTIONAL: bool> Circuit<F> for TestReferenceCircuit<F, OPTIONAL> {
+        type Config = SubstrMatchConfig<F>;
+        type FloorPlanner = SimpleFloorPlanner;
+
+        fn without_witnesses(&self) -> Self {
+            Self::default()
+        }
+
+        fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+            let pattern = if OPTIONAL {
+                OPTIONAL_REGEX
+            } else {
+                REFERENCE_REGEX
+            };
+            let compiled = crate::compile_regex(pattern).unwrap();
+            configure_substrs(meta, compiled.regex_def(), reference_substr_defs(&compiled))
+        }
+
+        fn synthesize(
+            &self,
+            config: Self::Config,
+            mut layouter: impl Layouter<F>,
+        ) -> Result<(), Error> {
+            synthesize_in_context(&config, &mut layouter, |ctx| {
+                let result = config.match_substrs(ctx, &self.characters)?;
+                let gate = config.gate();
+                // The masked bytes are the reference substrings padded with zeros.
+                for (idx, expected) in self.expected.iter().enumerate() {
+                    gate.assert_is_const(
+                        ctx,
+                        &result.substrs_length[idx],
+                        F::from(expected.len() as u64),
+                    );
+                    for (j, byte) in result.substrs_bytes[idx].iter().enumerate() {
+                        let expected = expected.get(j).copied().unwrap_or(0);
+                        gate.assert_is_const(ctx, byte, F::from(expected as u64));
+                    }
+                }
+                Ok(())
+            })
+        }
+    }
+
+    fn pack_bytes<F: PrimeField>(bytes: &[u8]) -> F {
+        bytes.iter().rev().fold(F::from(0), |acc, byte| {
+            acc * F::from(256) + F::from(*byte as u64)
+        })
+    }
+
     #[test]
     fn test_substr_pass1() {
         let characters: Vec<u8> = "email was meant for @y.".chars().map(|c| c as u8).collect();
-        // Make a vector of the numbers 1...24
-        // let states = (1..=STRING_LEN as u128).collect::<Vec<u128>>();
-        // assert_eq!(characters.len(), STRING_LEN);
-        // assert_eq!(states.len(), STRING_LEN);
 
         // Successful cases
         let circuit = TestSubstrMatchCircuit::<Fr> {
@@ -864,10 +1602,6 @@ mod test {
             .chars()
             .map(|c| c as u8)
             .collect();
-        // Make a vector of the numbers 1...24
-        // let states = (1..=STRING_LEN as u128).collect::<Vec<u128>>();
-        // assert_eq!(characters.len(), STRING_LEN);
-        // assert_eq!(states.len(), STRING_LEN);
 
         // Successful cases
         let circuit = TestSubstrMatchCircuit::<Fr> {
@@ -894,10 +1628,6 @@ mod test {
             .chars()
             .map(|c| c as u8)
             .collect();
-        // Make a vector of the numbers 1...24
-        // let states = (1..=STRING_LEN as u128).collect::<Vec<u128>>();
-        // assert_eq!(characters.len(), STRING_LEN);
-        // assert_eq!(states.len(), STRING_LEN);
 
         // Successful cases
         let circuit = TestSubstrMatchCircuit::<Fr> {
@@ -923,11 +1653,6 @@ mod test {
         // 1. The string does not satisfy the regex.
         let characters: Vec<u8> = "email was meant for @@".chars().map(|c| c as u8).collect();
 
-        // Make a vector of the numbers 1...24
-        // let states = (1..=STRING_LEN as u128).collect::<Vec<u128>>();
-        // assert_eq!(characters.len(), STRING_LEN);
-        // assert_eq!(states.len(), STRING_LEN);
-
         // Successful cases
         let circuit = TestSubstrMatchCircuit::<Fr> {
             characters,
@@ -951,4 +1676,416 @@ mod test {
             )
         );
     }
+
+    #[test]
+    fn test_expose_substrs() {
+        let characters: Vec<u8> = "email was meant for @yajk and kaiew and oiewk."
+            .chars()
+            .map(|c| c as u8)
+            .collect();
+        let circuit = TestExposeSubstrsCircuit::<Fr> {
+            characters,
+            _marker: PhantomData,
+        };
+        let instances = vec![
+            vec![pack_bytes::<Fr>(b"yajk")],
+            vec![pack_bytes::<Fr>(b"and kaiew and oiewk")],
+        ];
+        let prover = MockProver::run(K as u32, &circuit, instances).unwrap();
+        prover.assert_satisfied();
+
+        // The second group does not match, so it is exposed as 0.
+        let characters: Vec<u8> = "email was meant for @yajk."
+            .chars()
+            .map(|c| c as u8)
+            .collect();
+        let circuit = TestExposeSubstrsCircuit::<Fr> {
+            characters,
+            _marker: PhantomData,
+        };
+        let instances = vec![vec![pack_bytes::<Fr>(b"yajk")], vec![Fr::from(0)]];
+        let prover = MockProver::run(K as u32, &circuit, instances).unwrap();
+        prover.assert_satisfied();
+
+        let instances = vec![vec![pack_bytes::<Fr>(b"yajj")], vec![Fr::from(0)]];
+        let prover = MockProver::run(K as u32, &circuit, instances).unwrap();
+        assert!(prover.verify().is_err());
+    }
+
+    #[test]
+    fn test_expose_substrs_selective() {
+        let circuit = TestRevealDomainCircuit::<Fr> {
+            characters: b"alice@mail.com".to_vec(),
+            _marker: PhantomData,
+        };
+        // Only the domain is public; the local-part has no instance values.
+        let instances = vec![vec![], vec![pack_bytes::<Fr>(b"mail.com")]];
+        let prover = MockProver::run(K as u32, &circuit, instances).unwrap();
+        prover.assert_satisfied();
+
+        let instances = vec![vec![], vec![pack_bytes::<Fr>(b"gmail.com")]];
+        let prover = MockProver::run(K as u32, &circuit, instances).unwrap();
+        assert!(prover.verify().is_err());
+    }
+
+    #[test]
+    fn test_pack_substrs_byte_order() {
+        // The bytes padded to the maximum length, the first one the most significant.
+        fn pack_bytes_be(bytes: &[u8], max_length: usize) -> Fr {
+            let mut padded = bytes.to_vec();
+            padded.resize(max_length, 0);
+            pack_bytes(&padded.into_iter().rev().collect::<Vec<u8>>())
+        }
+        let characters = b"alice@mail.com".to_vec();
+        let little_endian = vec![vec![], vec![pack_bytes::<Fr>(b"mail.com")]];
+        let big_endian = vec![vec![], vec![pack_bytes_be(b"mail.com", 12)]];
+        // "ab" packs to 0x6261 little-endian and to 0x6162 followed by the padding big-endian.
+        assert_eq!(pack_bytes::<Fr>(b"ab"), Fr::from(0x6261));
+        assert_eq!(pack_bytes_be(b"ab", 4), Fr::from(0x6162_0000));
+
+        let circuit = TestRevealDomainCircuit::<Fr, false> {
+            characters: characters.clone(),
+            _marker: PhantomData,
+        };
+        let prover = MockProver::run(K as u32, &circuit, little_endian.clone()).unwrap();
+        prover.assert_satisfied();
+        let prover = MockProver::run(K as u32, &circuit, big_endian.clone()).unwrap();
+        assert!(prover.verify().is_err());
+
+        let circuit = TestRevealDomainCircuit::<Fr, true> {
+            characters,
+            _marker: PhantomData,
+        };
+        let prover = MockProver::run(K as u32, &circuit, big_endian).unwrap();
+        prover.assert_satisfied();
+        let prover = MockProver::run(K as u32, &circuit, little_endian).unwrap();
+        assert!(prover.verify().is_err());
+    }
+
+    // Matches the email of `TestRevealDomainCircuit` without the witness check of the maximum
+    // capture lengths.
+    #[derive(Default, Clone, Debug)]
+    struct TestUncheckedLengthCircuit<F: PrimeField> {
+        characters: Vec<u8>,
+        _marker: PhantomData<F>,
+    }
+
+    impl<F: PrimeField> Circuit<F> for TestUncheckedLengthCircuit<F> {
+        type Config = SubstrMatchConfig<F>;
+        type FloorPlanner = SimpleFloorPlanner;
+
+        fn without_witnesses(&self) -> Self {
+            Self::default()
+        }
+
+        fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+            let compiled = crate::compile_regex(r"([a-z]+)@([a-z]+\.com)").unwrap();
+            configure_substrs(
+                meta,
+                compiled.regex_def(),
+                vec![
+                    compiled.substr_def(0, 16, 0, 15),
+                    compiled.substr_def(1, 12, 1, 127),
+                ],
+            )
+        }
+
+        fn synthesize(
+            &self,
+            config: Self::Config,
+            mut layouter: impl Layouter<F>,
+        ) -> Result<(), Error> {
+            synthesize_in_context(&config, &mut layouter, |ctx| {
+                config.assign_substrs(ctx, &self.characters, false)?;
+                Ok(())
+            })
+        }
+    }
+
+    #[test]
+    fn test_substr_max_length() {
+        // The domain is capped at 12 characters.
+        let circuit = TestRevealDomainCircuit::<Fr> {
+            characters: b"alice@abcdefgh.com".to_vec(),
+            _marker: PhantomData,
+        };
+        let instances = vec![vec![], vec![pack_bytes::<Fr>(b"abcdefgh.com")]];
+        let prover = MockProver::run(K as u32, &circuit, instances).unwrap();
+        prover.assert_satisfied();
+
+        let circuit = TestRevealDomainCircuit::<Fr> {
+            characters: b"alice@abcdefghi.com".to_vec(),
+            _marker: PhantomData,
+        };
+        let instances = vec![vec![], vec![pack_bytes::<Fr>(b"abcdefghi.co")]];
+        assert!(MockProver::run(K as u32, &circuit, instances).is_err());
+
+        // Without the witness check, the over-long domain is rejected by the count constraint,
+        // a copy of the count comparison to the constant 1.
+        let circuit = TestUncheckedLengthCircuit::<Fr> {
+            characters: b"alice@abcdefgh.com".to_vec(),
+            _marker: PhantomData,
+        };
+        MockProver::run(K as u32, &circuit, vec![])
+            .unwrap()
+            .assert_satisfied();
+        let circuit = TestUncheckedLengthCircuit::<Fr> {
+            characters: b"alice@abcdefghi.com".to_vec(),
+            _marker: PhantomData,
+        };
+        let failures = MockProver::run(K as u32, &circuit, vec![])
+            .unwrap()
+            .verify()
+            .unwrap_err();
+        assert!(failures
+            .iter()
+            .all(|failure| matches!(failure, VerifyFailure::Permutation { .. })));
+    }
+
+    #[test]
+    fn test_decimal_value() {
+        let decimal_circuit = |characters: &[u8], value: u64, bound: u64| TestDecimalCircuit::<Fr> {
+            characters: characters.to_vec(),
+            value,
+            bound,
+            _marker: PhantomData,
+        };
+        let circuit = decimal_circuit(b"amount: 1234;", 1234, 10000);
+        let prover = MockProver::run(K as u32, &circuit, vec![]).unwrap();
+        prover.assert_satisfied();
+
+        // Leading zeros.
+        let circuit = decimal_circuit(b"amount: 0042;", 42, 100);
+        let prover = MockProver::run(K as u32, &circuit, vec![]).unwrap();
+        prover.assert_satisfied();
+
+        // Out of bounds.
+        let circuit = decimal_circuit(b"amount: 1234;", 1234, 1000);
+        let prover = MockProver::run(K as u32, &circuit, vec![]).unwrap();
+        assert!(prover.verify().is_err());
+
+        // A wrong value.
+        let circuit = decimal_circuit(b"amount: 1234;", 1243, 10000);
+        let prover = MockProver::run(K as u32, &circuit, vec![]).unwrap();
+        assert!(prover.verify().is_err());
+
+        // The regex accepts hex letters, but they are not decimal digits.
+        let circuit = decimal_circuit(b"amount: 12a4;", 1304, 10000);
+        let prover = MockProver::run(K as u32, &circuit, vec![]).unwrap();
+        assert!(prover.verify().is_err());
+    }
+
+    #[test]
+    fn test_concat_substrs() {
+        let concat_circuit = |characters: &[u8], expected: &[u8]| TestConcatCircuit::<Fr> {
+            characters: characters.to_vec(),
+            expected: expected.to_vec(),
+            _marker: PhantomData,
+        };
+        for (input, expected) in [
+            (&b"abc-de;"[..], &b"abcde"[..]),
+            (b"a-bcdefgh;", b"abcdefgh"),
+            (b"abcdefgh-ijklmnop;", b"abcdefghijklmnop"),
+        ] {
+            let circuit = concat_circuit(input, expected);
+            let prover = MockProver::run(K as u32, &circuit, vec![]).unwrap();
+            prover.assert_satisfied();
+        }
+
+        // The delimiter is not part of the result.
+        let circuit = concat_circuit(b"abc-de;", b"abc-de");
+        let prover = MockProver::run(K as u32, &circuit, vec![]).unwrap();
+        assert!(prover.verify().is_err());
+    }
+
+    #[test]
+    fn test_substrs_match_reference() {
+        let compiled = crate::compile_regex(REFERENCE_REGEX).unwrap();
+        let substr_defs = reference_substr_defs(&compiled);
+        let mut rng = StdRng::seed_from_u64(164);
+        let name_chars = b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789_";
+        let mut inputs = vec![b"email was meant for @y.".to_vec()];
+        for _ in 0..6 {
+            let mut input = b"email was meant for @".to_vec();
+            for _ in 0..rng.gen_range(1..=10) {
+                input.push(name_chars[rng.gen_range(0..name_chars.len())]);
+            }
+            for _ in 0..rng.gen_range(0..=3) {
+                input.extend(b" and ");
+                for _ in 0..rng.gen_range(1..=6) {
+                    input.push(rng.gen_range(b'a'..=b'z'));
+                }
+            }
+            input.push(b'.');
+            inputs.push(input);
+        }
+
+        for input in inputs {
+            let expected = crate::test_utils::reference_substrs(&input, &compiled, &substr_defs);
+            let circuit = TestReferenceCircuit::<Fr> {
+                characters: input.clone(),
+                expected: expected.clone().unwrap_or_default(),
+                _marker: PhantomData,
+            };
+            let label = String::from_utf8_lossy(&input).to_string();
+            match expected {
+                Some(expected) => {
+                    MockProver::run(K as u32, &circuit, vec![])
+                        .unwrap()
+                        .assert_satisfied();
+                    // The definitions cover every position, so the circuit reveals the captures.
+                    assert_eq!(
+                        crate::extract_captures(&input, &compiled),
+                        Some(expected),
+                        "{}",
+                        label
+                    );
+                }
+                // A capture over its maximum length fails at witness generation.
+                None => assert!(
+                    MockProver::run(K as u32, &circuit, vec![]).is_err(),
+                    "{}",
+                    label
+                ),
+            }
+        }
+    }
+
+    #[test]
+    fn test_substrs_empty_input() {
+        let compiled = crate::compile_regex(OPTIONAL_REGEX).unwrap();
+        let substr_defs = reference_substr_defs(&compiled);
+        for input in [&b""[..], b"aab", b"b"] {
+            let expected =
+                crate::test_utils::reference_substrs(input, &compiled, &substr_defs).unwrap();
+            if input.is_empty() {
+                assert_eq!(expected, vec![vec![], vec![]]);
+            }
+            let circuit = TestReferenceCircuit::<Fr, true> {
+                characters: input.to_vec(),
+                expected,
+                _marker: PhantomData,
+            };
+            MockProver::run(K as u32, &circuit, vec![])
+                .unwrap()
+                .assert_satisfied();
+        }
+
+        // The start state of the reference regex is not accepting.
+        let circuit = TestReferenceCircuit::<Fr> {
+            characters: vec![],
+            expected: vec![],
+            _marker: PhantomData,
+        };
+        assert!(MockProver::run(K as u32, &circuit, vec![])
+            .unwrap()
+            .verify()
+            .is_err());
+    }
+
+    #[test]
+    fn test_substr_def_validate() {
+        // The sample definitions fit the sample table.
+        let regex_def = RegexDef::read_from_text("./test_regexes/regex_test_lookup.txt");
+        for path in [
+            "./test_regexes/substr1_test_lookup.txt",
+            "./test_regexes/substr2_test_lookup.txt",
+        ] {
+            assert_eq!(SubstrDef::read_from_text(path).validate(&regex_def), Ok(()));
+        }
+
+        let compiled = crate::compile_regex(r"([a-z]+)@([a-z]+)\.com").unwrap();
+        let regex_def = compiled.regex_def();
+        let user = compiled.substr_def(0, 8, 0, 127);
+        let domain = compiled.substr_def(1, 8, 0, 127);
+        assert_eq!(user.validate(&regex_def), Ok(()));
+        assert_eq!(domain.validate(&regex_def), Ok(()));
+        assert_eq!(SubstrDef::default().validate(&regex_def), Ok(()));
+
+        // A transition missing from the table.
+        let mut transitions = user.valid_state_transitions().clone();
+        transitions.insert((100, 101));
+        assert_eq!(
+            SubstrDef::new(8, 0, 127, transitions).validate(&regex_def),
+            Err(RegexError::MissingTransition {
+                prev_state: 100,
+                next_state: 101
+            })
+        );
+        // Two captures merged into one definition are not connected.
+        let transitions = user
+            .valid_state_transitions()
+            .union(domain.valid_state_transitions())
+            .copied()
+            .collect();
+        assert!(matches!(
+            SubstrDef::new(8, 0, 127, transitions).validate(&regex_def),
+            Err(RegexError::DisconnectedCapture { .. })
+        ));
+    }
+
+    #[test]
+    fn test_substr_configure_checked() {
+        let compiled = crate::compile_regex(r"([a-z]+)@([a-z]+)\.com").unwrap();
+        let user = compiled.substr_def(0, 8, 0, 127);
+        let domain = compiled.substr_def(1, 8, 0, 127);
+        let configure = |substr_defs: Vec<SubstrDef>| {
+            let mut meta = ConstraintSystem::<Fr>::default();
+            let range_config = RangeConfig::configure(&mut meta, Vertical, &[1], &[1], 1, 8, 0, 10);
+            SubstrMatchConfig::configure_checked(
+                &mut meta,
+                compiled.regex_def(),
+                16,
+                range_config,
+                substr_defs,
+            )
+            .map(|_| ())
+        };
+        assert_eq!(configure(vec![user.clone(), domain]), Ok(()));
+        assert_eq!(
+            configure(vec![user.clone(), user]),
+            Err(RegexError::AmbiguousCaptures {
+                first: 0,
+                second: 1
+            })
+        );
+    }
+
+    #[test]
+    fn test_substr_def_check_concat() {
+        let check = |pattern: &str, groups: &[usize]| {
+            let compiled = crate::compile_regex(pattern).unwrap();
+            let substr_defs: Vec<SubstrDef> = groups
+                .iter()
+                .map(|group| compiled.substr_def(*group, 16, 0, 127))
+                .collect();
+            let substr_defs: Vec<&SubstrDef> = substr_defs.iter().collect();
+            SubstrDef::check_concat(&substr_defs, &compiled.regex_def(), b';')
+        };
+        // Each capture but the last ends at its only `;`.
+        let pattern = r"([a-z]+;)([a-z]+;)([0-9]+)";
+        assert_eq!(check(pattern, &[0, 1, 2]), Ok(()));
+        assert_eq!(check(pattern, &[0, 2]), Ok(()));
+        assert_eq!(check(pattern, &[2]), Ok(()));
+        // Only the last capture may end without a delimiter.
+        assert!(matches!(
+            check(pattern, &[2, 0]),
+            Err(RegexError::UndelimitedCapture { group: 0, .. })
+        ));
+        // `aa` and `a` concatenate like `a` and `aa`.
+        assert!(matches!(
+            check(r"(a+)-(a+)", &[0, 1]),
+            Err(RegexError::UndelimitedCapture { group: 0, .. })
+        ));
+        // A delimiter inside the capture.
+        assert!(matches!(
+            check(r"([a-z;]+;)([0-9]+)", &[0, 1]),
+            Err(RegexError::UndelimitedCapture { group: 0, .. })
+        ));
+        // An empty capture leaves no delimiter.
+        assert_eq!(
+            check(r"([a-z]+;)?([0-9]+)", &[0, 1]),
+            Err(RegexError::OptionalCapture { group: 0 })
+        );
+    }
 }
@@ -1,12 +1,21 @@
-use std::{collections::HashMap, fmt::format, marker::PhantomData};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::{format, Debug},
+    hash::Hash,
+    marker::PhantomData,
+};
 
 use halo2_base::halo2_proofs::{
     circuit::{Layouter, Value},
     plonk::{ConstraintSystem, Error, TableColumn},
 };
 use halo2_base::utils::PrimeField;
-use std::fs::File;
-use std::io::{BufRead, BufReader};
+
+use crate::RegexError;
+use std::io::BufRead;
+
+mod prepared;
+pub use prepared::PreparedRegex;
 
 // struct Sizes {
 //     RANGE: usize,
@@ -17,36 +26,60 @@ use std::io::{BufRead, BufReader};
 /// A lookup table of values from 0..RANGE.
 #[derive(Debug, Clone)]
 pub struct TransitionTableConfig<F: PrimeField> {
+    // 1 for the transitions of the regex, 0 for the dummy row.
+    pub(crate) enabled: TableColumn,
     pub(crate) prev_state: TableColumn,
     pub(crate) next_state: TableColumn,
     pub(crate) character: TableColumn,
+    // Prefixed to the name of the table.
+    tag: Option<String>,
     _marker: PhantomData<F>,
 }
 
 impl<F: PrimeField> TransitionTableConfig<F> {
     pub fn configure(meta: &mut ConstraintSystem<F>) -> Self {
+        let enabled = meta.lookup_table_column();
         let prev_state = meta.lookup_table_column();
         let next_state = meta.lookup_table_column();
         let character = meta.lookup_table_column();
 
         Self {
+            enabled,
             prev_state,
             next_state,
             character,
+            tag: None,
             _marker: PhantomData,
         }
     }
 
-    pub fn load(
+    /// Like [`Self::configure`], but the table is loaded as `"{tag}: load transition table"`.
+    pub fn configure_tagged(meta: &mut ConstraintSystem<F>, tag: &str) -> Self {
+        Self {
+            tag: Some(tag.to_string()),
+            ..Self::configure(meta)
+        }
+    }
+
+    pub fn load<C: RegexChar>(
         &self,
         layouter: &mut impl Layouter<F>,
-        state_lookup: &HashMap<(u8, u64), u64>,
+        state_lookup: &HashMap<(C, u64), u64>,
     ) -> Result<(), Error> {
         layouter.assign_table(
-            || "load transition table",
+            || match self.tag.as_deref() {
+                Some(tag) => format!("{}: load transition table", tag),
+                None => "load transition table".to_string(),
+            },
             |mut table| {
                 let mut offset = 0;
-                let mut assign_row = |prev_state: u64, next_state: u64, char: u8| {
+                let mut assign_row = |enabled: u64, prev_state: u64, next_state: u64, char: C| {
+                    table.assign_cell(
+                        || "enabled",
+                        self.enabled,
+                        offset,
+                        || Value::known(F::from(enabled)),
+                    )?;
                     table.assign_cell(
                         || "prev_state",
                         self.prev_state,
@@ -63,20 +96,21 @@ impl<F: PrimeField> TransitionTableConfig<F> {
                         || "character",
                         self.character,
                         offset,
-                        || Value::known(F::from(char as u64)),
+                        || Value::known(F::from(char.to_u64())),
                     )?;
                     offset += 1;
                     Ok::<(), Error>(())
                 };
                 // let mut array = lookups.to_vec();
-                // Append a dummy row [0, 0, 0].
-                assign_row(0, 0, 0)?;
+                // Append a dummy row [0, 0, 0, 0]. It is flagged as disabled so that enabled rows
+                // cannot use it, even if 0 is a state of the regex.
+                assign_row(0, 0, 0, C::default())?;
                 for ((char, prev_state), next_state) in state_lookup
                     .keys()
                     .into_iter()
                     .zip(state_lookup.values().into_iter())
                 {
-                    assign_row(*prev_state, *next_state, *char)?;
+                    assign_row(1, *prev_state, *next_state, *char)?;
                 }
 
                 // let dummy_lookup = vec![0, 0, 0];
@@ -111,23 +145,233 @@ impl<F: PrimeField> TransitionTableConfig<F> {
     }
 }
 
+/// Character value marking a default transition in the text format. It is taken for every byte
+/// without an explicit transition from the same state, and is expanded into one row per such byte.
+pub const ANY_CHAR: u64 = 256;
+
+/// The CRC-32 (IEEE) of a table buffer, as expected by [`RegexDef::from_text_bytes`].
+pub fn table_checksum(bytes: &[u8]) -> u32 {
+    let mut crc = u32::MAX;
+    for byte in bytes.iter() {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+/// Extends the `len + 1` states of an input of `len` characters, e.g. from
+/// [`RegexDef::gen_states`], to the `max_len + 1` rows of a check of `max_len` characters: the
+/// row after the last character keeps the state the input ends in, and every later row holds
+/// `padding_state`, which is 0 in [`RegexCheckConfig`](crate::RegexCheckConfig). Panics if
+/// there are more than `max_len + 1` states.
+pub fn pad_states(states: &[u64], max_len: usize, padding_state: u64) -> Vec<u64> {
+    assert!(
+        states.len() <= max_len + 1,
+        "{} states do not fit {} characters",
+        states.len(),
+        max_len
+    );
+    let mut padded = states.to_vec();
+    padded.resize(max_len + 1, padding_state);
+    padded
+}
+
+/// Extends `chars` to `max_len` characters with `padding_byte`, which is 0 in
+/// [`RegexCheckConfig`](crate::RegexCheckConfig), so that each row of the check has one. Pairs
+/// with [`pad_states`]. Panics if there are more than `max_len` characters.
+pub fn pad_chars(chars: &[u8], max_len: usize, padding_byte: u8) -> Vec<u8> {
+    assert!(
+        chars.len() <= max_len,
+        "{} characters do not fit {}",
+        chars.len(),
+        max_len
+    );
+    let mut padded = chars.to_vec();
+    padded.resize(max_len, padding_byte);
+    padded
+}
+
+/// The first bytes of a table in the binary format of [`RegexDef::to_bytes`].
+pub const TABLE_MAGIC: [u8; 4] = *b"RGXT";
+/// The version of the binary table format.
+pub const TABLE_VERSION: u16 = 1;
+
+/// A character of the input: a byte, or a wider code unit for alphabets of more than 256
+/// characters, such as UTF-16 code units.
+///
+/// Characters are constrained by the transition lookup alone, which only holds characters of
+/// the table, so [`RegexCheckConfig`](crate::RegexCheckConfig) needs no range check on them.
+/// `BITS` sizes the range checks of gadgets that consume the characters.
+pub trait RegexChar: Copy + Eq + Ord + Hash + Debug + Default {
+    /// The width of a character in bits, a multiple of 8.
+    const BITS: u32;
+
+    fn to_u64(self) -> u64;
+
+    /// The ASCII lowercase of the character; other characters are returned as they are.
+    fn fold_ascii_case(self) -> Self;
+}
+
+impl RegexChar for u8 {
+    const BITS: u32 = 8;
+
+    fn to_u64(self) -> u64 {
+        self as u64
+    }
+
+    fn fold_ascii_case(self) -> Self {
+        self.to_ascii_lowercase()
+    }
+}
+
+impl RegexChar for u16 {
+    const BITS: u32 = 16;
+
+    fn to_u64(self) -> u64 {
+        self as u64
+    }
+
+    fn fold_ascii_case(self) -> Self {
+        match u8::try_from(self) {
+            Ok(byte) => byte.to_ascii_lowercase() as u16,
+            Err(_) => self,
+        }
+    }
+}
+
+/// A transition table over characters of type `C`. Only byte tables (`C = u8`) are read from
+/// and written to files; wider tables are built in code.
 #[derive(Debug, Clone, Default)]
-pub struct RegexDef {
-    pub state_lookup: HashMap<(u8, u64), u64>,
+pub struct RegexDef<C = u8> {
+    pub state_lookup: HashMap<(C, u64), u64>,
     pub first_state_val: u64,
     pub accepted_state_vals: Vec<u64>,
     pub largest_state_val: u64,
 }
 
 impl RegexDef {
+    /// Reads a table in the text format. Panics if the file is missing or empty; see
+    /// [`Self::try_read_from_text`].
     pub fn read_from_text(file_path: &str) -> Self {
-        let file = File::open(file_path).unwrap();
-        let reader = BufReader::new(file);
+        Self::try_read_from_text(file_path).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Like [`Self::read_from_text`], but returns [`RegexError::Io`] if the file cannot be read
+    /// (e.g. a wrong relative path) and [`RegexError::EmptyTable`] if it is blank.
+    pub fn try_read_from_text(file_path: &str) -> Result<Self, RegexError> {
+        let bytes = std::fs::read(file_path).map_err(|err| RegexError::Io {
+            path: file_path.to_string(),
+            message: err.to_string(),
+        })?;
+        if bytes.iter().all(|byte| byte.is_ascii_whitespace()) {
+            return Err(RegexError::EmptyTable {
+                path: file_path.to_string(),
+            });
+        }
+        Ok(Self::read_from_reader(&bytes[..]))
+    }
+
+    /// Parses a table in the text format of [`Self::read_from_text`] from `bytes`, after checking
+    /// that their CRC-32 ([`table_checksum`]) is `expected_checksum`.
+    pub fn from_text_bytes(bytes: &[u8], expected_checksum: u32) -> Result<Self, RegexError> {
+        let actual = table_checksum(bytes);
+        if actual != expected_checksum {
+            return Err(RegexError::ChecksumMismatch {
+                expected: expected_checksum,
+                actual,
+            });
+        }
+        Ok(Self::read_from_reader(bytes))
+    }
+
+    /// Serializes the table into a little-endian binary format, which loads about 4x faster than
+    /// the text format (see `bench_binary_table_load`). It is a header of [`TABLE_MAGIC`], the
+    /// `u16` [`TABLE_VERSION`], `largest_state_val` (`u64`), the alphabet size (`u16`),
+    /// `first_state_val` (`u64`) and the accepted states (`u32` count, `u64` each), followed by
+    /// the `u32` number of transitions and a sorted 17-byte record per transition: `prev_state`
+    /// (`u64`), `next_state` (`u64`) and the character.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut transitions: Vec<(u64, u8, u64)> = self
+            .state_lookup
+            .iter()
+            .map(|((char, prev_state), next_state)| (*prev_state, *char, *next_state))
+            .collect();
+        transitions.sort_unstable();
+        let alphabet: HashSet<u8> = self.state_lookup.keys().map(|(char, _)| *char).collect();
+        let mut bytes = TABLE_MAGIC.to_vec();
+        bytes.extend(TABLE_VERSION.to_le_bytes());
+        bytes.extend(self.largest_state_val.to_le_bytes());
+        bytes.extend((alphabet.len() as u16).to_le_bytes());
+        bytes.extend(self.first_state_val.to_le_bytes());
+        bytes.extend((self.accepted_state_vals.len() as u32).to_le_bytes());
+        for state in self.accepted_state_vals.iter() {
+            bytes.extend(state.to_le_bytes());
+        }
+        bytes.extend((transitions.len() as u32).to_le_bytes());
+        for (prev_state, char, next_state) in transitions {
+            bytes.extend(prev_state.to_le_bytes());
+            bytes.extend(next_state.to_le_bytes());
+            bytes.push(char);
+        }
+        bytes
+    }
+
+    /// Parses a table in the binary format of [`Self::to_bytes`].
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, RegexError> {
+        let mut reader = ByteReader { bytes };
+        if reader.take(4)? != TABLE_MAGIC {
+            return Err(RegexError::InvalidTable("bad magic"));
+        }
+        if reader.u16()? != TABLE_VERSION {
+            return Err(RegexError::InvalidTable("unsupported version"));
+        }
+        let largest_state_val = reader.u64()?;
+        let alphabet_size = reader.u16()? as usize;
+        let first_state_val = reader.u64()?;
+        let accepted_state_vals = (0..reader.u32()?)
+            .map(|_| reader.u64())
+            .collect::<Result<Vec<u64>, RegexError>>()?;
+        let num_transitions = reader.u32()?;
+        let mut state_lookup = HashMap::new();
+        for _ in 0..num_transitions {
+            let prev_state = reader.u64()?;
+            let next_state = reader.u64()?;
+            let char = reader.take(1)?[0];
+            if state_lookup
+                .insert((char, prev_state), next_state)
+                .is_some()
+            {
+                return Err(RegexError::InvalidTable("duplicate transition"));
+            }
+        }
+        if !reader.bytes.is_empty() {
+            return Err(RegexError::InvalidTable("trailing bytes"));
+        }
+        let alphabet: HashSet<u8> = state_lookup.keys().map(|(char, _)| *char).collect();
+        if alphabet.len() != alphabet_size {
+            return Err(RegexError::InvalidTable("alphabet size mismatch"));
+        }
+        Ok(Self {
+            state_lookup,
+            first_state_val,
+            accepted_state_vals,
+            largest_state_val,
+        })
+    }
+
+    fn read_from_reader(reader: impl BufRead) -> Self {
         let mut state_lookup = HashMap::<(u8, u64), u64>::new();
         // let mut array = Vec::new();
         let mut first_state_val = 0;
         let mut accepted_state_vals = Vec::new();
         let mut largest_state_val = 0;
+        let mut default_transitions = Vec::new();
 
         for (idx, line) in reader.lines().enumerate() {
             let line = line.expect(&format!("fail to get {}-th line.", idx));
@@ -144,11 +388,18 @@ impl RegexDef {
                 accepted_state_vals = elements;
             } else if idx == 2 {
                 largest_state_val = elements[0];
+            } else if elements[2] == ANY_CHAR {
+                default_transitions.push((elements[0], elements[1]));
             } else {
                 state_lookup.insert((elements[2] as u8, elements[0]), elements[1]);
             }
             // array.push(elements);
         }
+        for (prev_state, next_state) in default_transitions {
+            for char in 0..=u8::MAX {
+                state_lookup.entry((char, prev_state)).or_insert(next_state);
+            }
+        }
         Self {
             state_lookup,
             first_state_val,
@@ -156,4 +407,839 @@ impl RegexDef {
             largest_state_val,
         }
     }
+
+    /// Returns the states visited on `characters`, starting with `first_state_val`.
+    ///
+    /// Matching is anchored: a character without a transition is an error at its offset rather
+    /// than a restart. On a total definition (see [`Self::with_trap_state`]) this never fails,
+    /// and a rejected input ends in the trap state.
+    pub fn gen_states(&self, characters: &[u8]) -> Result<Vec<u64>, RegexError> {
+        let mut states = vec![self.first_state_val];
+        for (offset, char) in characters.iter().enumerate() {
+            let state = states[offset];
+            match self.next_state(state, *char) {
+                Some(next_state) => states.push(next_state),
+                None => {
+                    return Err(RegexError::NoTransition {
+                        offset,
+                        char: *char,
+                        state,
+                    })
+                }
+            }
+        }
+        Ok(states)
+    }
+
+    /// Returns the `(state, character, next_state)` transitions taken on a matching `input`, in
+    /// order, i.e. the witness of [`Self::gen_states`] as a path. Fails like `gen_states` if a
+    /// character has no transition, and with [`RegexError::NotAccepted`] if the input ends in a
+    /// state that is not accepted.
+    pub fn trace_path(&self, input: &[u8]) -> Result<Vec<(u64, u8, u64)>, RegexError> {
+        let states = self.gen_states(input)?;
+        let last_state = *states.last().unwrap();
+        if !self.accepted_state_vals.contains(&last_state) {
+            return Err(RegexError::NotAccepted { state: last_state });
+        }
+        Ok(input
+            .iter()
+            .zip(states.windows(2))
+            .map(|(char, pair)| (pair[0], *char, pair[1]))
+            .collect())
+    }
+
+    /// Like [`Self::gen_states`], but never fails: from the first character without a
+    /// transition on, the states are the trap state ([`Self::trap_state_val`]). This is the
+    /// witness of `characters` for the total table of [`Self::with_trap_state`], e.g. to prove
+    /// that an input is rejected, without building that table.
+    pub fn gen_states_total(&self, characters: &[u8]) -> Vec<u64> {
+        let trap_state_val = self.trap_state_val();
+        let mut states = vec![self.first_state_val];
+        for char in characters.iter() {
+            let state = *states.last().unwrap();
+            let next_state = if state == trap_state_val {
+                None
+            } else {
+                self.next_state(state, *char)
+            };
+            states.push(next_state.unwrap_or(trap_state_val));
+        }
+        states
+    }
+
+    /// Returns the total DFA: every missing `(char, state)` transition, for all 256 characters,
+    /// goes to a new non-accepting trap state ([`Self::trap_state_val`]) that loops on every
+    /// character. Its table lets a rejected input be witnessed with a valid state sequence.
+    pub fn with_trap_state(&self) -> Self {
+        let trap_state_val = self.trap_state_val();
+        // Only the states of the table get transitions; values in between that are no state,
+        // like 0 for tables starting at 1, are left out.
+        let states: HashSet<u64> = self
+            .state_lookup
+            .iter()
+            .flat_map(|((_, prev_state), next_state)| [*prev_state, *next_state])
+            .chain([self.first_state_val, trap_state_val])
+            .chain(self.accepted_state_vals.iter().copied())
+            .collect();
+        let mut state_lookup = self.state_lookup.clone();
+        for state in states {
+            for char in 0..=u8::MAX {
+                state_lookup.entry((char, state)).or_insert(trap_state_val);
+            }
+        }
+        Self {
+            state_lookup,
+            first_state_val: self.first_state_val,
+            accepted_state_vals: self.accepted_state_vals.clone(),
+            largest_state_val: trap_state_val,
+        }
+    }
+
+    /// Returns the definition matching every input that starts with a match: each accepted
+    /// state moves on any character to a new accepted tail state ([`Self::trap_state_val`]) that
+    /// loops on every character. The prefix ends at the first accepted state reached, so its
+    /// transitions, e.g. those of captures, are not taken past the shortest match.
+    pub fn with_prefix_match(&self) -> Self {
+        let tail_state_val = self.trap_state_val();
+        let mut state_lookup = self.state_lookup.clone();
+        for state in self
+            .accepted_state_vals
+            .iter()
+            .copied()
+            .chain([tail_state_val])
+        {
+            for char in 0..=u8::MAX {
+                state_lookup.insert((char, state), tail_state_val);
+            }
+        }
+        let mut accepted_state_vals = self.accepted_state_vals.clone();
+        accepted_state_vals.push(tail_state_val);
+        Self {
+            state_lookup,
+            first_state_val: self.first_state_val,
+            accepted_state_vals,
+            largest_state_val: tail_state_val,
+        }
+    }
+
+    /// Splits a value of [`Self::encode_accept_flags`] into the original state and its accept flag.
+    pub fn decode_state(encoded: u64) -> (u64, bool) {
+        (encoded / 2, encoded % 2 == 1)
+    }
+}
+
+impl<C: RegexChar> RegexDef<C> {
+    /// A CRC-32 of the table contents, independent of the order of transitions and accepted states.
+    pub fn fingerprint(&self) -> u32 {
+        let mut transitions: Vec<(u64, u64, C)> = self
+            .state_lookup
+            .iter()
+            .map(|((char, prev_state), next_state)| (*prev_state, *next_state, *char))
+            .collect();
+        transitions.sort_unstable();
+        let char_len = (C::BITS / 8) as usize;
+        let mut accepted_state_vals = self.accepted_state_vals.clone();
+        accepted_state_vals.sort_unstable();
+        let mut bytes = Vec::new();
+        bytes.extend(self.first_state_val.to_le_bytes());
+        bytes.extend(self.largest_state_val.to_le_bytes());
+        bytes.extend((accepted_state_vals.len() as u64).to_le_bytes());
+        for state in accepted_state_vals {
+            bytes.extend(state.to_le_bytes());
+        }
+        for (prev_state, next_state, char) in transitions {
+            bytes.extend(prev_state.to_le_bytes());
+            bytes.extend(next_state.to_le_bytes());
+            bytes.extend(&char.to_u64().to_le_bytes()[..char_len]);
+        }
+        table_checksum(&bytes)
+    }
+
+    /// Like [`Self::fingerprint`], but a field element for pinning the table in a circuit, e.g.
+    /// as a public input: `hasher`, such as a Poseidon sponge, hashes the first and largest
+    /// states, the sorted accepted states and the sorted `(prev_state, char, next_state)`
+    /// transitions, each list prefixed with its length. The order of the rows does not matter.
+    pub fn commitment<F: PrimeField>(&self, hasher: impl Fn(&[F]) -> F) -> F {
+        let mut transitions: Vec<(u64, C, u64)> = self
+            .state_lookup
+            .iter()
+            .map(|((char, prev_state), next_state)| (*prev_state, *char, *next_state))
+            .collect();
+        transitions.sort_unstable();
+        let mut accepted_state_vals = self.accepted_state_vals.clone();
+        accepted_state_vals.sort_unstable();
+        let mut values = vec![
+            F::from(self.first_state_val),
+            F::from(self.largest_state_val),
+            F::from(accepted_state_vals.len() as u64),
+        ];
+        values.extend(accepted_state_vals.into_iter().map(F::from));
+        values.push(F::from(transitions.len() as u64));
+        for (prev_state, char, next_state) in transitions {
+            values.extend([
+                F::from(prev_state),
+                F::from(char.to_u64()),
+                F::from(next_state),
+            ]);
+        }
+        hasher(&values)
+    }
+
+    /// The state reached from `state` on `char`, or `None` if the table has no such transition.
+    pub fn next_state(&self, state: u64, char: C) -> Option<u64> {
+        self.state_lookup.get(&(char, state)).copied()
+    }
+
+    // The witness states for `characters`. If the table rejects the input at some offset, the
+    // remaining states are set to the first state, so the lookup at that row fails; proving
+    // that an input does not match needs a total table (`RegexDef::with_trap_state`).
+    pub(crate) fn witness_states(&self, characters: &[C]) -> Vec<u64> {
+        let mut states = vec![self.first_state_val];
+        for char in characters.iter() {
+            match self.next_state(*states.last().unwrap(), *char) {
+                Some(next_state) => states.push(next_state),
+                None => break,
+            }
+        }
+        states.resize(characters.len() + 1, self.first_state_val);
+        states
+    }
+
+    /// Checks host-side that `states` is a witness the transition lookup accepts for `characters`:
+    /// it starts in `first_state_val` and every `(states[i], characters[i], states[i + 1])` is a
+    /// transition of the table. Whether the last state is accepted is not checked. Returns an
+    /// error at the first bad state.
+    pub fn check_witness(&self, characters: &[C], states: &[u64]) -> Result<(), RegexError> {
+        if states.len() != characters.len() + 1 {
+            return Err(RegexError::WitnessLength {
+                expected: characters.len() + 1,
+                actual: states.len(),
+            });
+        }
+        if states[0] != self.first_state_val {
+            return Err(RegexError::InvalidWitness {
+                index: 0,
+                expected: Some(self.first_state_val),
+                actual: states[0],
+            });
+        }
+        for (idx, char) in characters.iter().enumerate() {
+            let expected = self.next_state(states[idx], *char);
+            if expected != Some(states[idx + 1]) {
+                return Err(RegexError::InvalidWitness {
+                    index: idx + 1,
+                    expected,
+                    actual: states[idx + 1],
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// The distinct characters of the transitions, in increasing order: every other character
+    /// rejects from any state, so the input must be drawn from these.
+    pub fn alphabet(&self) -> Vec<C> {
+        let mut alphabet: Vec<C> = self.state_lookup.keys().map(|(char, _)| *char).collect();
+        alphabet.sort_unstable();
+        alphabet.dedup();
+        alphabet
+    }
+
+    /// Whether no input is accepted, i.e. no accepted state is reachable from `first_state_val`.
+    pub fn is_empty_language(&self) -> bool {
+        let mut reachable = HashSet::from([self.first_state_val]);
+        let mut stack = vec![self.first_state_val];
+        while let Some(state) = stack.pop() {
+            if self.accepted_state_vals.contains(&state) {
+                return false;
+            }
+            for ((_, prev_state), next_state) in self.state_lookup.iter() {
+                if *prev_state == state && reachable.insert(*next_state) {
+                    stack.push(*next_state);
+                }
+            }
+        }
+        true
+    }
+
+    /// Checks that some input is accepted, so that a matching proof can be generated at all.
+    pub fn validate(&self) -> Result<(), RegexError> {
+        if self.is_empty_language() {
+            return Err(RegexError::EmptyLanguage);
+        }
+        Ok(())
+    }
+
+    /// The state value that [`RegexDef::with_trap_state`] and [`RegexDef::with_prefix_match`] add.
+    pub fn trap_state_val(&self) -> u64 {
+        self.largest_state_val + 1
+    }
+
+    /// Returns a copy where every state value is increased by `offset`, e.g. to give the states
+    /// of several regexes sharing a table disjoint values.
+    pub fn with_state_offset(&self, offset: u64) -> Self {
+        Self {
+            state_lookup: self
+                .state_lookup
+                .iter()
+                .map(|((char, prev_state), next_state)| {
+                    ((*char, prev_state + offset), next_state + offset)
+                })
+                .collect(),
+            first_state_val: self.first_state_val + offset,
+            accepted_state_vals: self
+                .accepted_state_vals
+                .iter()
+                .map(|state| state + offset)
+                .collect(),
+            largest_state_val: self.largest_state_val + offset,
+        }
+    }
+
+    /// Returns a copy where every state value `s` is replaced by `2 * s + is_accepted(s)`, so
+    /// whether a state is accepting can be read from its value alone (see [`RegexDef::decode_state`]).
+    /// Padding rows keep the value 0 as long as 0 is not an accepting state. The encoded values
+    /// need one more bit than the originals; a packed `(state, char, next_state)` encoding must
+    /// size its state fields for `largest_state_val` of the encoded definition so they don't overlap.
+    pub fn encode_accept_flags(&self) -> Self {
+        let encode = |state: u64| 2 * state + self.accepted_state_vals.contains(&state) as u64;
+        Self {
+            state_lookup: self
+                .state_lookup
+                .iter()
+                .map(|((char, prev_state), next_state)| {
+                    ((*char, encode(*prev_state)), encode(*next_state))
+                })
+                .collect(),
+            first_state_val: encode(self.first_state_val),
+            accepted_state_vals: self
+                .accepted_state_vals
+                .iter()
+                .map(|state| encode(*state))
+                .collect(),
+            largest_state_val: 2 * self.largest_state_val + 1,
+        }
+    }
+
+    /// Returns a copy where every state of the table is accepted, so that any input with a
+    /// valid state sequence passes, e.g. for a scan whose matches are counted on the states
+    /// instead (see `RegexCheckConfig::configure_match_count`).
+    pub fn accepting_all(&self) -> Self {
+        let mut accepted_state_vals: Vec<u64> = self
+            .state_lookup
+            .iter()
+            .flat_map(|((_, prev_state), next_state)| [*prev_state, *next_state])
+            .chain([self.first_state_val])
+            .collect();
+        accepted_state_vals.sort_unstable();
+        accepted_state_vals.dedup();
+        Self {
+            state_lookup: self.state_lookup.clone(),
+            first_state_val: self.first_state_val,
+            accepted_state_vals,
+            largest_state_val: self.largest_state_val,
+        }
+    }
+}
+
+// Reads the little-endian fields of the binary table format.
+struct ByteReader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> ByteReader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], RegexError> {
+        if self.bytes.len() < len {
+            return Err(RegexError::InvalidTable("unexpected end of table"));
+        }
+        let (head, tail) = self.bytes.split_at(len);
+        self.bytes = tail;
+        Ok(head)
+    }
+
+    fn u16(&mut self) -> Result<u16, RegexError> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> Result<u32, RegexError> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Result<u64, RegexError> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_transition() {
+        // "a", then any character except "x" leads to the accepted state; "x" goes back to the start.
+        let regex_def = RegexDef::read_from_text("./test_regexes/default_test_lookup.txt");
+        assert_eq!(regex_def.state_lookup.get(&(b'a', 0)), Some(&1));
+        assert_eq!(regex_def.state_lookup.get(&(b'x', 1)), Some(&0));
+        for char in [0, b'b', b'@', b'~', 0xff] {
+            assert_eq!(regex_def.state_lookup.get(&(char, 1)), Some(&2));
+        }
+        assert_eq!(regex_def.state_lookup.get(&(b'b', 0)), None);
+        assert_eq!(regex_def.state_lookup.len(), 257);
+    }
+
+    #[test]
+    fn test_accept_flag_encoding() {
+        let regex_def = RegexDef::read_from_text("./test_regexes/regex_test_lookup.txt");
+        let encoded = regex_def.encode_accept_flags();
+        let mut state = encoded.first_state_val;
+        for char in "email was meant for @y".bytes() {
+            state = encoded.state_lookup[&(char, state)];
+            assert!(!RegexDef::decode_state(state).1);
+        }
+        state = encoded.state_lookup[&(b'.', state)];
+        let (decoded, accepted) = RegexDef::decode_state(state);
+        assert!(accepted);
+        assert_eq!(decoded, regex_def.accepted_state_vals[0]);
+        assert!(encoded.accepted_state_vals.contains(&state));
+    }
+
+    #[test]
+    fn test_empty_language() {
+        let regex_def = RegexDef::read_from_text("./test_regexes/regex_test_lookup.txt");
+        assert!(!regex_def.is_empty_language());
+        assert_eq!(regex_def.validate(), Ok(()));
+
+        // An accepted state that no transition leads to.
+        let unreachable = RegexDef {
+            accepted_state_vals: vec![regex_def.largest_state_val + 1],
+            ..regex_def
+        };
+        assert!(unreachable.is_empty_language());
+        assert_eq!(unreachable.validate(), Err(RegexError::EmptyLanguage));
+    }
+
+    #[test]
+    fn test_wide_char_table() {
+        // "αβ" over UTF-16 code units.
+        let wide = RegexDef::<u16> {
+            state_lookup: HashMap::from([((0x3b1, 1), 2), ((0x3b2, 2), 3)]),
+            first_state_val: 1,
+            accepted_state_vals: vec![3],
+            largest_state_val: 3,
+        };
+        let input: Vec<u16> = "αβ".encode_utf16().collect();
+        assert_eq!(wide.witness_states(&input), vec![1, 2, 3]);
+        assert_eq!(wide.witness_states(&[0xb1, 0xb2]), vec![1, 1, 1]);
+        assert_eq!(wide.next_state(2, 0x3b2), Some(3));
+        assert_eq!(wide.validate(), Ok(()));
+
+        // The same table truncated to the low bytes has a different fingerprint.
+        let narrow = RegexDef::<u8> {
+            state_lookup: HashMap::from([((0xb1, 1), 2), ((0xb2, 2), 3)]),
+            first_state_val: 1,
+            accepted_state_vals: vec![3],
+            largest_state_val: 3,
+        };
+        assert_ne!(wide.fingerprint(), narrow.fingerprint());
+        assert_eq!(0x3b1u16.fold_ascii_case(), 0x3b1);
+        assert_eq!(u16::from(b'A').fold_ascii_case(), u16::from(b'a'));
+    }
+
+    #[test]
+    fn test_next_state() {
+        let regex_def = RegexDef::read_from_text("./test_regexes/regex_test_lookup.txt");
+        for ((char, state), next_state) in regex_def.state_lookup.iter() {
+            assert_eq!(regex_def.next_state(*state, *char), Some(*next_state));
+        }
+        let first = regex_def.first_state_val;
+        assert!(regex_def.next_state(first, b'e').is_some());
+        assert_eq!(regex_def.next_state(first, b'x'), None);
+        assert_eq!(
+            regex_def.next_state(regex_def.largest_state_val + 1, b'e'),
+            None
+        );
+    }
+
+    #[test]
+    fn test_gen_states_anchored() {
+        let regex_def = RegexDef::read_from_text("./test_regexes/regex_test_lookup.txt");
+        let states = regex_def.gen_states(b"email was meant for @y.").unwrap();
+        assert_eq!(states.len(), 24);
+        assert!(regex_def
+            .accepted_state_vals
+            .contains(states.last().unwrap()));
+
+        let err = regex_def.gen_states(b"email was sent").unwrap_err();
+        let states = regex_def.gen_states(b"email was ").unwrap();
+        assert_eq!(
+            err,
+            RegexError::NoTransition {
+                offset: 10,
+                char: b's',
+                state: *states.last().unwrap(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_pad_states_and_chars() {
+        let regex_def = RegexDef::read_from_text("./test_regexes/regex_test_lookup.txt");
+        let input = b"email was meant for @y.";
+        let states = regex_def.gen_states(input).unwrap();
+        let padded = pad_states(&states, 30, 0);
+        assert_eq!(padded.len(), 31);
+        assert_eq!(padded[..24], states[..]);
+        assert_eq!(padded[24..], [0; 7]);
+        let chars = pad_chars(input, 30, 0);
+        assert_eq!(chars.len(), 30);
+        assert_eq!(chars[..23], input[..]);
+        assert_eq!(chars[23..], [0; 7]);
+        // An input that fills the rows keeps all its states, the last on the extra row.
+        assert_eq!(pad_states(&states, 23, 0), states);
+        assert_eq!(pad_chars(input, 23, 0), input);
+        assert_eq!(pad_states(&[1], 2, 9), vec![1, 9, 9]);
+        assert_eq!(pad_chars(b"", 2, b'#'), b"##");
+    }
+
+    #[test]
+    #[should_panic(expected = "do not fit")]
+    fn test_pad_states_too_long() {
+        pad_states(&[1, 2, 3], 1, 0);
+    }
+
+    #[test]
+    fn test_alphabet() {
+        let regex_def = RegexDef::read_from_text("./test_regexes/regex_test_lookup.txt");
+        let expected: Vec<u8> = b" .0123456789@"
+            .iter()
+            .copied()
+            .chain(b'A'..=b'Z')
+            .chain([b'_'])
+            .chain(b'a'..=b'z')
+            .collect();
+        assert_eq!(regex_def.alphabet(), expected);
+        let regex_def = crate::compile_regex("(y|[a-c]x)+").unwrap().regex_def();
+        assert_eq!(regex_def.alphabet(), b"abcxy");
+    }
+
+    #[test]
+    fn test_trace_path() {
+        let regex_def = crate::compile_regex("ab*c").unwrap().regex_def();
+        let path = regex_def.trace_path(b"abbc").unwrap();
+        assert_eq!(
+            path,
+            vec![(1, b'a', 2), (2, b'b', 2), (2, b'b', 2), (2, b'c', 3)]
+        );
+        assert_eq!(regex_def.accepted_state_vals, vec![3]);
+        let states = regex_def.gen_states(b"abbc").unwrap();
+        assert_eq!(
+            path.iter().map(|(state, _, _)| *state).collect::<Vec<_>>(),
+            states[..4]
+        );
+
+        assert_eq!(
+            regex_def.trace_path(b"abb"),
+            Err(RegexError::NotAccepted { state: 2 })
+        );
+        assert!(matches!(
+            regex_def.trace_path(b"ac!"),
+            Err(RegexError::NoTransition { offset: 2, .. })
+        ));
+    }
+
+    #[test]
+    fn test_check_witness() {
+        let regex_def = RegexDef::read_from_text("./test_regexes/regex_test_lookup.txt");
+        let characters = b"email was meant for @y.";
+        let mut states = regex_def.gen_states(characters).unwrap();
+        assert_eq!(regex_def.check_witness(characters, &states), Ok(()));
+
+        assert_eq!(
+            regex_def.check_witness(characters, &states[..10]),
+            Err(RegexError::WitnessLength {
+                expected: 24,
+                actual: 10,
+            })
+        );
+
+        let expected = states[5];
+        states[5] = regex_def.largest_state_val + 1;
+        assert_eq!(
+            regex_def.check_witness(characters, &states),
+            Err(RegexError::InvalidWitness {
+                index: 5,
+                expected: Some(expected),
+                actual: regex_def.largest_state_val + 1,
+            })
+        );
+
+        // A corrupted character is reported at the state after it.
+        states[5] = expected;
+        let mut corrupted = *characters;
+        corrupted[2] = b'x';
+        assert_eq!(
+            regex_def.check_witness(&corrupted, &states),
+            Err(RegexError::InvalidWitness {
+                index: 3,
+                expected: None,
+                actual: states[3],
+            })
+        );
+
+        states[0] += 1;
+        assert!(matches!(
+            regex_def.check_witness(characters, &states),
+            Err(RegexError::InvalidWitness { index: 0, .. })
+        ));
+    }
+
+    #[test]
+    fn test_prefix_match() {
+        let regex_def = crate::compile_regex("ab|abcd").unwrap().regex_def();
+        let prefix = regex_def.with_prefix_match();
+        let tail = regex_def.trap_state_val();
+        assert_eq!(prefix.largest_state_val, tail);
+        for input in ["ab", "abc", "abcd", "abxyz", "ab\0"] {
+            let states = prefix.gen_states(input.as_bytes()).unwrap();
+            assert!(
+                prefix.accepted_state_vals.contains(states.last().unwrap()),
+                "{}",
+                input
+            );
+        }
+        for input in ["", "a", "ba", "xab"] {
+            let accepted = match prefix.gen_states(input.as_bytes()) {
+                Ok(states) => prefix.accepted_state_vals.contains(states.last().unwrap()),
+                Err(_) => false,
+            };
+            assert!(!accepted, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_state_offset() {
+        let regex_def = RegexDef::read_from_text("./test_regexes/regex_test_lookup.txt");
+        let shifted = regex_def.with_state_offset(100);
+        assert_eq!(shifted.first_state_val, regex_def.first_state_val + 100);
+        assert_eq!(shifted.largest_state_val, regex_def.largest_state_val + 100);
+        let input = b"email was meant for @y.";
+        let states = regex_def.gen_states(input).unwrap();
+        let shifted_states = shifted.gen_states(input).unwrap();
+        for (state, shifted_state) in states.iter().zip(shifted_states.iter()) {
+            assert_eq!(state + 100, *shifted_state);
+        }
+        assert!(shifted
+            .accepted_state_vals
+            .contains(shifted_states.last().unwrap()));
+    }
+
+    #[test]
+    fn test_commitment() {
+        use halo2_base::halo2_proofs::halo2curves::bn256::Fr;
+
+        // Horner's rule stands in for a real hash; it depends on the order of the values.
+        let hasher = |values: &[Fr]| {
+            values
+                .iter()
+                .fold(Fr::zero(), |acc, value| acc * Fr::from(1 << 20) + value)
+        };
+        let regex_def = crate::compile_regex("[a-c]+@(x|yz)").unwrap().regex_def();
+        let mut rows: Vec<_> = regex_def.state_lookup.iter().collect();
+        rows.sort();
+        let reordered = RegexDef {
+            state_lookup: rows.into_iter().rev().map(|(k, v)| (*k, *v)).collect(),
+            accepted_state_vals: regex_def
+                .accepted_state_vals
+                .iter()
+                .rev()
+                .copied()
+                .collect(),
+            ..regex_def.clone()
+        };
+        assert_eq!(reordered.commitment(hasher), regex_def.commitment(hasher));
+
+        let mut fewer = regex_def.clone();
+        fewer
+            .state_lookup
+            .remove(&(b'c', regex_def.first_state_val));
+        assert_ne!(fewer.commitment(hasher), regex_def.commitment(hasher));
+        let mut accepting = regex_def.clone();
+        accepting
+            .accepted_state_vals
+            .push(regex_def.first_state_val);
+        assert_ne!(accepting.commitment(hasher), regex_def.commitment(hasher));
+    }
+
+    #[test]
+    fn test_accepting_all() {
+        let regex_def = crate::compile_regex("ab").unwrap().regex_def();
+        let accepting = regex_def.accepting_all();
+        assert_eq!(accepting.state_lookup, regex_def.state_lookup);
+        assert_eq!(accepting.accepted_state_vals, vec![1, 2, 3]);
+        let states = accepting.gen_states(b"a").unwrap();
+        assert!(accepting
+            .accepted_state_vals
+            .contains(states.last().unwrap()));
+    }
+
+    #[test]
+    fn test_gen_states_total() {
+        let regex_def = RegexDef::read_from_text("./test_regexes/regex_test_lookup.txt");
+        let total = regex_def.with_trap_state();
+        let trap = regex_def.trap_state_val();
+        assert_eq!(total.largest_state_val, trap);
+        assert!(!total.accepted_state_vals.contains(&trap));
+        // Every state of the table and the trap state get all 256 transitions, and no value
+        // that is not a state does.
+        let states: HashSet<u64> = regex_def
+            .state_lookup
+            .iter()
+            .flat_map(|((_, prev_state), next_state)| [*prev_state, *next_state])
+            .chain([regex_def.first_state_val])
+            .collect();
+        assert_eq!(total.state_lookup.len(), (states.len() + 1) * 256);
+        // Transitions of the original table are kept.
+        assert_eq!(
+            total.gen_states(b"email was meant for @y.").unwrap(),
+            regex_def.gen_states(b"email was meant for @y.").unwrap()
+        );
+
+        let states = total.gen_states(b"email was sent for @y.").unwrap();
+        assert_eq!(states.len(), 23);
+        assert_eq!(
+            states[..11],
+            regex_def.gen_states(b"email was ").unwrap()[..]
+        );
+        assert!(states[11..].iter().all(|state| *state == trap));
+
+        // "ab" has states 1 to 3, so there is no state 0 to add transitions to.
+        let regex_def = crate::compile_regex("ab").unwrap().regex_def();
+        assert_eq!(regex_def.first_state_val, 1);
+        let total = regex_def.with_trap_state();
+        assert_eq!(total.next_state(0, b'a'), None);
+        assert_eq!(total.state_lookup.len(), 4 * 256);
+    }
+
+    #[test]
+    fn test_gen_states_total_witness() {
+        let regex_def = RegexDef::read_from_text("./test_regexes/regex_test_lookup.txt");
+        let total = regex_def.with_trap_state();
+        let matched = b"email was meant for @y.";
+        assert_eq!(
+            regex_def.gen_states_total(matched),
+            regex_def.gen_states(matched).unwrap()
+        );
+
+        // A non-match witness lands in the trap state, which is not accepted, and satisfies
+        // the total table.
+        let rejected = b"email was sent for @y.";
+        let states = regex_def.gen_states_total(rejected);
+        assert_eq!(states, total.gen_states(rejected).unwrap());
+        assert_eq!(*states.last().unwrap(), regex_def.trap_state_val());
+        assert!(!total.accepted_state_vals.contains(states.last().unwrap()));
+        assert!(total.check_witness(rejected, &states).is_ok());
+        assert!(regex_def.check_witness(rejected, &states).is_err());
+        // A prefix of a match is rejected without reaching the trap state.
+        let states = regex_def.gen_states_total(b"email");
+        assert!(!regex_def
+            .accepted_state_vals
+            .contains(states.last().unwrap()));
+    }
+
+    #[test]
+    fn test_from_text_bytes_checksum() {
+        assert_eq!(table_checksum(b"123456789"), 0xcbf4_3926);
+        let bytes = std::fs::read("./test_regexes/regex_test_lookup.txt").unwrap();
+        let checksum = table_checksum(&bytes);
+        let regex_def = RegexDef::from_text_bytes(&bytes, checksum).unwrap();
+        let expected = RegexDef::read_from_text("./test_regexes/regex_test_lookup.txt");
+        assert_eq!(regex_def.state_lookup, expected.state_lookup);
+        assert_eq!(regex_def.fingerprint(), expected.fingerprint());
+
+        // Change the character of the last transition.
+        let mut tampered = bytes.clone();
+        let pos = bytes.iter().rposition(|byte| *byte == b'\n').unwrap() - 1;
+        tampered[pos] = if bytes[pos] == b'1' { b'2' } else { b'1' };
+        assert_eq!(
+            RegexDef::from_text_bytes(&tampered, checksum).unwrap_err(),
+            RegexError::ChecksumMismatch {
+                expected: checksum,
+                actual: table_checksum(&tampered),
+            }
+        );
+    }
+
+    #[test]
+    fn test_binary_table_round_trip() {
+        let regex_def = RegexDef::read_from_text("./test_regexes/regex_test_lookup.txt");
+        let bytes = regex_def.to_bytes();
+        assert_eq!(&bytes[..4], b"RGXT");
+        let decoded = RegexDef::from_bytes(&bytes).unwrap();
+        assert_eq!(decoded.state_lookup, regex_def.state_lookup);
+        assert_eq!(decoded.first_state_val, regex_def.first_state_val);
+        assert_eq!(decoded.accepted_state_vals, regex_def.accepted_state_vals);
+        assert_eq!(decoded.largest_state_val, regex_def.largest_state_val);
+        assert_eq!(decoded.to_bytes(), bytes);
+
+        assert_eq!(
+            RegexDef::from_bytes(&bytes[..bytes.len() - 1]).unwrap_err(),
+            RegexError::InvalidTable("unexpected end of table")
+        );
+        let mut tampered = bytes.clone();
+        tampered[4] = 2;
+        assert_eq!(
+            RegexDef::from_bytes(&tampered).unwrap_err(),
+            RegexError::InvalidTable("unsupported version")
+        );
+        assert_eq!(
+            RegexDef::from_bytes(b"{\"first\": 0}").unwrap_err(),
+            RegexError::InvalidTable("bad magic")
+        );
+    }
+
+    #[test]
+    fn test_binary_table_matches_text() {
+        let regex_def = crate::compile_regex(r"[a-z0-9]{1,64}@[a-z0-9]{1,64}\.(com|org|net)")
+            .unwrap()
+            .regex_def();
+        let mut text = format!(
+            "{}\n{}\n{}\n",
+            regex_def.first_state_val,
+            regex_def
+                .accepted_state_vals
+                .iter()
+                .map(|state| state.to_string())
+                .collect::<Vec<_>>()
+                .join(" "),
+            regex_def.largest_state_val
+        );
+        for ((char, prev_state), next_state) in regex_def.state_lookup.iter() {
+            text += &format!("{} {} {}\n", prev_state, next_state, char);
+        }
+        let bytes = regex_def.to_bytes();
+
+        let from_text = RegexDef::read_from_reader(text.as_bytes());
+        let from_binary = RegexDef::from_bytes(&bytes).unwrap();
+        assert_eq!(from_text.state_lookup, from_binary.state_lookup);
+    }
+
+    #[test]
+    fn test_read_missing_or_empty_table() {
+        let path = "./test_regexes/no_such_table.txt";
+        match RegexDef::try_read_from_text(path) {
+            Err(RegexError::Io { path: err_path, .. }) => assert_eq!(err_path, path),
+            other => panic!("unexpected {:?}", other),
+        }
+
+        let path = std::env::temp_dir().join("halo2_regex_empty_table.txt");
+        std::fs::write(&path, " \n").unwrap();
+        let path = path.to_str().unwrap();
+        assert_eq!(
+            RegexDef::try_read_from_text(path).unwrap_err(),
+            RegexError::EmptyTable {
+                path: path.to_string()
+            }
+        );
+        std::fs::remove_file(path).unwrap();
+    }
 }
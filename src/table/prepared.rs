@@ -0,0 +1,190 @@
+use crate::{RegexDef, RegexError};
+
+/// A [`RegexDef`] with its transitions in a dense array indexed by state and byte, built once
+/// for matching many inputs against one regex: each character is an array access instead of a
+/// hash map lookup. The array has 256 entries per state value up to the largest one, so the
+/// states should be numbered compactly, as [`crate::compile_regex`] does.
+#[derive(Debug, Clone)]
+pub struct PreparedRegex {
+    // The next state of `state` on `char` at `state * 256 + char`.
+    transitions: Vec<Option<u64>>,
+    // Whether each state value is accepted.
+    accepted: Vec<bool>,
+    first_state_val: u64,
+}
+
+impl PreparedRegex {
+    pub fn new(regex_def: &RegexDef) -> Self {
+        let largest_state_val = regex_def
+            .state_lookup
+            .iter()
+            .flat_map(|((_, prev_state), next_state)| [*prev_state, *next_state])
+            .chain(regex_def.accepted_state_vals.iter().copied())
+            .chain([regex_def.first_state_val, regex_def.largest_state_val])
+            .max()
+            .unwrap() as usize;
+        let mut transitions = vec![None; (largest_state_val + 1) * 256];
+        for ((char, prev_state), next_state) in regex_def.state_lookup.iter() {
+            transitions[*prev_state as usize * 256 + *char as usize] = Some(*next_state);
+        }
+        let mut accepted = vec![false; largest_state_val + 1];
+        for state in regex_def.accepted_state_vals.iter() {
+            accepted[*state as usize] = true;
+        }
+        Self {
+            transitions,
+            accepted,
+            first_state_val: regex_def.first_state_val,
+        }
+    }
+
+    /// Like [`RegexDef::next_state`].
+    pub fn next_state(&self, state: u64, char: u8) -> Option<u64> {
+        *self.transitions.get(state as usize * 256 + char as usize)?
+    }
+
+    /// The characters with a transition from `state`, in increasing order, e.g. to suggest the
+    /// characters that may follow a partial input. Empty for a state past the table.
+    pub fn valid_next(&self, state: u64) -> Vec<u8> {
+        (0..=u8::MAX)
+            .filter(|char| self.next_state(state, *char).is_some())
+            .collect()
+    }
+
+    /// Like [`RegexDef::gen_states`].
+    pub fn gen_states(&self, characters: &[u8]) -> Result<Vec<u64>, RegexError> {
+        let mut states = Vec::with_capacity(characters.len() + 1);
+        let mut state = self.first_state_val;
+        states.push(state);
+        for (offset, char) in characters.iter().enumerate() {
+            state = self
+                .next_state(state, *char)
+                .ok_or(RegexError::NoTransition {
+                    offset,
+                    char: *char,
+                    state,
+                })?;
+            states.push(state);
+        }
+        Ok(states)
+    }
+
+    /// Like [`RegexDef::check_witness`].
+    pub fn check_witness(&self, characters: &[u8], states: &[u64]) -> Result<(), RegexError> {
+        if states.len() != characters.len() + 1 {
+            return Err(RegexError::WitnessLength {
+                expected: characters.len() + 1,
+                actual: states.len(),
+            });
+        }
+        if states[0] != self.first_state_val {
+            return Err(RegexError::InvalidWitness {
+                index: 0,
+                expected: Some(self.first_state_val),
+                actual: states[0],
+            });
+        }
+        for (idx, char) in characters.iter().enumerate() {
+            let expected = self.next_state(states[idx], *char);
+            if expected != Some(states[idx + 1]) {
+                return Err(RegexError::InvalidWitness {
+                    index: idx + 1,
+                    expected,
+                    actual: states[idx + 1],
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether the whole input is accepted.
+    pub fn is_match(&self, characters: &[u8]) -> bool {
+        let mut state = self.first_state_val;
+        for char in characters.iter() {
+            match self.next_state(state, *char) {
+                Some(next_state) => state = next_state,
+                None => return false,
+            }
+        }
+        self.accepted[state as usize]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compile_regex;
+
+    // Every input of up to `max_len` bytes of `alphabet`.
+    fn all_inputs(alphabet: &[u8], max_len: usize) -> Vec<Vec<u8>> {
+        let mut inputs = vec![vec![]];
+        let mut last = vec![vec![]];
+        for _ in 0..max_len {
+            last = last
+                .iter()
+                .flat_map(|input: &Vec<u8>| {
+                    alphabet.iter().map(move |char| {
+                        let mut input = input.clone();
+                        input.push(*char);
+                        input
+                    })
+                })
+                .collect();
+            inputs.extend(last.iter().cloned());
+        }
+        inputs
+    }
+
+    #[test]
+    fn test_prepared_regex() {
+        for pattern in ["ab+c", "(a|b)*@(b|c)?", "[a-c]{2,3}\\.", "a*"] {
+            let compiled = compile_regex(pattern).unwrap();
+            let regex_def = compiled.regex_def();
+            let prepared = PreparedRegex::new(&regex_def);
+            for input in all_inputs(b"abc@.", 5) {
+                assert_eq!(prepared.is_match(&input), compiled.is_match(&input));
+                let states = prepared.gen_states(&input);
+                assert_eq!(states, regex_def.gen_states(&input));
+                if let Ok(mut states) = states {
+                    assert_eq!(prepared.check_witness(&input, &states), Ok(()));
+                    *states.last_mut().unwrap() += 1;
+                    assert_eq!(
+                        prepared.check_witness(&input, &states),
+                        regex_def.check_witness(&input, &states)
+                    );
+                }
+            }
+        }
+        // A state past the table has no transitions.
+        let prepared = PreparedRegex::new(&compile_regex("a").unwrap().regex_def());
+        assert_eq!(prepared.next_state(100, b'a'), None);
+    }
+
+    #[test]
+    fn test_valid_next() {
+        let regex_def = compile_regex("(ab|c)[0-2]").unwrap().regex_def();
+        let prepared = PreparedRegex::new(&regex_def);
+        assert_eq!(prepared.valid_next(regex_def.first_state_val), b"ac");
+        let states = prepared.gen_states(b"a").unwrap();
+        assert_eq!(prepared.valid_next(states[1]), b"b");
+        let states = prepared.gen_states(b"c").unwrap();
+        assert_eq!(prepared.valid_next(states[1]), b"012");
+        // An accepting state without transitions, and a state past the table.
+        let states = prepared.gen_states(b"c1").unwrap();
+        assert!(prepared.valid_next(states[2]).is_empty());
+        assert!(prepared.valid_next(100).is_empty());
+    }
+
+    #[test]
+    fn test_prepared_regex_large_table() {
+        let regex_def = compile_regex(r"[a-z0-9]{1,64}@[a-z0-9]{1,64}\.(com|org|net)")
+            .unwrap()
+            .regex_def();
+        let prepared = PreparedRegex::new(&regex_def);
+        for i in 0..1000 {
+            let input = format!("user{}@mail{}.com", i, i * 7).into_bytes();
+            assert!(prepared.is_match(&input));
+            assert_eq!(prepared.gen_states(&input), regex_def.gen_states(&input));
+        }
+    }
+}
@@ -0,0 +1,320 @@
+use halo2_base::halo2_proofs::{
+    dev::MockProver,
+    halo2curves::bn256::Bn256,
+    plonk::Circuit,
+    poly::{commitment::Params, kzg::commitment::ParamsKZG},
+};
+use halo2_base::utils::{fe_to_biguint, PrimeField};
+
+use crate::{AssignedRegexResult, CompiledRegex, SubstrDef};
+
+/// Runs `MockProver` after checking `k` against `minimum_k`, e.g. from
+/// [`crate::RegexCheckConfig::minimum_k`]. An undersized `k` is reported up front with the `k`
+/// it needs instead of `MockProver`'s `NotEnoughRowsAvailable`.
+pub fn run_checked<F: PrimeField, C: Circuit<F>>(
+    k: u32,
+    minimum_k: u32,
+    circuit: &C,
+    instances: Vec<Vec<F>>,
+) -> Result<MockProver<F>, String> {
+    if k < minimum_k {
+        let message = format!(
+            "k = {} is too small for this circuit, which needs at least k = {}",
+            k, minimum_k
+        );
+        return Err(message);
+    }
+    MockProver::run(k, circuit, instances)
+        .map_err(|err| format!("MockProver::run failed with k = {}: {:?}", k, err))
+}
+
+/// Params of degree `k` for proving a circuit that passed `MockProver` with the same `k`, e.g.
+/// the one from [`crate::RegexCheckConfig::minimum_k`]. Both count the same usable rows, so a
+/// circuit that fits `MockProver` at `k` also fits params of degree `k`, and the keys must be
+/// generated with exactly those. `srs`, e.g. read from a trusted setup, is downsized to `k` if
+/// it is larger; a smaller one cannot hold the circuit.
+pub fn params_for_k(srs: &ParamsKZG<Bn256>, k: u32) -> Result<ParamsKZG<Bn256>, String> {
+    if srs.k() < k {
+        return Err(format!(
+            "the params have k = {}, but the circuit needs k = {}",
+            srs.k(),
+            k
+        ));
+    }
+    let mut params = srs.clone();
+    if params.k() > k {
+        params.downsize(k);
+    }
+    Ok(params)
+}
+
+/// The smallest `k` up to `max_k` for which `MockProver` runs `circuit` and verifies it, found
+/// by binary search, or `None` if even `max_k` fails. This measures how much headroom a `k` has
+/// and checks estimates like [`crate::RegexCheckConfig::minimum_k`]; it assumes that a circuit
+/// which fits into `2^k` rows also fits into more.
+pub fn empirical_minimum_k<F: PrimeField, C: Circuit<F>>(
+    circuit: &C,
+    instances: Vec<Vec<F>>,
+    max_k: u32,
+) -> Option<u32> {
+    let satisfied = |k: u32| match MockProver::run(k, circuit, instances.clone()) {
+        Ok(prover) => prover.verify().is_ok(),
+        Err(_) => false,
+    };
+    if !satisfied(max_k) {
+        return None;
+    }
+    // `satisfied(hi)` holds throughout.
+    let (mut lo, mut hi) = (1, max_k);
+    while lo < hi {
+        let mid = (lo + hi) / 2;
+        if satisfied(mid) {
+            hi = mid;
+        } else {
+            lo = mid + 1;
+        }
+    }
+    Some(hi)
+}
+
+/// Reads the input back from the assigned characters of `result`, without the padding rows, to
+/// check that the assignment is faithful. Returns `None` if the values are unknown, as during
+/// key generation, or if they are not the bytes of a UTF-8 string.
+pub fn assigned_string<F: PrimeField>(result: &AssignedRegexResult<F>) -> Option<String> {
+    let mut bytes = vec![];
+    for (char, padding) in result.characters.iter().zip(result.is_padding.iter()) {
+        let mut is_padding = None;
+        padding.value().map(|v| is_padding = Some(*v == F::one()));
+        let mut byte = None;
+        char.value()
+            .map(|v| byte = u8::try_from(fe_to_biguint(v)).ok());
+        match (is_padding?, byte) {
+            (true, _) => break,
+            (false, Some(byte)) => bytes.push(byte),
+            (false, None) => return None,
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+/// A plain reference for the substrings `SubstrMatchConfig::match_substrs` extracts from `input`
+/// with `substr_defs` of `compiled`, written independently of the circuit to cross-check its
+/// masked outputs: the bytes of the first maximal run of positions within
+/// `min_position..=max_position` whose `(state, next_state)` transition belongs to the
+/// definition. Returns `None` if no proof exists: `input` is not matched, or a run is longer
+/// than its `max_length`.
+pub fn reference_substrs(
+    input: &[u8],
+    compiled: &CompiledRegex,
+    substr_defs: &[SubstrDef],
+) -> Option<Vec<Vec<u8>>> {
+    if !compiled.is_match(input) {
+        return None;
+    }
+    let mut states = vec![compiled.dfa.start];
+    for byte in input.iter() {
+        let state = *states.last().unwrap();
+        states.push(compiled.dfa.states[state].edges[byte]);
+    }
+    let mut substrs = vec![];
+    for substr_def in substr_defs.iter() {
+        let in_substr: Vec<bool> = (0..input.len())
+            .map(|position| {
+                let in_window = substr_def.min_position <= position as u64
+                    && position as u64 <= substr_def.max_position;
+                in_window
+                    && substr_def.valid_state_transitions().contains(&(
+                        CompiledRegex::state_val(states[position]),
+                        CompiledRegex::state_val(states[position + 1]),
+                    ))
+            })
+            .collect();
+        let substr: Vec<u8> = match in_substr.iter().position(|flag| *flag) {
+            Some(start) => input[start..]
+                .iter()
+                .zip(in_substr[start..].iter())
+                .take_while(|(_, flag)| **flag)
+                .map(|(byte, _)| *byte)
+                .collect(),
+            None => vec![],
+        };
+        if substr.len() > substr_def.max_length {
+            return None;
+        }
+        substrs.push(substr);
+    }
+    Some(substrs)
+}
+
+#[cfg(test)]
+mod tests {
+    use halo2_base::halo2_proofs::{
+        circuit::{Layouter, SimpleFloorPlanner},
+        halo2curves::bn256::{Bn256, Fr},
+        plonk::{keygen_vk, ConstraintSystem, Error},
+        poly::kzg::commitment::ParamsKZG,
+    };
+    use rand::rngs::OsRng;
+    use std::cell::RefCell;
+
+    use super::*;
+    use crate::{compile_regex, extract_captures, RegexCheckConfig, RegexDef};
+
+    const SAMPLE_LOOKUP: &str = "./test_regexes/regex_test_lookup.txt";
+
+    crate::regex_circuit!(
+        SampleRegexCircuit,
+        RegexDef::read_from_text(SAMPLE_LOOKUP),
+        128
+    );
+
+    #[test]
+    fn test_run_checked() {
+        let minimum_k =
+            RegexCheckConfig::<Fr>::minimum_k(&RegexDef::read_from_text(SAMPLE_LOOKUP), 128);
+        let circuit = SampleRegexCircuit::<Fr>::new(b"email was meant for @y.".to_vec());
+        let message = run_checked(minimum_k - 1, minimum_k, &circuit, vec![]).unwrap_err();
+        assert_eq!(
+            message,
+            format!(
+                "k = {} is too small for this circuit, which needs at least k = {}",
+                minimum_k - 1,
+                minimum_k
+            )
+        );
+        run_checked(minimum_k, minimum_k, &circuit, vec![])
+            .unwrap()
+            .assert_satisfied();
+    }
+
+    #[test]
+    fn test_empirical_minimum_k() {
+        let minimum_k =
+            RegexCheckConfig::<Fr>::minimum_k(&RegexDef::read_from_text(SAMPLE_LOOKUP), 128);
+        let circuit = SampleRegexCircuit::<Fr>::new(b"email was meant for @y.".to_vec());
+        assert_eq!(
+            empirical_minimum_k(&circuit, vec![], minimum_k + 2),
+            Some(minimum_k)
+        );
+        assert_eq!(empirical_minimum_k(&circuit, vec![], minimum_k - 1), None);
+
+        // A rejected input fails with every `k`.
+        let circuit = SampleRegexCircuit::<Fr>::new(b"email was meant for @".to_vec());
+        assert_eq!(empirical_minimum_k(&circuit, vec![], minimum_k + 2), None);
+    }
+
+    #[test]
+    fn test_params_for_k() {
+        let srs = ParamsKZG::<Bn256>::setup(8, OsRng);
+        assert_eq!(params_for_k(&srs, 6).unwrap().k(), 6);
+        assert_eq!(params_for_k(&srs, 8).unwrap().k(), 8);
+        assert_eq!(
+            params_for_k(&srs, 9).unwrap_err(),
+            "the params have k = 8, but the circuit needs k = 9"
+        );
+    }
+
+    #[derive(Default, Clone, Debug)]
+    struct RoundTripCircuit {
+        characters: Vec<u8>,
+        // The string read back on each synthesis.
+        assigned: RefCell<Vec<Option<String>>>,
+    }
+
+    impl Circuit<Fr> for RoundTripCircuit {
+        type Config = RegexCheckConfig<Fr>;
+        type FloorPlanner = SimpleFloorPlanner;
+
+        fn without_witnesses(&self) -> Self {
+            Self::default()
+        }
+
+        fn configure(meta: &mut ConstraintSystem<Fr>) -> Self::Config {
+            let regex_def = compile_regex("[a-z ]+").unwrap().regex_def();
+            RegexCheckConfig::configure(meta, regex_def, 16)
+        }
+
+        fn synthesize(
+            &self,
+            config: Self::Config,
+            mut layouter: impl Layouter<Fr>,
+        ) -> Result<(), Error> {
+            config.load(&mut layouter)?;
+            layouter.assign_region(
+                || "regex",
+                |mut region| {
+                    let result = config.assign_values(&mut region, &self.characters)?;
+                    self.assigned.borrow_mut().push(assigned_string(&result));
+                    Ok(())
+                },
+            )
+        }
+    }
+
+    #[test]
+    fn test_assigned_string() {
+        let circuit = RoundTripCircuit {
+            characters: b"hello world".to_vec(),
+            ..Default::default()
+        };
+        MockProver::run(7, &circuit, vec![])
+            .unwrap()
+            .assert_satisfied();
+        assert_eq!(
+            circuit.assigned.borrow().last(),
+            Some(&Some("hello world".to_string()))
+        );
+
+        // Key generation assigns unknown values.
+        let params = ParamsKZG::<Bn256>::setup(7, OsRng);
+        let circuit = RoundTripCircuit::default();
+        keygen_vk(&params, &circuit).unwrap();
+        assert_eq!(circuit.assigned.borrow().last(), Some(&None));
+    }
+
+    #[test]
+    fn test_reference_substrs() {
+        let compiled =
+            compile_regex(r"email was meant for @([a-zA-Z0-9_]+)((?: and [a-z]+)*)\.").unwrap();
+        let input = b"email was meant for @yajk and kaiew and oiewk.";
+        let defs = |name: (usize, u64, u64), and: (usize, u64, u64)| {
+            vec![
+                compiled.substr_def(0, name.0, name.1, name.2),
+                compiled.substr_def(1, and.0, and.1, and.2),
+            ]
+        };
+        let substrs = reference_substrs(input, &compiled, &defs((8, 0, 127), (32, 0, 127)));
+        assert_eq!(
+            substrs,
+            Some(vec![b"yajk".to_vec(), b" and kaiew and oiewk".to_vec()])
+        );
+        assert_eq!(substrs, extract_captures(input, &compiled));
+        assert_eq!(
+            reference_substrs(
+                b"email was meant for @y.",
+                &compiled,
+                &defs((8, 0, 127), (32, 0, 127))
+            ),
+            Some(vec![b"y".to_vec(), vec![]])
+        );
+
+        // Only the positions in the window are extracted.
+        assert_eq!(
+            reference_substrs(input, &compiled, &defs((8, 0, 22), (32, 30, 127))),
+            Some(vec![b"ya".to_vec(), b"kaiew and oiewk".to_vec()])
+        );
+        // A capture longer than `max_length` or an unmatched input cannot be proven.
+        assert_eq!(
+            reference_substrs(input, &compiled, &defs((3, 0, 127), (32, 0, 127))),
+            None
+        );
+        assert_eq!(
+            reference_substrs(
+                b"email was meant for @.",
+                &compiled,
+                &defs((8, 0, 127), (32, 0, 127))
+            ),
+            None
+        );
+    }
+}